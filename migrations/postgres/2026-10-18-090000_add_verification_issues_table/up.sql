@@ -0,0 +1,7 @@
+CREATE TABLE verification_issues (
+    id SERIAL PRIMARY KEY NOT NULL,
+    height INTEGER NOT NULL,
+    block_hash TEXT,
+    kind TEXT NOT NULL,
+    message TEXT NOT NULL
+);
@@ -0,0 +1,2 @@
+DROP TABLE inputs;
+DROP TABLE outputs;
@@ -0,0 +1 @@
+DROP TABLE op_returns;
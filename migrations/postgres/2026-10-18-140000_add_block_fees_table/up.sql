@@ -0,0 +1,17 @@
+CREATE TABLE block_fees (
+    height INTEGER PRIMARY KEY NOT NULL,
+    total_fee BIGINT NOT NULL,
+    subsidy BIGINT NOT NULL,
+    avg_fee BIGINT NOT NULL,
+    min_fee BIGINT NOT NULL,
+    max_fee BIGINT NOT NULL,
+    median_fee BIGINT NOT NULL,
+    avg_feerate BIGINT NOT NULL,
+    min_feerate BIGINT NOT NULL,
+    max_feerate BIGINT NOT NULL,
+    feerate_p10 BIGINT NOT NULL,
+    feerate_p25 BIGINT NOT NULL,
+    feerate_p50 BIGINT NOT NULL,
+    feerate_p75 BIGINT NOT NULL,
+    feerate_p90 BIGINT NOT NULL
+);
@@ -0,0 +1,5 @@
+DROP INDEX blocks_block_hash;
+ALTER TABLE blocks DROP COLUMN prev_hash;
+ALTER TABLE blocks DROP COLUMN block_hash;
+ALTER TABLE blocks ALTER COLUMN encoded_target TYPE INTEGER;
+ALTER TABLE blocks ALTER COLUMN time TYPE INTEGER;
@@ -0,0 +1,7 @@
+-- existing rows keep empty hashes, the block data isn't available to backfill them
+-- time and bits are unsigned 32 bit values, which don't fit INTEGER columns
+ALTER TABLE blocks ALTER COLUMN time TYPE BIGINT;
+ALTER TABLE blocks ALTER COLUMN encoded_target TYPE BIGINT;
+ALTER TABLE blocks ADD COLUMN block_hash TEXT NOT NULL DEFAULT '';
+ALTER TABLE blocks ADD COLUMN prev_hash TEXT NOT NULL DEFAULT '';
+CREATE INDEX blocks_block_hash ON blocks (block_hash);
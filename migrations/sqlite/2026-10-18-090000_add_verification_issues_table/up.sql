@@ -0,0 +1,7 @@
+CREATE TABLE verification_issues (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    height INTEGER NOT NULL,
+    block_hash TEXT,
+    kind TEXT NOT NULL,
+    message TEXT NOT NULL
+);
@@ -0,0 +1,5 @@
+CREATE TABLE checkpoint (
+    id INTEGER PRIMARY KEY NOT NULL,
+    coin TEXT NOT NULL,
+    height BIGINT NOT NULL
+);
@@ -0,0 +1,18 @@
+CREATE TABLE transactions (
+    height INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    txid TEXT NOT NULL,
+    wtxid TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    locktime BIGINT NOT NULL,
+    size INTEGER NOT NULL,
+    vsize INTEGER NOT NULL,
+    weight BIGINT NOT NULL,
+    input_count INTEGER NOT NULL,
+    output_count INTEGER NOT NULL,
+    output_value BIGINT NOT NULL,
+    is_segwit BOOLEAN NOT NULL,
+    is_coinbase BOOLEAN NOT NULL,
+    PRIMARY KEY (height, position)
+);
+CREATE INDEX transactions_txid ON transactions (txid);
@@ -0,0 +1,25 @@
+CREATE TABLE outputs (
+    height INTEGER NOT NULL,
+    txid TEXT NOT NULL,
+    vout INTEGER NOT NULL,
+    value BIGINT NOT NULL,
+    script BLOB NOT NULL,
+    script_type TEXT NOT NULL,
+    address TEXT,
+    spent_by_txid TEXT,
+    spent_height INTEGER,
+    PRIMARY KEY (txid, vout)
+);
+CREATE INDEX outputs_address ON outputs (address);
+CREATE TABLE inputs (
+    height INTEGER NOT NULL,
+    txid TEXT NOT NULL,
+    vin INTEGER NOT NULL,
+    prev_txid TEXT NOT NULL,
+    prev_vout BIGINT NOT NULL,
+    sequence BIGINT NOT NULL,
+    script_sig BLOB NOT NULL,
+    witness BLOB,
+    PRIMARY KEY (txid, vin)
+);
+CREATE INDEX inputs_prevout ON inputs (prev_txid, prev_vout);
@@ -0,0 +1,11 @@
+CREATE TABLE op_returns (
+    height INTEGER NOT NULL,
+    txid TEXT NOT NULL,
+    vout INTEGER NOT NULL,
+    value BIGINT NOT NULL,
+    payload BLOB NOT NULL,
+    data BLOB,
+    protocol TEXT,
+    PRIMARY KEY (txid, vout)
+);
+CREATE INDEX op_returns_protocol ON op_returns (protocol);
@@ -0,0 +1 @@
+DROP TABLE block_script_stats;
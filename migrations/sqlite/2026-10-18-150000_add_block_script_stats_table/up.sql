@@ -0,0 +1,9 @@
+CREATE TABLE block_script_stats (
+    height INTEGER NOT NULL,
+    script_type TEXT NOT NULL,
+    output_count INTEGER NOT NULL,
+    output_value BIGINT NOT NULL,
+    input_count INTEGER,
+    input_value BIGINT,
+    PRIMARY KEY (height, script_type)
+);
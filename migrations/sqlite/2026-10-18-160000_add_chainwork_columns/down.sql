@@ -0,0 +1,4 @@
+DROP TABLE block_hashrates;
+ALTER TABLE blocks DROP COLUMN chainwork;
+ALTER TABLE blocks DROP COLUMN work;
+ALTER TABLE blocks DROP COLUMN difficulty;
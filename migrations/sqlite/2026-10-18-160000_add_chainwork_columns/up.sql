@@ -0,0 +1,9 @@
+ALTER TABLE blocks ADD COLUMN difficulty DOUBLE NOT NULL DEFAULT 0;
+ALTER TABLE blocks ADD COLUMN work TEXT NOT NULL DEFAULT '';
+ALTER TABLE blocks ADD COLUMN chainwork TEXT NOT NULL DEFAULT '';
+CREATE TABLE block_hashrates (
+    height INTEGER NOT NULL,
+    window_size INTEGER NOT NULL,
+    hashrate DOUBLE NOT NULL,
+    PRIMARY KEY (height, window_size)
+);
@@ -0,0 +1,2 @@
+DROP TABLE coinbase_payouts;
+DROP TABLE coinbases;
@@ -0,0 +1,19 @@
+CREATE TABLE coinbases (
+    height INTEGER PRIMARY KEY NOT NULL,
+    txid TEXT NOT NULL,
+    script_sig BLOB NOT NULL,
+    tags TEXT NOT NULL,
+    bip34_height BIGINT,
+    bip34_valid BOOLEAN NOT NULL,
+    extranonce_length INTEGER,
+    witness_commitment BOOLEAN NOT NULL,
+    merge_mining TEXT
+);
+CREATE TABLE coinbase_payouts (
+    height INTEGER NOT NULL,
+    vout INTEGER NOT NULL,
+    value BIGINT NOT NULL,
+    script BLOB NOT NULL,
+    address TEXT,
+    PRIMARY KEY (height, vout)
+);
@@ -0,0 +1,23 @@
+CREATE TABLE blocks_v1 (
+    height INTEGER PRIMARY KEY NOT NULL,
+    version INTEGER NOT NULL,
+    time INTEGER NOT NULL,
+    encoded_target INTEGER NOT NULL,
+    nonce BIGINT NOT NULL,
+    tx_count INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    weight BIGINT NOT NULL,
+    turnover BIGINT NOT NULL,
+    miner_reward BIGINT NOT NULL,
+    pool TEXT,
+    difficulty DOUBLE NOT NULL,
+    work TEXT NOT NULL,
+    chainwork TEXT NOT NULL
+);
+INSERT INTO blocks_v1 (height, version, time, encoded_target, nonce, tx_count, size, weight, turnover, miner_reward, pool, difficulty,
+    work, chainwork)
+SELECT height, version, time, encoded_target, nonce, tx_count, size, weight, turnover, miner_reward, pool, difficulty,
+    work, chainwork
+FROM blocks;
+DROP TABLE blocks;
+ALTER TABLE blocks_v1 RENAME TO blocks;
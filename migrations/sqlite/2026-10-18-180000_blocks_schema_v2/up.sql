@@ -0,0 +1,28 @@
+-- existing rows keep empty hashes, the block data isn't available to backfill them
+-- time and bits are unsigned 32 bit values, which don't fit i32 columns
+CREATE TABLE blocks_v2 (
+    height INTEGER PRIMARY KEY NOT NULL,
+    version INTEGER NOT NULL,
+    time BIGINT NOT NULL,
+    encoded_target BIGINT NOT NULL,
+    nonce BIGINT NOT NULL,
+    tx_count INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    weight BIGINT NOT NULL,
+    turnover BIGINT NOT NULL,
+    miner_reward BIGINT NOT NULL,
+    pool TEXT,
+    difficulty DOUBLE NOT NULL,
+    work TEXT NOT NULL,
+    chainwork TEXT NOT NULL,
+    block_hash TEXT NOT NULL DEFAULT '',
+    prev_hash TEXT NOT NULL DEFAULT ''
+);
+INSERT INTO blocks_v2 (height, version, time, encoded_target, nonce, tx_count, size, weight, turnover, miner_reward, pool, difficulty,
+    work, chainwork)
+SELECT height, version, time, encoded_target, nonce, tx_count, size, weight, turnover, miner_reward, pool, difficulty,
+    work, chainwork
+FROM blocks;
+DROP TABLE blocks;
+ALTER TABLE blocks_v2 RENAME TO blocks;
+CREATE INDEX blocks_block_hash ON blocks (block_hash);
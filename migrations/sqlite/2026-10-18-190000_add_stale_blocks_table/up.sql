@@ -0,0 +1,13 @@
+CREATE TABLE stale_blocks (
+    block_hash TEXT PRIMARY KEY NOT NULL,
+    height INTEGER NOT NULL,
+    prev_hash TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    merkle_root TEXT NOT NULL,
+    time BIGINT NOT NULL,
+    encoded_target BIGINT NOT NULL,
+    nonce BIGINT NOT NULL,
+    tx_count INTEGER,
+    pool TEXT,
+    miner_tag TEXT
+);
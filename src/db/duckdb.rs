@@ -0,0 +1,744 @@
+use std::ops::RangeInclusive;
+
+use ::duckdb::{params, OptionalExt};
+
+use super::queries::BLOCK_AGGREGATES;
+use super::{
+    Backend, Batch, Block, BlockAggregates, BlockFees, BlockHashrate, BlockScriptStats, Checkpoint,
+    Coinbase, CoinbasePayout, Input, OpReturn, Output, PoolBlocks, StaleBlock, Transaction,
+    VerificationIssue,
+};
+
+/// Tables created when the database is opened. DuckDB needs no secondary indexes for the
+/// analytical queries it's meant for, only the primary keys are kept.
+const SCHEMA: &str = include_str!("duckdb.sql");
+
+const BLOCK_COLUMNS: &str =
+    "height, version, time, encoded_target, nonce, tx_count, size, weight, \
+     turnover, miner_reward, pool, difficulty, work, chainwork, block_hash, prev_hash";
+const BLOCK_FEES_COLUMNS: &str = "height, total_fee, subsidy, avg_fee, min_fee, max_fee, \
+     median_fee, avg_feerate, min_feerate, max_feerate, feerate_p10, feerate_p25, feerate_p50, \
+     feerate_p75, feerate_p90";
+const BLOCK_SCRIPT_STATS_COLUMNS: &str =
+    "height, script_type, output_count, output_value, input_count, input_value";
+const BLOCK_HASHRATE_COLUMNS: &str = "height, window_size, hashrate";
+const COINBASE_COLUMNS: &str = "height, txid, script_sig, tags, bip34_height, bip34_valid, \
+     extranonce_length, witness_commitment, merge_mining";
+const COINBASE_PAYOUT_COLUMNS: &str = "height, vout, value, script, address";
+const TRANSACTION_COLUMNS: &str = "height, position, txid, wtxid, version, locktime, size, vsize, \
+     weight, input_count, output_count, output_value, is_segwit, is_coinbase";
+const OUTPUT_COLUMNS: &str =
+    "height, txid, vout, value, script, script_type, address, spent_by_txid, spent_height";
+const INPUT_COLUMNS: &str =
+    "height, txid, vin, prev_txid, prev_vout, sequence, script_sig, witness";
+const OP_RETURN_COLUMNS: &str = "height, txid, vout, value, payload, data, protocol";
+const VERIFICATION_ISSUE_COLUMNS: &str = "height, block_hash, kind, message";
+const STALE_BLOCK_COLUMNS: &str = "block_hash, height, prev_hash, version, merkle_root, time, \
+     encoded_target, nonce, tx_count, pool, miner_tag";
+
+/// Embedded DuckDB database, selected with `--db-url duckdb://<path>`
+pub struct DuckDb {
+    conn: std::sync::Mutex<::duckdb::Connection>,
+}
+
+impl DuckDb {
+    /// Opens the database at `path` and creates the tables which don't exist yet
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let conn = if path.is_empty() || path == ":memory:" {
+            tracing::info!("opening in-memory DuckDB database");
+            ::duckdb::Connection::open_in_memory()?
+        } else {
+            tracing::info!("opening DuckDB database {path}");
+            ::duckdb::Connection::open(path)?
+        };
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: std::sync::Mutex::new(conn),
+        })
+    }
+
+    /// Opens the database at `path` without creating tables, fails if any is missing
+    pub fn open_existing(path: &str) -> anyhow::Result<Self> {
+        tracing::info!("opening DuckDB database {path}");
+        let conn = ::duckdb::Connection::open(path)?;
+        for table in SCHEMA
+            .split("CREATE TABLE IF NOT EXISTS ")
+            .skip(1)
+            .filter_map(|statement| statement.split_whitespace().next())
+        {
+            if !has_table(&conn, table)? {
+                anyhow::bail!("database has no {table} table, run `parse` on it to create it");
+            }
+        }
+        Ok(Self {
+            conn: std::sync::Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, ::duckdb::Connection> {
+        self.conn.lock().expect("connection lock is poisoned")
+    }
+}
+
+fn has_table(conn: &::duckdb::Connection, table: &str) -> ::duckdb::Result<bool> {
+    conn.query_row(
+        "SELECT count(*) > 0 FROM information_schema.tables WHERE table_name = ?",
+        [table],
+        |row| row.get(0),
+    )
+}
+
+/// `INSERT` statement for all `columns` of `table`
+fn insert_sql(verb: &str, table: &str, columns: &str) -> String {
+    let placeholders = vec!["?"; columns.split(',').count()].join(", ");
+    format!("{verb} INTO {table} ({columns}) VALUES ({placeholders})")
+}
+
+/// `INSERT` statement for all `columns` of `table` which replaces the row with the same `key`.
+/// DuckDB rejects `INSERT OR REPLACE` on tables with a composite primary key, the conflict
+/// target has to be spelled out.
+fn upsert_sql(table: &str, columns: &str, key: &[&str]) -> String {
+    let updates = columns
+        .split(',')
+        .map(str::trim)
+        .filter(|column| !key.contains(column))
+        .map(|column| format!("{column} = excluded.{column}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "{} ON CONFLICT ({}) DO UPDATE SET {updates}",
+        insert_sql("INSERT", table, columns),
+        key.join(", ")
+    )
+}
+
+fn insert_blocks(conn: &::duckdb::Connection, blocks: &[Block]) -> anyhow::Result<usize> {
+    let mut stmt = conn.prepare(&insert_sql("INSERT", "blocks", BLOCK_COLUMNS))?;
+    for b in blocks {
+        stmt.execute(params![
+            b.height,
+            b.version,
+            b.time,
+            b.encoded_target,
+            b.nonce,
+            b.tx_count,
+            b.size,
+            b.weight,
+            b.turnover,
+            b.miner_reward,
+            b.pool,
+            b.difficulty,
+            b.work,
+            b.chainwork,
+            b.hash,
+            b.prev_hash,
+        ])?;
+    }
+    Ok(blocks.len())
+}
+
+fn insert_verification_issues(
+    conn: &::duckdb::Connection,
+    issues: &[VerificationIssue],
+) -> anyhow::Result<usize> {
+    let mut stmt = conn.prepare(&insert_sql(
+        "INSERT",
+        "verification_issues",
+        VERIFICATION_ISSUE_COLUMNS,
+    ))?;
+    for issue in issues {
+        stmt.execute(params![
+            issue.height,
+            issue.block_hash,
+            issue.kind,
+            issue.message
+        ])?;
+    }
+    Ok(issues.len())
+}
+
+fn block_row(row: &::duckdb::Row) -> ::duckdb::Result<Block> {
+    Ok(Block {
+        height: row.get(0)?,
+        version: row.get(1)?,
+        time: row.get(2)?,
+        encoded_target: row.get(3)?,
+        nonce: row.get(4)?,
+        tx_count: row.get(5)?,
+        size: row.get(6)?,
+        weight: row.get(7)?,
+        turnover: row.get(8)?,
+        miner_reward: row.get(9)?,
+        pool: row.get(10)?,
+        difficulty: row.get(11)?,
+        work: row.get(12)?,
+        chainwork: row.get(13)?,
+        hash: row.get(14)?,
+        prev_hash: row.get(15)?,
+    })
+}
+
+fn transaction_row(row: &::duckdb::Row) -> ::duckdb::Result<Transaction> {
+    Ok(Transaction {
+        height: row.get(0)?,
+        position: row.get(1)?,
+        txid: row.get(2)?,
+        wtxid: row.get(3)?,
+        version: row.get(4)?,
+        locktime: row.get(5)?,
+        size: row.get(6)?,
+        vsize: row.get(7)?,
+        weight: row.get(8)?,
+        input_count: row.get(9)?,
+        output_count: row.get(10)?,
+        output_value: row.get(11)?,
+        is_segwit: row.get(12)?,
+        is_coinbase: row.get(13)?,
+    })
+}
+
+fn output_row(row: &::duckdb::Row) -> ::duckdb::Result<Output> {
+    Ok(Output {
+        height: row.get(0)?,
+        txid: row.get(1)?,
+        vout: row.get(2)?,
+        value: row.get(3)?,
+        script: row.get(4)?,
+        script_type: row.get(5)?,
+        address: row.get(6)?,
+        spent_by_txid: row.get(7)?,
+        spent_height: row.get(8)?,
+    })
+}
+
+/// Fee statistics, script stats and hashrates of the blocks
+fn insert_block_stats(conn: &::duckdb::Connection, batch: &Batch) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare(&insert_sql("INSERT", "block_fees", BLOCK_FEES_COLUMNS))?;
+    for f in &batch.block_fees {
+        stmt.execute(params![
+            f.height,
+            f.total_fee,
+            f.subsidy,
+            f.avg_fee,
+            f.min_fee,
+            f.max_fee,
+            f.median_fee,
+            f.avg_feerate,
+            f.min_feerate,
+            f.max_feerate,
+            f.feerate_p10,
+            f.feerate_p25,
+            f.feerate_p50,
+            f.feerate_p75,
+            f.feerate_p90,
+        ])?;
+    }
+
+    let mut stmt = conn.prepare(&insert_sql(
+        "INSERT",
+        "block_script_stats",
+        BLOCK_SCRIPT_STATS_COLUMNS,
+    ))?;
+    for s in &batch.block_script_stats {
+        stmt.execute(params![
+            s.height,
+            s.script_type,
+            s.output_count,
+            s.output_value,
+            s.input_count,
+            s.input_value,
+        ])?;
+    }
+
+    let mut stmt = conn.prepare(&insert_sql(
+        "INSERT",
+        "block_hashrates",
+        BLOCK_HASHRATE_COLUMNS,
+    ))?;
+    for h in &batch.block_hashrates {
+        stmt.execute(params![h.height, h.window_size, h.hashrate])?;
+    }
+    Ok(())
+}
+
+/// Coinbases and their payouts
+fn insert_coinbases(conn: &::duckdb::Connection, batch: &Batch) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare(&insert_sql("INSERT", "coinbases", COINBASE_COLUMNS))?;
+    for c in &batch.coinbases {
+        stmt.execute(params![
+            c.height,
+            c.txid,
+            c.script_sig,
+            c.tags,
+            c.bip34_height,
+            c.bip34_valid,
+            c.extranonce_length,
+            c.witness_commitment,
+            c.merge_mining,
+        ])?;
+    }
+
+    let mut stmt = conn.prepare(&insert_sql(
+        "INSERT",
+        "coinbase_payouts",
+        COINBASE_PAYOUT_COLUMNS,
+    ))?;
+    for p in &batch.coinbase_payouts {
+        stmt.execute(params![p.height, p.vout, p.value, p.script, p.address])?;
+    }
+    Ok(())
+}
+
+/// Transactions with their outputs, inputs and OP_RETURN payloads, marking the outputs they spend
+fn insert_transactions(conn: &::duckdb::Connection, batch: &Batch) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare(&insert_sql("INSERT", "transactions", TRANSACTION_COLUMNS))?;
+    for t in &batch.transactions {
+        stmt.execute(params![
+            t.height,
+            t.position,
+            t.txid,
+            t.wtxid,
+            t.version,
+            t.locktime,
+            t.size,
+            t.vsize,
+            t.weight,
+            t.input_count,
+            t.output_count,
+            t.output_value,
+            t.is_segwit,
+            t.is_coinbase,
+        ])?;
+    }
+
+    // replace, as the duplicate coinbases before BIP30 overwrite the earlier outputs
+    let mut stmt = conn.prepare(&upsert_sql("outputs", OUTPUT_COLUMNS, &["txid", "vout"]))?;
+    for o in &batch.outputs {
+        stmt.execute(params![
+            o.height,
+            o.txid,
+            o.vout,
+            o.value,
+            o.script,
+            o.script_type,
+            o.address,
+            o.spent_by_txid,
+            o.spent_height,
+        ])?;
+    }
+
+    let mut stmt = conn.prepare(&upsert_sql("inputs", INPUT_COLUMNS, &["txid", "vin"]))?;
+    for i in &batch.inputs {
+        stmt.execute(params![
+            i.height,
+            i.txid,
+            i.vin,
+            i.prev_txid,
+            i.prev_vout,
+            i.sequence,
+            i.script_sig,
+            i.witness,
+        ])?;
+    }
+
+    let mut stmt = conn.prepare(
+        "UPDATE outputs SET spent_by_txid = ?, spent_height = ? WHERE txid = ? AND vout = ?",
+    )?;
+    for spend in &batch.spends {
+        stmt.execute(params![
+            spend.txid,
+            spend.height,
+            spend.prev_txid,
+            spend.prev_vout
+        ])?;
+    }
+
+    let mut stmt = conn.prepare(&upsert_sql(
+        "op_returns",
+        OP_RETURN_COLUMNS,
+        &["txid", "vout"],
+    ))?;
+    for o in &batch.op_returns {
+        stmt.execute(params![
+            o.height, o.txid, o.vout, o.value, o.payload, o.data, o.protocol,
+        ])?;
+    }
+    Ok(())
+}
+
+impl Backend for DuckDb {
+    fn write(&self, batch: Batch, checkpoint: Checkpoint) -> anyhow::Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        insert_blocks(&tx, &batch.blocks)?;
+        insert_block_stats(&tx, &batch)?;
+        insert_coinbases(&tx, &batch)?;
+        insert_transactions(&tx, &batch)?;
+        insert_verification_issues(&tx, &batch.verification_issues)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO checkpoint (id, coin, height) VALUES (?, ?, ?)",
+            params![checkpoint.id, checkpoint.coin, checkpoint.height],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // DuckDB tables only have primary keys, which can't be dropped
+    fn drop_indexes(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn create_indexes(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(self
+            .conn()
+            .query_row(
+                "SELECT id, coin, height FROM checkpoint WHERE id = 0",
+                [],
+                |row| {
+                    Ok(Checkpoint {
+                        id: row.get(0)?,
+                        coin: row.get(1)?,
+                        height: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    fn insert_blocks(&self, blocks: Vec<Block>) -> anyhow::Result<usize> {
+        insert_blocks(&self.conn(), &blocks)
+    }
+
+    fn insert_verification_issues(&self, issues: Vec<VerificationIssue>) -> anyhow::Result<usize> {
+        insert_verification_issues(&self.conn(), &issues)
+    }
+
+    fn verification_issues(&self) -> anyhow::Result<Vec<VerificationIssue>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {VERIFICATION_ISSUE_COLUMNS} FROM verification_issues ORDER BY id"
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok(VerificationIssue {
+                height: row.get(0)?,
+                block_hash: row.get(1)?,
+                kind: row.get(2)?,
+                message: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn replace_stale_blocks(&self, stale_blocks: Vec<StaleBlock>) -> anyhow::Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM stale_blocks", [])?;
+        {
+            let mut stmt =
+                tx.prepare(&insert_sql("INSERT", "stale_blocks", STALE_BLOCK_COLUMNS))?;
+            for b in &stale_blocks {
+                stmt.execute(params![
+                    b.block_hash,
+                    b.height,
+                    b.prev_hash,
+                    b.version,
+                    b.merkle_root,
+                    b.time,
+                    b.encoded_target,
+                    b.nonce,
+                    b.tx_count,
+                    b.pool,
+                    b.miner_tag,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn stale_blocks(&self) -> anyhow::Result<Vec<StaleBlock>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {STALE_BLOCK_COLUMNS} FROM stale_blocks ORDER BY height, block_hash"
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok(StaleBlock {
+                block_hash: row.get(0)?,
+                height: row.get(1)?,
+                prev_hash: row.get(2)?,
+                version: row.get(3)?,
+                merkle_root: row.get(4)?,
+                time: row.get(5)?,
+                encoded_target: row.get(6)?,
+                nonce: row.get(7)?,
+                tx_count: row.get(8)?,
+                pool: row.get(9)?,
+                miner_tag: row.get(10)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn block(&self, height: i32) -> anyhow::Result<Block> {
+        Ok(self.conn().query_row(
+            &format!("SELECT {BLOCK_COLUMNS} FROM blocks WHERE height = ?"),
+            [height],
+            block_row,
+        )?)
+    }
+
+    fn block_by_hash(&self, hash: &str) -> anyhow::Result<Option<Block>> {
+        if hash.is_empty() {
+            return Ok(None);
+        }
+        Ok(self
+            .conn()
+            .query_row(
+                &format!("SELECT {BLOCK_COLUMNS} FROM blocks WHERE block_hash = ?"),
+                [hash],
+                block_row,
+            )
+            .optional()?)
+    }
+
+    fn blocks_range(&self, heights: RangeInclusive<i32>) -> anyhow::Result<Vec<Block>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {BLOCK_COLUMNS} FROM blocks WHERE height BETWEEN ? AND ? ORDER BY height"
+        ))?;
+        let rows = stmt.query_map([heights.start(), heights.end()], block_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn blocks_by_time(&self, times: RangeInclusive<i64>) -> anyhow::Result<Vec<Block>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {BLOCK_COLUMNS} FROM blocks WHERE time BETWEEN ? AND ? ORDER BY height"
+        ))?;
+        let rows = stmt.query_map([times.start(), times.end()], block_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn block_aggregates(&self, heights: RangeInclusive<i32>) -> anyhow::Result<BlockAggregates> {
+        Ok(self.conn().query_row(
+            &format!("SELECT {BLOCK_AGGREGATES} FROM blocks WHERE height BETWEEN ? AND ?"),
+            [heights.start(), heights.end()],
+            |row| {
+                Ok(BlockAggregates {
+                    block_count: row.get(0)?,
+                    tx_count: row.get(1)?,
+                    turnover: row.get(2)?,
+                    miner_reward: row.get(3)?,
+                    avg_size: row.get(4)?,
+                    avg_weight: row.get(5)?,
+                })
+            },
+        )?)
+    }
+
+    fn pool_counts(&self, heights: RangeInclusive<i32>) -> anyhow::Result<Vec<PoolBlocks>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT pool, COUNT(*) FROM blocks WHERE height BETWEEN ? AND ? \
+             GROUP BY pool ORDER BY COUNT(*) DESC, pool NULLS FIRST",
+        )?;
+        let rows = stmt.query_map([heights.start(), heights.end()], |row| {
+            Ok(PoolBlocks {
+                pool: row.get(0)?,
+                block_count: row.get(1)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn block_fees(&self, height: i32) -> anyhow::Result<Option<BlockFees>> {
+        Ok(self
+            .conn()
+            .query_row(
+                &format!("SELECT {BLOCK_FEES_COLUMNS} FROM block_fees WHERE height = ?"),
+                [height],
+                |row| {
+                    Ok(BlockFees {
+                        height: row.get(0)?,
+                        total_fee: row.get(1)?,
+                        subsidy: row.get(2)?,
+                        avg_fee: row.get(3)?,
+                        min_fee: row.get(4)?,
+                        max_fee: row.get(5)?,
+                        median_fee: row.get(6)?,
+                        avg_feerate: row.get(7)?,
+                        min_feerate: row.get(8)?,
+                        max_feerate: row.get(9)?,
+                        feerate_p10: row.get(10)?,
+                        feerate_p25: row.get(11)?,
+                        feerate_p50: row.get(12)?,
+                        feerate_p75: row.get(13)?,
+                        feerate_p90: row.get(14)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    fn block_hashrates(&self, height: i32) -> anyhow::Result<Vec<BlockHashrate>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {BLOCK_HASHRATE_COLUMNS} FROM block_hashrates WHERE height = ? ORDER BY window_size"
+        ))?;
+        let rows = stmt.query_map([height], |row| {
+            Ok(BlockHashrate {
+                height: row.get(0)?,
+                window_size: row.get(1)?,
+                hashrate: row.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn block_script_stats(&self, height: i32) -> anyhow::Result<Vec<BlockScriptStats>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {BLOCK_SCRIPT_STATS_COLUMNS} FROM block_script_stats WHERE height = ? ORDER BY script_type"
+        ))?;
+        let rows = stmt.query_map([height], |row| {
+            Ok(BlockScriptStats {
+                height: row.get(0)?,
+                script_type: row.get(1)?,
+                output_count: row.get(2)?,
+                output_value: row.get(3)?,
+                input_count: row.get(4)?,
+                input_value: row.get(5)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn coinbase(&self, height: i32) -> anyhow::Result<Coinbase> {
+        Ok(self.conn().query_row(
+            &format!("SELECT {COINBASE_COLUMNS} FROM coinbases WHERE height = ?"),
+            [height],
+            |row| {
+                Ok(Coinbase {
+                    height: row.get(0)?,
+                    txid: row.get(1)?,
+                    script_sig: row.get(2)?,
+                    tags: row.get(3)?,
+                    bip34_height: row.get(4)?,
+                    bip34_valid: row.get(5)?,
+                    extranonce_length: row.get(6)?,
+                    witness_commitment: row.get(7)?,
+                    merge_mining: row.get(8)?,
+                })
+            },
+        )?)
+    }
+
+    fn coinbase_payouts(&self, height: i32) -> anyhow::Result<Vec<CoinbasePayout>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COINBASE_PAYOUT_COLUMNS} FROM coinbase_payouts WHERE height = ? ORDER BY vout"
+        ))?;
+        let rows = stmt.query_map([height], |row| {
+            Ok(CoinbasePayout {
+                height: row.get(0)?,
+                vout: row.get(1)?,
+                value: row.get(2)?,
+                script: row.get(3)?,
+                address: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn transactions(&self, height: i32) -> anyhow::Result<Vec<Transaction>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TRANSACTION_COLUMNS} FROM transactions WHERE height = ? ORDER BY position"
+        ))?;
+        let rows = stmt.query_map([height], transaction_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn transactions_range(&self, heights: RangeInclusive<i32>) -> anyhow::Result<Vec<Transaction>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TRANSACTION_COLUMNS} FROM transactions WHERE height BETWEEN ? AND ? \
+             ORDER BY height, position"
+        ))?;
+        let rows = stmt.query_map([heights.start(), heights.end()], transaction_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn transactions_count(&self) -> anyhow::Result<i64> {
+        Ok(self
+            .conn()
+            .query_row("SELECT COUNT(*) FROM transactions", [], |row| row.get(0))?)
+    }
+
+    fn output(&self, txid: &str, vout: i32) -> anyhow::Result<Output> {
+        Ok(self.conn().query_row(
+            &format!("SELECT {OUTPUT_COLUMNS} FROM outputs WHERE txid = ? AND vout = ?"),
+            params![txid, vout],
+            output_row,
+        )?)
+    }
+
+    fn outputs_range(&self, heights: RangeInclusive<i32>) -> anyhow::Result<Vec<Output>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {OUTPUT_COLUMNS} FROM outputs JOIN transactions USING (height, txid) \
+             WHERE height BETWEEN ? AND ? ORDER BY height, position, vout"
+        ))?;
+        let rows = stmt.query_map([heights.start(), heights.end()], output_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn inputs(&self, txid: &str) -> anyhow::Result<Vec<Input>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {INPUT_COLUMNS} FROM inputs WHERE txid = ? ORDER BY vin"
+        ))?;
+        let rows = stmt.query_map([txid], |row| {
+            Ok(Input {
+                height: row.get(0)?,
+                txid: row.get(1)?,
+                vin: row.get(2)?,
+                prev_txid: row.get(3)?,
+                prev_vout: row.get(4)?,
+                sequence: row.get(5)?,
+                script_sig: row.get(6)?,
+                witness: row.get(7)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn op_returns(&self, height: i32) -> anyhow::Result<Vec<OpReturn>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {OP_RETURN_COLUMNS} FROM op_returns WHERE height = ?"
+        ))?;
+        let rows = stmt.query_map([height], |row| {
+            Ok(OpReturn {
+                height: row.get(0)?,
+                txid: row.get(1)?,
+                vout: row.get(2)?,
+                value: row.get(3)?,
+                payload: row.get(4)?,
+                data: row.get(5)?,
+                protocol: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn blocks_count(&self) -> anyhow::Result<i64> {
+        Ok(self
+            .conn()
+            .query_row("SELECT COUNT(*) FROM blocks", [], |row| row.get(0))?)
+    }
+}
@@ -0,0 +1,158 @@
+-- Same tables as the SQLite migrations, created on open
+CREATE TABLE IF NOT EXISTS blocks (
+    height INTEGER PRIMARY KEY NOT NULL,
+    version INTEGER NOT NULL,
+    time BIGINT NOT NULL,
+    encoded_target BIGINT NOT NULL,
+    nonce BIGINT NOT NULL,
+    tx_count INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    weight BIGINT NOT NULL,
+    turnover BIGINT NOT NULL,
+    miner_reward BIGINT NOT NULL,
+    pool TEXT,
+    difficulty DOUBLE NOT NULL,
+    work TEXT NOT NULL,
+    chainwork TEXT NOT NULL,
+    block_hash TEXT NOT NULL DEFAULT '',
+    prev_hash TEXT NOT NULL DEFAULT ''
+);
+
+CREATE SEQUENCE IF NOT EXISTS verification_issue_ids START 1;
+CREATE TABLE IF NOT EXISTS verification_issues (
+    id INTEGER PRIMARY KEY DEFAULT nextval('verification_issue_ids') NOT NULL,
+    height INTEGER NOT NULL,
+    block_hash TEXT,
+    kind TEXT NOT NULL,
+    message TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS checkpoint (
+    id INTEGER PRIMARY KEY NOT NULL,
+    coin TEXT NOT NULL,
+    height BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS transactions (
+    height INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    txid TEXT NOT NULL,
+    wtxid TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    locktime BIGINT NOT NULL,
+    size INTEGER NOT NULL,
+    vsize INTEGER NOT NULL,
+    weight BIGINT NOT NULL,
+    input_count INTEGER NOT NULL,
+    output_count INTEGER NOT NULL,
+    output_value BIGINT NOT NULL,
+    is_segwit BOOLEAN NOT NULL,
+    is_coinbase BOOLEAN NOT NULL,
+    PRIMARY KEY (height, position)
+);
+
+CREATE TABLE IF NOT EXISTS outputs (
+    height INTEGER NOT NULL,
+    txid TEXT NOT NULL,
+    vout INTEGER NOT NULL,
+    value BIGINT NOT NULL,
+    script BLOB NOT NULL,
+    script_type TEXT NOT NULL,
+    address TEXT,
+    spent_by_txid TEXT,
+    spent_height INTEGER,
+    PRIMARY KEY (txid, vout)
+);
+
+CREATE TABLE IF NOT EXISTS inputs (
+    height INTEGER NOT NULL,
+    txid TEXT NOT NULL,
+    vin INTEGER NOT NULL,
+    prev_txid TEXT NOT NULL,
+    prev_vout BIGINT NOT NULL,
+    sequence BIGINT NOT NULL,
+    script_sig BLOB NOT NULL,
+    witness BLOB,
+    PRIMARY KEY (txid, vin)
+);
+
+CREATE TABLE IF NOT EXISTS op_returns (
+    height INTEGER NOT NULL,
+    txid TEXT NOT NULL,
+    vout INTEGER NOT NULL,
+    value BIGINT NOT NULL,
+    payload BLOB NOT NULL,
+    data BLOB,
+    protocol TEXT,
+    PRIMARY KEY (txid, vout)
+);
+
+CREATE TABLE IF NOT EXISTS block_fees (
+    height INTEGER PRIMARY KEY NOT NULL,
+    total_fee BIGINT NOT NULL,
+    subsidy BIGINT NOT NULL,
+    avg_fee BIGINT NOT NULL,
+    min_fee BIGINT NOT NULL,
+    max_fee BIGINT NOT NULL,
+    median_fee BIGINT NOT NULL,
+    avg_feerate BIGINT NOT NULL,
+    min_feerate BIGINT NOT NULL,
+    max_feerate BIGINT NOT NULL,
+    feerate_p10 BIGINT NOT NULL,
+    feerate_p25 BIGINT NOT NULL,
+    feerate_p50 BIGINT NOT NULL,
+    feerate_p75 BIGINT NOT NULL,
+    feerate_p90 BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS block_script_stats (
+    height INTEGER NOT NULL,
+    script_type TEXT NOT NULL,
+    output_count INTEGER NOT NULL,
+    output_value BIGINT NOT NULL,
+    input_count INTEGER,
+    input_value BIGINT,
+    PRIMARY KEY (height, script_type)
+);
+
+CREATE TABLE IF NOT EXISTS block_hashrates (
+    height INTEGER NOT NULL,
+    window_size INTEGER NOT NULL,
+    hashrate DOUBLE NOT NULL,
+    PRIMARY KEY (height, window_size)
+);
+
+CREATE TABLE IF NOT EXISTS coinbases (
+    height INTEGER PRIMARY KEY NOT NULL,
+    txid TEXT NOT NULL,
+    script_sig BLOB NOT NULL,
+    tags TEXT NOT NULL,
+    bip34_height BIGINT,
+    bip34_valid BOOLEAN NOT NULL,
+    extranonce_length INTEGER,
+    witness_commitment BOOLEAN NOT NULL,
+    merge_mining TEXT
+);
+
+CREATE TABLE IF NOT EXISTS coinbase_payouts (
+    height INTEGER NOT NULL,
+    vout INTEGER NOT NULL,
+    value BIGINT NOT NULL,
+    script BLOB NOT NULL,
+    address TEXT,
+    PRIMARY KEY (height, vout)
+);
+
+CREATE TABLE IF NOT EXISTS stale_blocks (
+    block_hash TEXT PRIMARY KEY NOT NULL,
+    height INTEGER NOT NULL,
+    prev_hash TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    merkle_root TEXT NOT NULL,
+    time BIGINT NOT NULL,
+    encoded_target BIGINT NOT NULL,
+    nonce BIGINT NOT NULL,
+    tx_count INTEGER,
+    pool TEXT,
+    miner_tag TEXT
+);
@@ -1,38 +1,95 @@
 use diesel_migrations::MigrationHarness;
 
-pub type Pool =
-    diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<diesel::sqlite::SqliteConnection>>;
+pub type Pool<C = diesel::sqlite::SqliteConnection> =
+    diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<C>>;
 
-pub trait Managed {
-    #[must_use]
-    fn open(db_url: &str, migrations: diesel_migrations::EmbeddedMigrations) -> Pool {
+/// Runs on every new connection of a pool, e.g. to set connection pragmas
+pub type Customizer<C> = Box<dyn diesel::r2d2::CustomizeConnection<C, diesel::r2d2::Error>>;
+
+pub trait Managed: Sized {
+    type Connection;
+
+    /// Opens the database and applies the pending `migrations`
+    fn open(
+        db_url: &str,
+        migrations: diesel_migrations::EmbeddedMigrations,
+        customizer: Customizer<Self::Connection>,
+    ) -> anyhow::Result<Self>;
+
+    /// Opens the database without migrating it, fails if `migrations` haven't all been applied
+    fn open_existing(
+        db_url: &str,
+        migrations: diesel_migrations::EmbeddedMigrations,
+        customizer: Customizer<Self::Connection>,
+    ) -> anyhow::Result<Self>;
+}
+
+impl<C> Managed for Pool<C>
+where
+    C: diesel::r2d2::R2D2Connection + MigrationHarness<C::Backend> + Send + 'static,
+{
+    type Connection = C;
+
+    fn open(
+        db_url: &str,
+        migrations: diesel_migrations::EmbeddedMigrations,
+        customizer: Customizer<C>,
+    ) -> anyhow::Result<Self> {
         if db_url == ":memory:" {
             tracing::info!("opening in-memory database");
         } else {
             tracing::info!("opening database {db_url}");
         }
-        let db = memdb_pool(db_url);
-        create_tables(&db, migrations);
-        db
+        let db = memdb_pool(db_url, customizer)?;
+        create_tables(&db, migrations)?;
+        Ok(db)
+    }
+
+    fn open_existing(
+        db_url: &str,
+        migrations: diesel_migrations::EmbeddedMigrations,
+        customizer: Customizer<C>,
+    ) -> anyhow::Result<Self> {
+        tracing::info!("opening database {db_url}");
+        let db = memdb_pool(db_url, customizer)?;
+        let pending = db
+            .get()?
+            .has_pending_migration(migrations)
+            .map_err(|e| anyhow::anyhow!(e))?;
+        if pending {
+            anyhow::bail!("database schema is outdated, run `parse` on it to migrate it");
+        }
+        Ok(db)
     }
 }
 
-fn create_tables(pool: &Pool, migrations: diesel_migrations::EmbeddedMigrations) {
-    let conn = &mut pool.get().unwrap();
+fn create_tables<C>(
+    pool: &Pool<C>,
+    migrations: diesel_migrations::EmbeddedMigrations,
+) -> anyhow::Result<()>
+where
+    C: diesel::r2d2::R2D2Connection + MigrationHarness<C::Backend> + Send + 'static,
+{
+    let mut conn = pool.get()?;
+    let conn: &mut C = &mut conn;
     conn.run_pending_migrations(migrations)
-        .expect("Could not apply database migration");
+        .map_err(|e| anyhow::anyhow!("unable to migrate the database: {e}"))?;
+    Ok(())
 }
 
-#[must_use]
-fn memdb_pool(db_url: &str) -> Pool {
-    let manager = diesel::r2d2::ConnectionManager::<diesel::sqlite::SqliteConnection>::new(db_url);
+fn memdb_pool<C>(
+    db_url: &str,
+    customizer: Customizer<C>,
+) -> Result<Pool<C>, diesel::r2d2::PoolError>
+where
+    C: diesel::r2d2::R2D2Connection + Send + 'static,
+{
+    let manager = diesel::r2d2::ConnectionManager::<C>::new(db_url);
     let forever = Some(std::time::Duration::from_secs(u64::MAX));
     diesel::r2d2::Pool::builder()
         .idle_timeout(forever)
         .max_lifetime(forever)
         .connection_timeout(std::time::Duration::from_secs(5))
+        .connection_customizer(customizer)
         .build(manager)
-        .expect("Problem creating connection pool")
 }
-
-impl Managed for Pool {}
@@ -1,22 +1,38 @@
-use self::memory::Managed;
-use diesel::prelude::*;
-use diesel::RunQueryDsl;
-use schema::blocks;
+use std::ops::RangeInclusive;
 
+use schema::{
+    block_fees, block_hashrates, block_script_stats, blocks, checkpoint, coinbase_payouts,
+    coinbases, inputs, op_returns, outputs, stale_blocks, transactions, verification_issues,
+};
+
+#[cfg(feature = "duckdb")]
+pub mod duckdb;
 mod memory;
+pub mod postgres;
+mod queries;
 pub mod schema;
+pub mod sqlite;
+pub mod writer;
+
+/// URL prefix selecting the DuckDB backend, followed by the path of the database file
+pub const DUCKDB_PREFIX: &str = "duckdb://";
 
+/// URL prefixes selecting the PostgreSQL backend
+pub const POSTGRES_PREFIXES: [&str; 2] = ["postgres://", "postgresql://"];
+
+/// Database holding the parsed tables, shared by all clones
 #[derive(Clone)]
 pub struct Db {
-    pub pool: memory::Pool,
+    backend: std::sync::Arc<dyn Backend>,
 }
 
 #[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(treat_none_as_default_value = false)]
 pub struct Block {
     pub height: i32,
     pub version: i32,
-    pub time: i32,
-    pub encoded_target: i32,
+    pub time: i64,
+    pub encoded_target: i64,
     pub nonce: i64,
     pub tx_count: i32,
     pub size: i32,
@@ -24,32 +40,434 @@ pub struct Block {
     pub turnover: i64,
     pub miner_reward: i64,
     pub pool: Option<String>,
+    pub difficulty: f64,
+    /// Expected number of hashes to find the block, hex encoded
+    pub work: String,
+    /// Cumulative work of the chain up to this block, hex encoded
+    pub chainwork: String,
+    /// Empty for blocks written before the hashes were added to the schema. The migration can't
+    /// backfill them, parse the blocks into a new database to get them.
+    #[diesel(column_name = block_hash)]
+    pub hash: String,
+    /// Empty like `hash` for blocks written before the hashes were added
+    pub prev_hash: String,
 }
 
-const MIGRATIONS: diesel_migrations::EmbeddedMigrations = diesel_migrations::embed_migrations!();
+/// Fee statistics of a block computed from its undo data, fees in sat and feerates in sat/vB
+#[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(treat_none_as_default_value = false)]
+#[diesel(table_name = block_fees)]
+pub struct BlockFees {
+    pub height: i32,
+    pub total_fee: i64,
+    pub subsidy: i64,
+    pub avg_fee: i64,
+    pub min_fee: i64,
+    pub max_fee: i64,
+    pub median_fee: i64,
+    pub avg_feerate: i64,
+    pub min_feerate: i64,
+    pub max_feerate: i64,
+    pub feerate_p10: i64,
+    pub feerate_p25: i64,
+    pub feerate_p50: i64,
+    pub feerate_p75: i64,
+    pub feerate_p90: i64,
+}
 
-impl Db {
+/// Hashrate in hashes per second estimated over the `window_size` blocks up to `height`
+#[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(treat_none_as_default_value = false)]
+pub struct BlockHashrate {
+    pub height: i32,
+    pub window_size: i32,
+    pub hashrate: f64,
+}
+
+/// Outputs created and spent in a block by script type.
+/// The input columns are `None` if the undo data of the block isn't available.
+#[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(treat_none_as_default_value = false)]
+#[diesel(table_name = block_script_stats)]
+pub struct BlockScriptStats {
+    pub height: i32,
+    pub script_type: String,
+    pub output_count: i32,
+    pub output_value: i64,
+    pub input_count: Option<i32>,
+    pub input_value: Option<i64>,
+}
+
+#[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(treat_none_as_default_value = false)]
+pub struct Coinbase {
+    pub height: i32,
+    pub txid: String,
+    pub script_sig: Vec<u8>,
+    /// Printable ASCII runs of the scriptSig, one per line
+    pub tags: String,
+    /// Number encoded by the first push of the scriptSig
+    pub bip34_height: Option<i64>,
+    /// Whether `bip34_height` matches the height of the block
+    pub bip34_valid: bool,
+    pub extranonce_length: Option<i32>,
+    pub witness_commitment: bool,
+    /// Comma separated markers of merged mined chains
+    pub merge_mining: Option<String>,
+}
+
+/// An output of the coinbase of the block at `height`
+#[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(treat_none_as_default_value = false)]
+pub struct CoinbasePayout {
+    pub height: i32,
+    pub vout: i32,
+    pub value: i64,
+    pub script: Vec<u8>,
+    /// `None` if the script has no address, e.g. P2PK or `OP_RETURN`
+    pub address: Option<String>,
+}
+
+#[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(treat_none_as_default_value = false)]
+pub struct Transaction {
+    pub height: i32,
+    /// Index of the transaction within its block
+    pub position: i32,
+    pub txid: String,
+    pub wtxid: String,
+    pub version: i32,
+    pub locktime: i64,
+    pub size: i32,
+    pub vsize: i32,
+    pub weight: i64,
+    pub input_count: i32,
+    pub output_count: i32,
+    pub output_value: i64,
+    pub is_segwit: bool,
+    pub is_coinbase: bool,
+}
+
+#[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(treat_none_as_default_value = false)]
+pub struct Output {
+    pub height: i32,
+    pub txid: String,
+    pub vout: i32,
+    pub value: i64,
+    pub script: Vec<u8>,
+    pub script_type: String,
+    pub address: Option<String>,
+    /// Transaction spending this output, set once the spend has been parsed
+    pub spent_by_txid: Option<String>,
+    pub spent_height: Option<i32>,
+}
+
+#[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(treat_none_as_default_value = false)]
+pub struct Input {
+    pub height: i32,
+    pub txid: String,
+    pub vin: i32,
+    /// All zeros for coinbase inputs
+    pub prev_txid: String,
+    /// 0xffffffff for coinbase inputs
+    pub prev_vout: i64,
+    pub sequence: i64,
+    pub script_sig: Vec<u8>,
+    /// Consensus encoded witness, `None` if empty
+    pub witness: Option<Vec<u8>>,
+}
+
+#[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(treat_none_as_default_value = false)]
+pub struct OpReturn {
+    pub height: i32,
+    pub txid: String,
+    pub vout: i32,
+    pub value: i64,
+    /// Script bytes following `OP_RETURN`
+    pub payload: Vec<u8>,
+    /// Concatenated pushdata, `None` if the payload isn't a valid push sequence
+    pub data: Option<Vec<u8>>,
+    pub protocol: Option<String>,
+}
+
+/// Marks the output `prev_txid:prev_vout` as spent by `txid` at `height`
+#[derive(Debug)]
+pub struct Spend {
+    pub prev_txid: String,
+    pub prev_vout: i32,
+    pub txid: String,
+    pub height: i32,
+}
+
+#[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(treat_none_as_default_value = false)]
+pub struct VerificationIssue {
+    pub height: i32,
+    pub block_hash: Option<String>,
+    pub kind: String,
+    pub message: String,
+}
+
+/// Block of the index which isn't part of the active chain. The columns read from the block
+/// itself are `None` if its data isn't on disk.
+#[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(treat_none_as_default_value = false)]
+pub struct StaleBlock {
+    pub block_hash: String,
+    pub height: i32,
+    pub prev_hash: String,
+    pub version: i32,
+    pub merkle_root: String,
+    pub time: i64,
+    pub encoded_target: i64,
+    pub nonce: i64,
+    pub tx_count: Option<i32>,
+    pub pool: Option<String>,
+    /// Printable ASCII runs of the coinbase scriptSig, one per line
+    pub miner_tag: Option<String>,
+}
+
+/// Progress of the parser, stored as a single row with `id` 0
+#[derive(Debug, diesel::Selectable, diesel::Queryable, diesel::Insertable)]
+#[diesel(table_name = checkpoint)]
+pub struct Checkpoint {
+    pub id: i32,
+    pub coin: String,
+    /// Height of the last block whose data has been written
+    pub height: i64,
+}
+
+/// Rows buffered by the parser, written in a single transaction together with the checkpoint
+#[derive(Default)]
+pub struct Batch {
+    pub blocks: Vec<Block>,
+    pub block_fees: Vec<BlockFees>,
+    pub block_script_stats: Vec<BlockScriptStats>,
+    pub block_hashrates: Vec<BlockHashrate>,
+    pub coinbases: Vec<Coinbase>,
+    pub coinbase_payouts: Vec<CoinbasePayout>,
+    pub transactions: Vec<Transaction>,
+    pub outputs: Vec<Output>,
+    pub inputs: Vec<Input>,
+    pub spends: Vec<Spend>,
+    pub op_returns: Vec<OpReturn>,
+    pub verification_issues: Vec<VerificationIssue>,
+}
+
+impl Batch {
     #[must_use]
-    pub fn open(db_url: &str) -> Self {
-        Self {
-            pool: memory::Pool::open(db_url, MIGRATIONS),
-        }
+    pub fn len(&self) -> usize {
+        self.blocks.len()
     }
 
-    pub fn insert_blocks(&self, blocks: Vec<Block>) -> anyhow::Result<usize> {
-        Ok(diesel::insert_into(blocks::table)
-            .values(blocks)
-            .execute(&mut self.pool.get()?)?)
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.verification_issues.is_empty()
     }
+}
+
+/// Totals and averages over the blocks of a height range
+#[derive(Debug, Clone, PartialEq, diesel::Queryable)]
+pub struct BlockAggregates {
+    pub block_count: i64,
+    pub tx_count: i64,
+    pub turnover: i64,
+    pub miner_reward: i64,
+    /// Average size in bytes, 0 for a range without blocks
+    pub avg_size: f64,
+    pub avg_weight: f64,
+}
+
+/// Number of blocks mined by a pool, `pool` is `None` for unidentified miners
+#[derive(Debug, Clone, PartialEq, Eq, diesel::Queryable)]
+pub struct PoolBlocks {
+    pub pool: Option<String>,
+    pub block_count: i64,
+}
+
+/// Storage of the parsed tables. All backends create the same tables and columns.
+pub trait Backend: Send + Sync {
+    /// Writes all rows of `batch` and updates the checkpoint in a single transaction,
+    /// so an interrupted run can be resumed after the checkpoint.
+    fn write(&self, batch: Batch, checkpoint: Checkpoint) -> anyhow::Result<()>;
+
+    /// Drops the secondary indexes, so bulk loading doesn't update them with every write.
+    /// Lookups by txid, address or prevout are slow until [`Backend::create_indexes`].
+    fn drop_indexes(&self) -> anyhow::Result<()>;
+
+    /// Creates the secondary indexes again after bulk loading.
+    fn create_indexes(&self) -> anyhow::Result<()>;
+
+    fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>>;
+
+    fn insert_blocks(&self, blocks: Vec<Block>) -> anyhow::Result<usize>;
+
+    fn insert_verification_issues(&self, issues: Vec<VerificationIssue>) -> anyhow::Result<usize>;
+
+    fn verification_issues(&self) -> anyhow::Result<Vec<VerificationIssue>>;
+
+    /// Replaces all rows of `stale_blocks` in a single transaction, as blocks of an earlier run
+    /// may have become part of the active chain since.
+    fn replace_stale_blocks(&self, stale_blocks: Vec<StaleBlock>) -> anyhow::Result<()>;
+
+    /// Stale blocks ordered by height and hash
+    fn stale_blocks(&self) -> anyhow::Result<Vec<StaleBlock>>;
+
+    fn block(&self, height: i32) -> anyhow::Result<Block>;
+
+    /// Block with the hash `hash`, hex encoded in the byte order shown by Bitcoin Core.
+    /// Blocks migrated from a database without hashes are never found.
+    fn block_by_hash(&self, hash: &str) -> anyhow::Result<Option<Block>>;
+
+    /// Blocks in the height range, ordered by height
+    fn blocks_range(&self, heights: RangeInclusive<i32>) -> anyhow::Result<Vec<Block>>;
+
+    /// Blocks with a header time (Unix seconds) in `times`, ordered by height. Header times
+    /// aren't monotonic, so the heights may have gaps at the ends of the range.
+    fn blocks_by_time(&self, times: RangeInclusive<i64>) -> anyhow::Result<Vec<Block>>;
+
+    fn block_aggregates(&self, heights: RangeInclusive<i32>) -> anyhow::Result<BlockAggregates>;
+
+    /// Number of blocks per pool in the height range, the pool with the most blocks first
+    fn pool_counts(&self, heights: RangeInclusive<i32>) -> anyhow::Result<Vec<PoolBlocks>>;
+
+    fn block_fees(&self, height: i32) -> anyhow::Result<Option<BlockFees>>;
+
+    /// Hashrate estimates of the block at `height`, ordered by window size
+    fn block_hashrates(&self, height: i32) -> anyhow::Result<Vec<BlockHashrate>>;
+
+    /// Script type statistics of the block at `height`, ordered by script type
+    fn block_script_stats(&self, height: i32) -> anyhow::Result<Vec<BlockScriptStats>>;
+
+    fn coinbase(&self, height: i32) -> anyhow::Result<Coinbase>;
+
+    /// Outputs of the coinbase of the block at `height`, in output order
+    fn coinbase_payouts(&self, height: i32) -> anyhow::Result<Vec<CoinbasePayout>>;
 
-    pub fn block(&self, height: i32) -> anyhow::Result<Block> {
-        Ok(blocks::table
-            .select(Block::as_select())
-            .filter(blocks::height.eq(height))
-            .get_result(&mut self.pool.get()?)?)
+    /// Transactions of the block at `height`, in block order
+    fn transactions(&self, height: i32) -> anyhow::Result<Vec<Transaction>>;
+
+    /// Transactions of the blocks in the height range, in chain order
+    fn transactions_range(&self, heights: RangeInclusive<i32>) -> anyhow::Result<Vec<Transaction>>;
+
+    fn transactions_count(&self) -> anyhow::Result<i64>;
+
+    fn output(&self, txid: &str, vout: i32) -> anyhow::Result<Output>;
+
+    /// Outputs created by the blocks in the height range, in chain order
+    fn outputs_range(&self, heights: RangeInclusive<i32>) -> anyhow::Result<Vec<Output>>;
+
+    /// Inputs of the transaction `txid`, in input order
+    fn inputs(&self, txid: &str) -> anyhow::Result<Vec<Input>>;
+
+    /// OP_RETURN outputs of the block at `height`
+    fn op_returns(&self, height: i32) -> anyhow::Result<Vec<OpReturn>>;
+
+    fn blocks_count(&self) -> anyhow::Result<i64>;
+}
+
+impl Db {
+    /// Opens the database at `db_url`: DuckDB if it starts with `duckdb://`, PostgreSQL for
+    /// `postgres://` and `postgresql://` URLs and SQLite otherwise.
+    pub fn open(db_url: &str) -> anyhow::Result<Self> {
+        let backend: std::sync::Arc<dyn Backend> =
+            if let Some(path) = db_url.strip_prefix(DUCKDB_PREFIX) {
+                open_duckdb(path)?
+            } else if POSTGRES_PREFIXES
+                .iter()
+                .any(|prefix| db_url.starts_with(prefix))
+            {
+                std::sync::Arc::new(postgres::Postgres::open(db_url)?)
+            } else {
+                std::sync::Arc::new(sqlite::Sqlite::open(db_url)?)
+            };
+        Ok(Self { backend })
+    }
+
+    /// Opens the database at `db_url` like [`Db::open`] but without creating or migrating
+    /// tables, fails if the schema isn't up to date. Used to read databases written before.
+    pub fn open_existing(db_url: &str) -> anyhow::Result<Self> {
+        let backend: std::sync::Arc<dyn Backend> =
+            if let Some(path) = db_url.strip_prefix(DUCKDB_PREFIX) {
+                open_existing_duckdb(path)?
+            } else if POSTGRES_PREFIXES
+                .iter()
+                .any(|prefix| db_url.starts_with(prefix))
+            {
+                std::sync::Arc::new(postgres::Postgres::open_existing(db_url)?)
+            } else {
+                std::sync::Arc::new(sqlite::Sqlite::open_existing(db_url)?)
+            };
+        Ok(Self { backend })
     }
 
-    pub fn blocks_count(&self) -> anyhow::Result<i64> {
-        Ok(blocks::table.count().get_result(&mut self.pool.get()?)?)
+    /// Iterates over the transactions of the blocks in the height range in chain order,
+    /// loading [`PAGE_BLOCKS`] blocks at a time
+    pub fn iter_transactions(
+        &self,
+        heights: RangeInclusive<i32>,
+    ) -> impl Iterator<Item = anyhow::Result<Transaction>> + '_ {
+        paged(heights, |page| self.transactions_range(page))
+    }
+
+    /// Iterates over the outputs created by the blocks in the height range in chain order,
+    /// loading [`PAGE_BLOCKS`] blocks at a time
+    pub fn iter_outputs(
+        &self,
+        heights: RangeInclusive<i32>,
+    ) -> impl Iterator<Item = anyhow::Result<Output>> + '_ {
+        paged(heights, |page| self.outputs_range(page))
+    }
+}
+
+/// Number of blocks loaded per query by the iterators of [`Db`]
+pub const PAGE_BLOCKS: i32 = 100;
+
+/// Splits `heights` into pages of [`PAGE_BLOCKS`] and yields the rows loaded for each page
+fn paged<'a, T: 'a>(
+    heights: RangeInclusive<i32>,
+    load: impl Fn(RangeInclusive<i32>) -> anyhow::Result<Vec<T>> + 'a,
+) -> impl Iterator<Item = anyhow::Result<T>> + 'a {
+    let (start, end) = heights.into_inner();
+    let firsts = std::iter::successors((start <= end).then_some(start), move |first| {
+        first.checked_add(PAGE_BLOCKS).filter(|next| *next <= end)
+    });
+    firsts.flat_map(move |first| {
+        let (rows, error) = match load(first..=end.min(first.saturating_add(PAGE_BLOCKS - 1))) {
+            Ok(rows) => (rows, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        rows.into_iter().map(Ok).chain(error.map(Err))
+    })
+}
+
+#[cfg(feature = "duckdb")]
+fn open_duckdb(path: &str) -> anyhow::Result<std::sync::Arc<dyn Backend>> {
+    Ok(std::sync::Arc::new(duckdb::DuckDb::open(path)?))
+}
+
+#[cfg(not(feature = "duckdb"))]
+fn open_duckdb(_path: &str) -> anyhow::Result<std::sync::Arc<dyn Backend>> {
+    anyhow::bail!("DuckDB databases require building with the `duckdb` feature")
+}
+
+#[cfg(feature = "duckdb")]
+fn open_existing_duckdb(path: &str) -> anyhow::Result<std::sync::Arc<dyn Backend>> {
+    Ok(std::sync::Arc::new(duckdb::DuckDb::open_existing(path)?))
+}
+
+#[cfg(not(feature = "duckdb"))]
+fn open_existing_duckdb(_path: &str) -> anyhow::Result<std::sync::Arc<dyn Backend>> {
+    anyhow::bail!("DuckDB databases require building with the `duckdb` feature")
+}
+
+impl std::ops::Deref for Db {
+    type Target = dyn Backend;
+
+    fn deref(&self) -> &Self::Target {
+        self.backend.as_ref()
     }
 }
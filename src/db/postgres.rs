@@ -0,0 +1,132 @@
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel::RunQueryDsl;
+
+use super::memory::{self, Managed};
+use super::schema::{
+    block_fees, block_hashrates, block_script_stats, blocks, checkpoint, coinbase_payouts,
+    coinbases, inputs, op_returns, outputs, stale_blocks, transactions, verification_issues,
+};
+use super::{
+    Backend, Batch, Block, BlockAggregates, BlockFees, BlockHashrate, BlockScriptStats, Checkpoint,
+    Coinbase, CoinbasePayout, Input, OpReturn, Output, PoolBlocks, StaleBlock, Transaction,
+    VerificationIssue,
+};
+
+const MIGRATIONS: diesel_migrations::EmbeddedMigrations =
+    diesel_migrations::embed_migrations!("migrations/postgres");
+
+/// PostgreSQL database accessed through diesel, selected with a `postgres://` or
+/// `postgresql://` URL. Rows are bulk loaded with `COPY FROM`.
+pub struct Postgres {
+    pool: memory::Pool<diesel::pg::PgConnection>,
+}
+
+impl Postgres {
+    pub fn open(db_url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            pool: memory::Pool::open(
+                db_url,
+                MIGRATIONS,
+                Box::new(diesel::r2d2::NopConnectionCustomizer),
+            )?,
+        })
+    }
+
+    pub fn open_existing(db_url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            pool: memory::Pool::open_existing(
+                db_url,
+                MIGRATIONS,
+                Box::new(diesel::r2d2::NopConnectionCustomizer),
+            )?,
+        })
+    }
+}
+
+impl Backend for Postgres {
+    fn write(&self, mut batch: Batch, checkpoint: Checkpoint) -> anyhow::Result<()> {
+        self.pool.get()?.transaction::<_, anyhow::Error, _>(|conn| {
+            // `COPY` can't replace rows, so the outputs of the duplicate coinbases before BIP30
+            // are deleted first. Only coinbases can repeat a txid.
+            let mut coinbases = std::collections::HashMap::<String, i32>::new();
+            for tx in batch.transactions.iter().filter(|tx| tx.is_coinbase) {
+                coinbases.insert(tx.txid.clone(), tx.height);
+            }
+            let is_latest =
+                |txid: &str, height: i32| coinbases.get(txid).copied().unwrap_or(height) == height;
+            batch.outputs.retain(|o| is_latest(&o.txid, o.height));
+            batch.inputs.retain(|i| is_latest(&i.txid, i.height));
+            batch.op_returns.retain(|o| is_latest(&o.txid, o.height));
+            let txids: Vec<_> = coinbases.keys().cloned().collect();
+            diesel::delete(outputs::table.filter(outputs::txid.eq_any(&txids))).execute(conn)?;
+            diesel::delete(inputs::table.filter(inputs::txid.eq_any(&txids))).execute(conn)?;
+            diesel::delete(op_returns::table.filter(op_returns::txid.eq_any(&txids)))
+                .execute(conn)?;
+
+            diesel::copy_from(blocks::table)
+                .from_insertable(batch.blocks)
+                .execute(conn)?;
+            diesel::copy_from(block_fees::table)
+                .from_insertable(batch.block_fees)
+                .execute(conn)?;
+            diesel::copy_from(block_script_stats::table)
+                .from_insertable(batch.block_script_stats)
+                .execute(conn)?;
+            diesel::copy_from(block_hashrates::table)
+                .from_insertable(batch.block_hashrates)
+                .execute(conn)?;
+            diesel::copy_from(coinbases::table)
+                .from_insertable(batch.coinbases)
+                .execute(conn)?;
+            diesel::copy_from(coinbase_payouts::table)
+                .from_insertable(batch.coinbase_payouts)
+                .execute(conn)?;
+            diesel::copy_from(transactions::table)
+                .from_insertable(batch.transactions)
+                .execute(conn)?;
+            diesel::copy_from(outputs::table)
+                .from_insertable(batch.outputs)
+                .execute(conn)?;
+            diesel::copy_from(inputs::table)
+                .from_insertable(batch.inputs)
+                .execute(conn)?;
+            for spend in &batch.spends {
+                diesel::update(outputs::table.find((&spend.prev_txid, spend.prev_vout)))
+                    .set((
+                        outputs::spent_by_txid.eq(&spend.txid),
+                        outputs::spent_height.eq(spend.height),
+                    ))
+                    .execute(conn)?;
+            }
+            diesel::copy_from(op_returns::table)
+                .from_insertable(batch.op_returns)
+                .execute(conn)?;
+            diesel::copy_from(verification_issues::table)
+                .from_insertable(batch.verification_issues)
+                .execute(conn)?;
+            diesel::insert_into(checkpoint::table)
+                .values(checkpoint)
+                .on_conflict(checkpoint::id)
+                .do_update()
+                .set((
+                    checkpoint::coin.eq(excluded(checkpoint::coin)),
+                    checkpoint::height.eq(excluded(checkpoint::height)),
+                ))
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+
+    fn replace_stale_blocks(&self, stale_blocks: Vec<StaleBlock>) -> anyhow::Result<()> {
+        self.pool.get()?.transaction::<_, anyhow::Error, _>(|conn| {
+            diesel::delete(stale_blocks::table).execute(conn)?;
+            diesel::copy_from(stale_blocks::table)
+                .from_insertable(stale_blocks)
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+
+    super::queries::diesel_queries!();
+}
@@ -0,0 +1,262 @@
+/// Secondary indexes of the diesel backends as name and indexed columns. They are the same in
+/// the SQLite and PostgreSQL migrations.
+pub(crate) const INDEXES: [(&str, &str); 5] = [
+    ("blocks_block_hash", "blocks (block_hash)"),
+    ("transactions_txid", "transactions (txid)"),
+    ("outputs_address", "outputs (address)"),
+    ("inputs_prevout", "inputs (prev_txid, prev_vout)"),
+    ("op_returns_protocol", "op_returns (protocol)"),
+];
+
+/// Select list of [`BlockAggregates`](super::BlockAggregates), valid for all backends
+pub(crate) const BLOCK_AGGREGATES: &str = "COUNT(*), \
+    CAST(COALESCE(SUM(tx_count), 0) AS BIGINT), \
+    CAST(COALESCE(SUM(turnover), 0) AS BIGINT), \
+    CAST(COALESCE(SUM(miner_reward), 0) AS BIGINT), \
+    CAST(COALESCE(AVG(size), 0) AS DOUBLE PRECISION), \
+    CAST(COALESCE(AVG(weight), 0) AS DOUBLE PRECISION)";
+
+/// Implements the reading methods of [`Backend`](super::Backend) inside its `impl` for a struct
+/// with a diesel connection `pool`. The queries are the same for all diesel backends.
+macro_rules! diesel_queries {
+    () => {
+        fn drop_indexes(&self) -> anyhow::Result<()> {
+            use diesel::connection::SimpleConnection as _;
+            let mut conn = self.pool.get()?;
+            for (name, _) in super::queries::INDEXES {
+                conn.batch_execute(&format!("DROP INDEX IF EXISTS {name}"))?;
+            }
+            Ok(())
+        }
+
+        fn create_indexes(&self) -> anyhow::Result<()> {
+            use diesel::connection::SimpleConnection as _;
+            let mut conn = self.pool.get()?;
+            for (name, columns) in super::queries::INDEXES {
+                conn.batch_execute(&format!("CREATE INDEX IF NOT EXISTS {name} ON {columns}"))?;
+            }
+            Ok(())
+        }
+
+        fn checkpoint(&self) -> anyhow::Result<Option<Checkpoint>> {
+            Ok(checkpoint::table
+                .select(Checkpoint::as_select())
+                .filter(checkpoint::id.eq(0))
+                .first(&mut self.pool.get()?)
+                .optional()?)
+        }
+
+        fn insert_blocks(&self, blocks: Vec<Block>) -> anyhow::Result<usize> {
+            Ok(diesel::insert_into(blocks::table)
+                .values(blocks)
+                .execute(&mut self.pool.get()?)?)
+        }
+
+        fn insert_verification_issues(
+            &self,
+            issues: Vec<VerificationIssue>,
+        ) -> anyhow::Result<usize> {
+            Ok(diesel::insert_into(verification_issues::table)
+                .values(issues)
+                .execute(&mut self.pool.get()?)?)
+        }
+
+        fn verification_issues(&self) -> anyhow::Result<Vec<VerificationIssue>> {
+            Ok(verification_issues::table
+                .select(VerificationIssue::as_select())
+                .order(verification_issues::id)
+                .load(&mut self.pool.get()?)?)
+        }
+
+        fn stale_blocks(&self) -> anyhow::Result<Vec<StaleBlock>> {
+            Ok(stale_blocks::table
+                .select(StaleBlock::as_select())
+                .order((stale_blocks::height, stale_blocks::block_hash))
+                .load(&mut self.pool.get()?)?)
+        }
+
+        fn block(&self, height: i32) -> anyhow::Result<Block> {
+            Ok(blocks::table
+                .select(Block::as_select())
+                .filter(blocks::height.eq(height))
+                .get_result(&mut self.pool.get()?)?)
+        }
+
+        fn block_by_hash(&self, hash: &str) -> anyhow::Result<Option<Block>> {
+            if hash.is_empty() {
+                return Ok(None);
+            }
+            Ok(blocks::table
+                .select(Block::as_select())
+                .filter(blocks::block_hash.eq(hash))
+                .first(&mut self.pool.get()?)
+                .optional()?)
+        }
+
+        fn blocks_range(
+            &self,
+            heights: std::ops::RangeInclusive<i32>,
+        ) -> anyhow::Result<Vec<Block>> {
+            Ok(blocks::table
+                .select(Block::as_select())
+                .filter(blocks::height.between(*heights.start(), *heights.end()))
+                .order(blocks::height)
+                .load(&mut self.pool.get()?)?)
+        }
+
+        fn blocks_by_time(
+            &self,
+            times: std::ops::RangeInclusive<i64>,
+        ) -> anyhow::Result<Vec<Block>> {
+            Ok(blocks::table
+                .select(Block::as_select())
+                .filter(blocks::time.between(*times.start(), *times.end()))
+                .order(blocks::height)
+                .load(&mut self.pool.get()?)?)
+        }
+
+        fn block_aggregates(
+            &self,
+            heights: std::ops::RangeInclusive<i32>,
+        ) -> anyhow::Result<BlockAggregates> {
+            use diesel::sql_types::{BigInt, Double};
+            Ok(blocks::table
+                .filter(blocks::height.between(*heights.start(), *heights.end()))
+                .select(diesel::dsl::sql::<(
+                    BigInt,
+                    BigInt,
+                    BigInt,
+                    BigInt,
+                    Double,
+                    Double,
+                )>(super::queries::BLOCK_AGGREGATES))
+                .get_result(&mut self.pool.get()?)?)
+        }
+
+        fn pool_counts(
+            &self,
+            heights: std::ops::RangeInclusive<i32>,
+        ) -> anyhow::Result<Vec<PoolBlocks>> {
+            let mut counts: Vec<PoolBlocks> = blocks::table
+                .filter(blocks::height.between(*heights.start(), *heights.end()))
+                .group_by(blocks::pool)
+                .select((blocks::pool, diesel::dsl::count_star()))
+                .load(&mut self.pool.get()?)?;
+            // sorted here, as SQLite and PostgreSQL order NULL differently
+            counts.sort_by(|a, b| {
+                b.block_count
+                    .cmp(&a.block_count)
+                    .then_with(|| a.pool.cmp(&b.pool))
+            });
+            Ok(counts)
+        }
+
+        fn block_fees(&self, height: i32) -> anyhow::Result<Option<BlockFees>> {
+            Ok(block_fees::table
+                .find(height)
+                .select(BlockFees::as_select())
+                .first(&mut self.pool.get()?)
+                .optional()?)
+        }
+
+        fn block_hashrates(&self, height: i32) -> anyhow::Result<Vec<BlockHashrate>> {
+            Ok(block_hashrates::table
+                .select(BlockHashrate::as_select())
+                .filter(block_hashrates::height.eq(height))
+                .order(block_hashrates::window_size)
+                .load(&mut self.pool.get()?)?)
+        }
+
+        fn block_script_stats(&self, height: i32) -> anyhow::Result<Vec<BlockScriptStats>> {
+            Ok(block_script_stats::table
+                .select(BlockScriptStats::as_select())
+                .filter(block_script_stats::height.eq(height))
+                .order(block_script_stats::script_type)
+                .load(&mut self.pool.get()?)?)
+        }
+
+        fn coinbase(&self, height: i32) -> anyhow::Result<Coinbase> {
+            Ok(coinbases::table
+                .find(height)
+                .select(Coinbase::as_select())
+                .get_result(&mut self.pool.get()?)?)
+        }
+
+        fn coinbase_payouts(&self, height: i32) -> anyhow::Result<Vec<CoinbasePayout>> {
+            Ok(coinbase_payouts::table
+                .select(CoinbasePayout::as_select())
+                .filter(coinbase_payouts::height.eq(height))
+                .order(coinbase_payouts::vout)
+                .load(&mut self.pool.get()?)?)
+        }
+
+        fn transactions(&self, height: i32) -> anyhow::Result<Vec<Transaction>> {
+            Ok(transactions::table
+                .select(Transaction::as_select())
+                .filter(transactions::height.eq(height))
+                .order(transactions::position)
+                .load(&mut self.pool.get()?)?)
+        }
+
+        fn transactions_range(
+            &self,
+            heights: std::ops::RangeInclusive<i32>,
+        ) -> anyhow::Result<Vec<Transaction>> {
+            Ok(transactions::table
+                .select(Transaction::as_select())
+                .filter(transactions::height.between(*heights.start(), *heights.end()))
+                .order((transactions::height, transactions::position))
+                .load(&mut self.pool.get()?)?)
+        }
+
+        fn transactions_count(&self) -> anyhow::Result<i64> {
+            Ok(transactions::table
+                .count()
+                .get_result(&mut self.pool.get()?)?)
+        }
+
+        fn output(&self, txid: &str, vout: i32) -> anyhow::Result<Output> {
+            Ok(outputs::table
+                .find((txid, vout))
+                .select(Output::as_select())
+                .get_result(&mut self.pool.get()?)?)
+        }
+
+        fn outputs_range(
+            &self,
+            heights: std::ops::RangeInclusive<i32>,
+        ) -> anyhow::Result<Vec<Output>> {
+            Ok(outputs::table
+                .inner_join(
+                    transactions::table.on(transactions::txid
+                        .eq(outputs::txid)
+                        .and(transactions::height.eq(outputs::height))),
+                )
+                .select(Output::as_select())
+                .filter(outputs::height.between(*heights.start(), *heights.end()))
+                .order((outputs::height, transactions::position, outputs::vout))
+                .load(&mut self.pool.get()?)?)
+        }
+
+        fn inputs(&self, txid: &str) -> anyhow::Result<Vec<Input>> {
+            Ok(inputs::table
+                .select(Input::as_select())
+                .filter(inputs::txid.eq(txid))
+                .order(inputs::vin)
+                .load(&mut self.pool.get()?)?)
+        }
+
+        fn op_returns(&self, height: i32) -> anyhow::Result<Vec<OpReturn>> {
+            Ok(op_returns::table
+                .select(OpReturn::as_select())
+                .filter(op_returns::height.eq(height))
+                .load(&mut self.pool.get()?)?)
+        }
+
+        fn blocks_count(&self) -> anyhow::Result<i64> {
+            Ok(blocks::table.count().get_result(&mut self.pool.get()?)?)
+        }
+    };
+}
+
+pub(crate) use diesel_queries;
@@ -1,11 +1,50 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    block_fees (height) {
+        height -> Integer,
+        total_fee -> BigInt,
+        subsidy -> BigInt,
+        avg_fee -> BigInt,
+        min_fee -> BigInt,
+        max_fee -> BigInt,
+        median_fee -> BigInt,
+        avg_feerate -> BigInt,
+        min_feerate -> BigInt,
+        max_feerate -> BigInt,
+        feerate_p10 -> BigInt,
+        feerate_p25 -> BigInt,
+        feerate_p50 -> BigInt,
+        feerate_p75 -> BigInt,
+        feerate_p90 -> BigInt,
+    }
+}
+
+diesel::table! {
+    block_hashrates (height, window_size) {
+        height -> Integer,
+        window_size -> Integer,
+        hashrate -> Double,
+    }
+}
+
+diesel::table! {
+    block_script_stats (height, script_type) {
+        height -> Integer,
+        script_type -> Text,
+        output_count -> Integer,
+        output_value -> BigInt,
+        input_count -> Nullable<Integer>,
+        input_value -> Nullable<BigInt>,
+    }
+}
+
 diesel::table! {
     blocks (height) {
         height -> Integer,
         version -> Integer,
-        time -> Integer,
-        encoded_target -> Integer,
+        time -> BigInt,
+        encoded_target -> BigInt,
         nonce -> BigInt,
         tx_count -> Integer,
         size -> Integer,
@@ -13,5 +52,142 @@ diesel::table! {
         turnover -> BigInt,
         miner_reward -> BigInt,
         pool -> Nullable<Text>,
+        difficulty -> Double,
+        work -> Text,
+        chainwork -> Text,
+        block_hash -> Text,
+        prev_hash -> Text,
+    }
+}
+
+diesel::table! {
+    checkpoint (id) {
+        id -> Integer,
+        coin -> Text,
+        height -> BigInt,
+    }
+}
+
+diesel::table! {
+    coinbase_payouts (height, vout) {
+        height -> Integer,
+        vout -> Integer,
+        value -> BigInt,
+        script -> Binary,
+        address -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    coinbases (height) {
+        height -> Integer,
+        txid -> Text,
+        script_sig -> Binary,
+        tags -> Text,
+        bip34_height -> Nullable<BigInt>,
+        bip34_valid -> Bool,
+        extranonce_length -> Nullable<Integer>,
+        witness_commitment -> Bool,
+        merge_mining -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    inputs (txid, vin) {
+        height -> Integer,
+        txid -> Text,
+        vin -> Integer,
+        prev_txid -> Text,
+        prev_vout -> BigInt,
+        sequence -> BigInt,
+        script_sig -> Binary,
+        witness -> Nullable<Binary>,
+    }
+}
+
+diesel::table! {
+    op_returns (txid, vout) {
+        height -> Integer,
+        txid -> Text,
+        vout -> Integer,
+        value -> BigInt,
+        payload -> Binary,
+        data -> Nullable<Binary>,
+        protocol -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    outputs (txid, vout) {
+        height -> Integer,
+        txid -> Text,
+        vout -> Integer,
+        value -> BigInt,
+        script -> Binary,
+        script_type -> Text,
+        address -> Nullable<Text>,
+        spent_by_txid -> Nullable<Text>,
+        spent_height -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    stale_blocks (block_hash) {
+        block_hash -> Text,
+        height -> Integer,
+        prev_hash -> Text,
+        version -> Integer,
+        merkle_root -> Text,
+        time -> BigInt,
+        encoded_target -> BigInt,
+        nonce -> BigInt,
+        tx_count -> Nullable<Integer>,
+        pool -> Nullable<Text>,
+        miner_tag -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    transactions (height, position) {
+        height -> Integer,
+        position -> Integer,
+        txid -> Text,
+        wtxid -> Text,
+        version -> Integer,
+        locktime -> BigInt,
+        size -> Integer,
+        vsize -> Integer,
+        weight -> BigInt,
+        input_count -> Integer,
+        output_count -> Integer,
+        output_value -> BigInt,
+        is_segwit -> Bool,
+        is_coinbase -> Bool,
+    }
+}
+
+diesel::table! {
+    verification_issues (id) {
+        id -> Integer,
+        height -> Integer,
+        block_hash -> Nullable<Text>,
+        kind -> Text,
+        message -> Text,
     }
 }
+
+diesel::allow_tables_to_appear_in_same_query!(
+    block_fees,
+    block_hashrates,
+    block_script_stats,
+    blocks,
+    checkpoint,
+    coinbase_payouts,
+    coinbases,
+    inputs,
+    op_returns,
+    outputs,
+    stale_blocks,
+    transactions,
+    verification_issues,
+);
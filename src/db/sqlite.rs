@@ -0,0 +1,136 @@
+use diesel::prelude::*;
+use diesel::RunQueryDsl;
+
+use super::memory::{self, Managed};
+use super::schema::{
+    block_fees, block_hashrates, block_script_stats, blocks, checkpoint, coinbase_payouts,
+    coinbases, inputs, op_returns, outputs, stale_blocks, transactions, verification_issues,
+};
+use super::{
+    Backend, Batch, Block, BlockAggregates, BlockFees, BlockHashrate, BlockScriptStats, Checkpoint,
+    Coinbase, CoinbasePayout, Input, OpReturn, Output, PoolBlocks, StaleBlock, Transaction,
+    VerificationIssue,
+};
+
+/// Rows per insert statement. A chunk of the widest table, `blocks` with 16 columns, binds 1024
+/// parameters, which needs SQLite 3.32 or later. Older versions allow at most 999.
+const INSERT_CHUNK_SIZE: usize = 64;
+
+/// Pragmas set on every connection. WAL with `synchronous = NORMAL` only syncs at checkpoints
+/// and still can't corrupt the database, a crash may only lose the last transactions.
+const PRAGMAS: &str = "PRAGMA busy_timeout = 5000;
+    PRAGMA journal_mode = WAL;
+    PRAGMA synchronous = NORMAL;
+    PRAGMA cache_size = -262144;
+    PRAGMA temp_store = MEMORY;";
+
+/// Inserts `rows` in statements of [`INSERT_CHUNK_SIZE`] rows. Full chunks always have the same
+/// SQL, so the statement is prepared once and reused from the connection's statement cache.
+macro_rules! insert_chunked {
+    ($insert:ident, $table:expr, $rows:expr, $conn:expr) => {{
+        let mut chunks = $rows.chunks_exact(INSERT_CHUNK_SIZE);
+        for chunk in &mut chunks {
+            let chunk: &[_; INSERT_CHUNK_SIZE] = chunk.try_into()?;
+            diesel::$insert($table).values(chunk).execute($conn)?;
+        }
+        if !chunks.remainder().is_empty() {
+            diesel::$insert($table)
+                .values(chunks.remainder())
+                .execute($conn)?;
+        }
+    }};
+}
+
+const MIGRATIONS: diesel_migrations::EmbeddedMigrations =
+    diesel_migrations::embed_migrations!("migrations/sqlite");
+
+/// SQLite database accessed through diesel
+pub struct Sqlite {
+    pool: memory::Pool,
+}
+
+impl Sqlite {
+    pub fn open(db_url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            pool: memory::Pool::open(db_url, MIGRATIONS, Box::new(Pragmas))?,
+        })
+    }
+
+    pub fn open_existing(db_url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            pool: memory::Pool::open_existing(db_url, MIGRATIONS, Box::new(Pragmas))?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Pragmas;
+
+impl diesel::r2d2::CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for Pragmas {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        use diesel::connection::SimpleConnection as _;
+        conn.batch_execute(PRAGMAS)
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+impl Backend for Sqlite {
+    fn write(&self, batch: Batch, checkpoint: Checkpoint) -> anyhow::Result<()> {
+        self.pool.get()?.transaction::<_, anyhow::Error, _>(|conn| {
+            insert_chunked!(insert_into, blocks::table, batch.blocks, conn);
+            insert_chunked!(insert_into, block_fees::table, batch.block_fees, conn);
+            insert_chunked!(
+                insert_into,
+                block_script_stats::table,
+                batch.block_script_stats,
+                conn
+            );
+            insert_chunked!(
+                insert_into,
+                block_hashrates::table,
+                batch.block_hashrates,
+                conn
+            );
+            insert_chunked!(insert_into, coinbases::table, batch.coinbases, conn);
+            insert_chunked!(
+                insert_into,
+                coinbase_payouts::table,
+                batch.coinbase_payouts,
+                conn
+            );
+            insert_chunked!(insert_into, transactions::table, batch.transactions, conn);
+            // replace, as the duplicate coinbases before BIP30 overwrite the earlier outputs
+            insert_chunked!(replace_into, outputs::table, batch.outputs, conn);
+            insert_chunked!(replace_into, inputs::table, batch.inputs, conn);
+            for spend in &batch.spends {
+                diesel::update(outputs::table.find((&spend.prev_txid, spend.prev_vout)))
+                    .set((
+                        outputs::spent_by_txid.eq(&spend.txid),
+                        outputs::spent_height.eq(spend.height),
+                    ))
+                    .execute(conn)?;
+            }
+            insert_chunked!(replace_into, op_returns::table, batch.op_returns, conn);
+            insert_chunked!(
+                insert_into,
+                verification_issues::table,
+                batch.verification_issues,
+                conn
+            );
+            diesel::replace_into(checkpoint::table)
+                .values(checkpoint)
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+
+    fn replace_stale_blocks(&self, stale_blocks: Vec<StaleBlock>) -> anyhow::Result<()> {
+        self.pool.get()?.transaction::<_, anyhow::Error, _>(|conn| {
+            diesel::delete(stale_blocks::table).execute(conn)?;
+            insert_chunked!(insert_into, stale_blocks::table, stale_blocks, conn);
+            Ok(())
+        })
+    }
+
+    super::queries::diesel_queries!();
+}
@@ -0,0 +1,370 @@
+use bitcoin_pool_identification::PoolIdentification;
+
+use crate::db::{Batch, Checkpoint, Db};
+use crate::parser::coinbase::CoinbaseInfo;
+use crate::parser::opreturn::OpReturn;
+use crate::parser::script::ScriptType;
+use crate::parser::undo::BlockUndo;
+use crate::parser::verify::VerificationIssue;
+use crate::parser::visitor::{BlockData, BlockVisitor};
+use crate::parser::{fees, pow, script};
+
+/// Writes all tables of the database and checkpoints the progress with every write
+pub struct DbWriter {
+    db: Db,
+    coin: String,
+    network: bitcoin::Network,
+    batch: Batch,
+    /// Number of blocks buffered before they are written in one transaction
+    batch_size: usize,
+    /// Drops the secondary indexes on start and creates them on completion
+    defer_indexes: bool,
+    /// Whether the indexes have been dropped and not yet created again
+    indexes_dropped: bool,
+    /// Rows replacing the `stale_blocks` table on completion
+    stale_blocks: Vec<crate::db::StaleBlock>,
+}
+
+impl DbWriter {
+    #[must_use]
+    pub fn new(
+        db: Db,
+        coin: String,
+        network: bitcoin::Network,
+        batch_size: usize,
+        defer_indexes: bool,
+    ) -> Self {
+        Self {
+            db,
+            coin,
+            network,
+            batch: Batch::default(),
+            batch_size,
+            defer_indexes,
+            indexes_dropped: false,
+            stale_blocks: Vec::new(),
+        }
+    }
+
+    /// Writes the buffered rows and marks everything up to `height` as done.
+    fn flush(&mut self, height: u64) -> anyhow::Result<()> {
+        let batch = std::mem::take(&mut self.batch);
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let checkpoint = Checkpoint {
+            id: 0,
+            coin: self.coin.clone(),
+            height: height.try_into()?,
+        };
+        self.db.write(batch, checkpoint)
+    }
+}
+
+impl BlockVisitor for DbWriter {
+    fn on_start(&mut self, _height: u64) -> anyhow::Result<()> {
+        if self.defer_indexes {
+            tracing::info!(target: "parser", "Dropping database indexes until parsing completes ...");
+            self.db.drop_indexes()?;
+            self.indexes_dropped = true;
+        } else {
+            // a killed run with --defer-indexes may have left them dropped
+            self.db.create_indexes()?;
+        }
+        Ok(())
+    }
+
+    fn on_block(&mut self, data: &BlockData) -> anyhow::Result<()> {
+        let batch = &mut self.batch;
+        push_block(batch, data)?;
+        if let Some(coinbase) = data.block.coinbase() {
+            push_coinbase(batch, coinbase, data.height, self.network)?;
+        }
+        if let Some(undo) = data.undo {
+            push_fees(batch, data.block, undo, data.height)?;
+        }
+        push_script_stats(batch, data)?;
+        for (position, tx) in data.block.txdata.iter().enumerate() {
+            push_transaction(batch, tx, position, data.height, self.network)?;
+        }
+
+        if self.batch.len() >= self.batch_size {
+            self.flush(data.height)?;
+        }
+        Ok(())
+    }
+
+    fn on_issue(&mut self, issue: &VerificationIssue) -> anyhow::Result<()> {
+        self.batch
+            .verification_issues
+            .push(crate::db::VerificationIssue {
+                height: issue.height.try_into()?,
+                block_hash: issue.block_hash.map(|h| h.to_string()),
+                kind: issue.kind.to_string(),
+                message: issue.message.clone(),
+            });
+        Ok(())
+    }
+
+    fn on_stale_block(
+        &mut self,
+        header: &bitcoin::blockdata::block::Header,
+        height: u64,
+        block: Option<&bitcoin::Block>,
+    ) -> anyhow::Result<()> {
+        let info = block
+            .and_then(bitcoin::Block::coinbase)
+            .and_then(|cb| CoinbaseInfo::new(cb, self.network));
+        self.stale_blocks.push(crate::db::StaleBlock {
+            block_hash: header.block_hash().to_string(),
+            height: height.try_into()?,
+            prev_hash: header.prev_blockhash.to_string(),
+            version: header.version.to_consensus(),
+            merkle_root: header.merkle_root.to_string(),
+            time: header.time.into(),
+            encoded_target: header.bits.to_consensus().into(),
+            nonce: header.nonce.into(),
+            tx_count: block.map(|b| b.txdata.len().try_into()).transpose()?,
+            pool: block
+                .and_then(bitcoin::Block::identify_pool)
+                .map(|p| p.name),
+            miner_tag: info.map(|info| info.tags.join("\n")),
+        });
+        Ok(())
+    }
+
+    fn on_complete(&mut self, height: u64) -> anyhow::Result<()> {
+        self.flush(height)?;
+        // also when empty, the stale blocks of an earlier run may be gone from the index
+        self.db
+            .replace_stale_blocks(std::mem::take(&mut self.stale_blocks))?;
+        if self.indexes_dropped {
+            tracing::info!(target: "parser", "Creating database indexes ...");
+            self.db.create_indexes()?;
+            self.indexes_dropped = false;
+        }
+        Ok(())
+    }
+}
+
+/// Adds the row of the block and its hashrate estimates
+fn push_block(batch: &mut Batch, data: &BlockData) -> anyhow::Result<()> {
+    let BlockData { height, block, .. } = *data;
+    let turnover: u64 = block
+        .txdata
+        .iter()
+        .flat_map(|tx| tx.output.iter().map(|output| output.value))
+        .sum();
+
+    let miner_reward = block
+        .coinbase()
+        .map_or_else(|| 0, |cb| cb.output.iter().map(|output| output.value).sum());
+
+    let pool = block.identify_pool().map(|p| p.name);
+
+    batch.blocks.push(crate::db::Block {
+        height: height.try_into()?,
+        version: block.header.version.to_consensus(),
+        time: block.header.time.into(),
+        encoded_target: block.header.bits.to_consensus().into(),
+        nonce: block.header.nonce.into(),
+        tx_count: block.txdata.len().try_into()?,
+        size: block.size().try_into()?,
+        weight: block.weight().to_wu().try_into()?,
+        turnover: turnover.try_into()?,
+        miner_reward: miner_reward.try_into()?,
+        pool,
+        difficulty: pow::difficulty(block.header.bits.to_consensus()),
+        work: pow::work_to_hex(block.header.work()),
+        chainwork: pow::work_to_hex(data.chainwork),
+        hash: block.block_hash().to_string(),
+        prev_hash: block.header.prev_blockhash.to_string(),
+    });
+    for (window, hashrate) in data.hashrates {
+        batch.block_hashrates.push(crate::db::BlockHashrate {
+            height: height.try_into()?,
+            window_size: (*window).try_into()?,
+            hashrate: *hashrate,
+        });
+    }
+    Ok(())
+}
+
+/// Adds the coinbase and its payouts, nothing if the coinbase has no inputs
+fn push_coinbase(
+    batch: &mut Batch,
+    coinbase: &bitcoin::Transaction,
+    height: u64,
+    network: bitcoin::Network,
+) -> anyhow::Result<()> {
+    let Some(info) = CoinbaseInfo::new(coinbase, network) else {
+        return Ok(());
+    };
+    batch.coinbases.push(crate::db::Coinbase {
+        height: height.try_into()?,
+        txid: coinbase.txid().to_string(),
+        script_sig: coinbase.input[0].script_sig.to_bytes(),
+        tags: info.tags.join("\n"),
+        bip34_height: info.bip34_height.map(i64::try_from).transpose()?,
+        bip34_valid: info.bip34_height == Some(height),
+        extranonce_length: info.extranonce_length.map(i32::try_from).transpose()?,
+        witness_commitment: info.witness_commitment,
+        merge_mining: (!info.merge_mining.is_empty()).then(|| info.merge_mining.join(",")),
+    });
+    for (vout, payout) in info.payouts.into_iter().enumerate() {
+        batch.coinbase_payouts.push(crate::db::CoinbasePayout {
+            height: height.try_into()?,
+            vout: vout.try_into()?,
+            value: payout.value.try_into()?,
+            script: payout.script_pubkey.into_bytes(),
+            address: payout.address,
+        });
+    }
+    Ok(())
+}
+
+/// Adds the fee statistics, logging instead of failing if the undo data doesn't match the block
+fn push_fees(
+    batch: &mut Batch,
+    block: &bitcoin::Block,
+    undo: &BlockUndo,
+    height: u64,
+) -> anyhow::Result<()> {
+    match fees::fee_stats(block, undo, height) {
+        Ok(stats) => batch.block_fees.push(crate::db::BlockFees {
+            height: height.try_into()?,
+            total_fee: stats.total_fee.try_into()?,
+            subsidy: stats.subsidy.try_into()?,
+            avg_fee: stats.avg_fee.try_into()?,
+            min_fee: stats.min_fee.try_into()?,
+            max_fee: stats.max_fee.try_into()?,
+            median_fee: stats.median_fee.try_into()?,
+            avg_feerate: stats.avg_feerate.try_into()?,
+            min_feerate: stats.min_feerate.try_into()?,
+            max_feerate: stats.max_feerate.try_into()?,
+            feerate_p10: stats.feerate_percentiles[0].try_into()?,
+            feerate_p25: stats.feerate_percentiles[1].try_into()?,
+            feerate_p50: stats.feerate_percentiles[2].try_into()?,
+            feerate_p75: stats.feerate_percentiles[3].try_into()?,
+            feerate_p90: stats.feerate_percentiles[4].try_into()?,
+        }),
+        Err(e) => {
+            tracing::warn!(target: "parser", "Unable to compute fees of block {}: {}", height, e);
+        }
+    }
+    Ok(())
+}
+
+/// Adds the output and input counts and values per script type
+fn push_script_stats(batch: &mut Batch, data: &BlockData) -> anyhow::Result<()> {
+    let BlockData {
+        height,
+        block,
+        undo,
+        ..
+    } = *data;
+    for (script_type, stats) in script::script_stats(block, undo) {
+        batch.block_script_stats.push(crate::db::BlockScriptStats {
+            height: height.try_into()?,
+            script_type: script_type.to_string(),
+            output_count: stats.output_count.try_into()?,
+            output_value: stats.output_value.try_into()?,
+            input_count: undo.map(|_| stats.input_count.try_into()).transpose()?,
+            input_value: undo.map(|_| stats.input_value.try_into()).transpose()?,
+        });
+    }
+    Ok(())
+}
+
+/// Adds the transaction with its outputs, OP_RETURN payloads, inputs and spends
+fn push_transaction(
+    batch: &mut Batch,
+    tx: &bitcoin::Transaction,
+    position: usize,
+    height: u64,
+    network: bitcoin::Network,
+) -> anyhow::Result<()> {
+    let txid = tx.txid().to_string();
+    batch.transactions.push(crate::db::Transaction {
+        height: height.try_into()?,
+        position: position.try_into()?,
+        txid: txid.clone(),
+        wtxid: tx.wtxid().to_string(),
+        version: tx.version,
+        locktime: tx.lock_time.to_consensus_u32().into(),
+        size: tx.size().try_into()?,
+        vsize: tx.vsize().try_into()?,
+        weight: tx.weight().to_wu().try_into()?,
+        input_count: tx.input.len().try_into()?,
+        output_count: tx.output.len().try_into()?,
+        output_value: tx
+            .output
+            .iter()
+            .map(|output| output.value)
+            .sum::<u64>()
+            .try_into()?,
+        is_segwit: tx.input.iter().any(|input| !input.witness.is_empty()),
+        is_coinbase: tx.is_coin_base(),
+    });
+
+    for (vout, output) in tx.output.iter().enumerate() {
+        batch.outputs.push(crate::db::Output {
+            height: height.try_into()?,
+            txid: txid.clone(),
+            vout: vout.try_into()?,
+            value: output.value.try_into()?,
+            script: output.script_pubkey.to_bytes(),
+            script_type: ScriptType::of(&output.script_pubkey).to_string(),
+            address: bitcoin::Address::from_script(&output.script_pubkey, network)
+                .ok()
+                .map(|address| address.to_string()),
+            spent_by_txid: None,
+            spent_height: None,
+        });
+        if let Some(op_return) = OpReturn::from_script(&output.script_pubkey) {
+            batch.op_returns.push(crate::db::OpReturn {
+                height: height.try_into()?,
+                txid: txid.clone(),
+                vout: vout.try_into()?,
+                value: output.value.try_into()?,
+                payload: op_return.payload,
+                data: op_return.data,
+                protocol: op_return.protocol.map(String::from),
+            });
+        }
+    }
+    for (vin, input) in tx.input.iter().enumerate() {
+        batch.inputs.push(crate::db::Input {
+            height: height.try_into()?,
+            txid: txid.clone(),
+            vin: vin.try_into()?,
+            prev_txid: input.previous_output.txid.to_string(),
+            prev_vout: input.previous_output.vout.into(),
+            sequence: input.sequence.to_consensus_u32().into(),
+            script_sig: input.script_sig.to_bytes(),
+            witness: (!input.witness.is_empty())
+                .then(|| bitcoin::consensus::serialize(&input.witness)),
+        });
+        if !tx.is_coin_base() {
+            batch.spends.push(crate::db::Spend {
+                prev_txid: input.previous_output.txid.to_string(),
+                prev_vout: input.previous_output.vout.try_into()?,
+                txid: txid.clone(),
+                height: height.try_into()?,
+            });
+        }
+    }
+    Ok(())
+}
+
+impl Drop for DbWriter {
+    /// Creates the indexes again if parsing failed before completion
+    fn drop(&mut self) {
+        if self.indexes_dropped {
+            tracing::info!(target: "parser", "Creating database indexes after a failed run ...");
+            if let Err(e) = self.db.create_indexes() {
+                tracing::error!(target: "parser", "Unable to create database indexes: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::hashes::Hash;
+use serde_json::json;
+
+use crate::export::json::JsonLines;
+use crate::export::rpc::{asm, script_type};
+use crate::export::{to_hex, ExportOptions};
+use crate::parser::script::ScriptType;
+use crate::parser::undo::SpentOutput;
+use crate::parser::visitor::{BlockData, BlockVisitor};
+
+/// Writes `blocks.json` and `transactions.json` with the tables and field names of the public
+/// `crypto_bitcoin` BigQuery dataset, as produced by bitcoin-etl. Inputs and outputs are nested
+/// in their transaction, amounts are in satoshis and addresses are arrays.
+pub struct CryptoBitcoinExporter {
+    options: ExportOptions,
+    network: bitcoin::Network,
+    blocks: JsonLines,
+    transactions: JsonLines,
+}
+
+impl CryptoBitcoinExporter {
+    #[must_use]
+    pub fn new(options: ExportOptions, network: bitcoin::Network) -> Self {
+        Self {
+            options,
+            network,
+            blocks: JsonLines::new("blocks", "json"),
+            transactions: JsonLines::new("transactions", "json"),
+        }
+    }
+}
+
+impl BlockVisitor for CryptoBitcoinExporter {
+    fn on_resume(&mut self, checkpoint: u64) -> anyhow::Result<u64> {
+        Ok(self.options.range_start(checkpoint + 1))
+    }
+
+    fn on_start(&mut self, _height: u64) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.options.dir)?;
+        Ok(())
+    }
+
+    fn on_block(&mut self, data: &BlockData) -> anyhow::Result<()> {
+        let block = data.block;
+        let hash = block.block_hash().to_string();
+        let time = block.header.time;
+        let coinbase_param = block
+            .txdata
+            .first()
+            .and_then(|tx| tx.input.first())
+            .map(|input| to_hex(input.script_sig.as_bytes()));
+        self.blocks.write(
+            &self.options,
+            data.height,
+            &json!({
+                "hash": hash,
+                "size": block.size(),
+                "stripped_size": block.strippedsize(),
+                "weight": block.weight().to_wu(),
+                "number": data.height,
+                "version": block.header.version.to_consensus(),
+                "merkle_root": block.header.merkle_root.to_string(),
+                "timestamp": timestamp(time),
+                "timestamp_month": month(time),
+                "nonce": format!("{:08x}", block.header.nonce),
+                "bits": format!("{:08x}", block.header.bits.to_consensus()),
+                "coinbase_param": coinbase_param,
+                "transaction_count": block.txdata.len(),
+            }),
+        )?;
+
+        for (position, tx) in block.txdata.iter().enumerate() {
+            let is_coinbase = tx.is_coin_base();
+            let prevouts = data
+                .undo
+                .and_then(|undo| undo.prevouts(position))
+                .filter(|prevouts| prevouts.len() == tx.input.len());
+            let inputs: Vec<_> = if is_coinbase {
+                Vec::new()
+            } else {
+                tx.input
+                    .iter()
+                    .enumerate()
+                    .map(|(index, input)| {
+                        let prevout = prevouts.and_then(|prevouts| prevouts.get(index));
+                        input_json(index, input, prevout, self.network)
+                    })
+                    .collect()
+            };
+            let outputs: Vec<_> = tx
+                .output
+                .iter()
+                .enumerate()
+                .map(|(index, output)| output_json(index, output, self.network))
+                .collect();
+
+            let input_value = if is_coinbase {
+                Some(0)
+            } else {
+                prevouts.map(|prevouts| prevouts.iter().map(|p| p.txout.value).sum::<u64>())
+            };
+            let output_value: u64 = tx.output.iter().map(|output| output.value).sum();
+            let fee = if is_coinbase {
+                Some(0)
+            } else {
+                input_value.and_then(|value| value.checked_sub(output_value))
+            };
+            self.transactions.write(
+                &self.options,
+                data.height,
+                &json!({
+                    "hash": tx.txid().to_string(),
+                    "size": tx.size(),
+                    "virtual_size": tx.vsize(),
+                    "version": tx.version,
+                    "lock_time": tx.lock_time.to_consensus_u32(),
+                    "block_hash": hash,
+                    "block_number": data.height,
+                    "block_timestamp": timestamp(time),
+                    "block_timestamp_month": month(time),
+                    "input_count": inputs.len(),
+                    "output_count": outputs.len(),
+                    "input_value": input_value,
+                    "output_value": output_value,
+                    "is_coinbase": is_coinbase,
+                    "fee": fee,
+                    "inputs": inputs,
+                    "outputs": outputs,
+                }),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn on_complete(&mut self, _height: u64) -> anyhow::Result<()> {
+        self.blocks.finish()?;
+        self.transactions.finish()
+    }
+}
+
+/// Nested input of a transaction, with the fields of its prevout if the undo data has it
+fn input_json(
+    index: usize,
+    input: &bitcoin::TxIn,
+    prevout: Option<&SpentOutput>,
+    network: bitcoin::Network,
+) -> serde_json::Value {
+    let script_pubkey = prevout.map(|p| p.txout.script_pubkey.as_script());
+    json!({
+        "index": index,
+        "spent_transaction_hash": input.previous_output.txid.to_string(),
+        "spent_output_index": input.previous_output.vout,
+        "script_asm": asm(&input.script_sig, true),
+        "script_hex": to_hex(input.script_sig.as_bytes()),
+        "sequence": input.sequence.to_consensus_u32(),
+        "required_signatures": script_pubkey.and_then(required_signatures),
+        "type": script_pubkey.map(script_type),
+        "addresses": script_pubkey
+            .map(|script| addresses(script, network))
+            .unwrap_or_default(),
+        "value": prevout.map(|p| p.txout.value),
+    })
+}
+
+/// Nested output of a transaction
+fn output_json(
+    index: usize,
+    output: &bitcoin::TxOut,
+    network: bitcoin::Network,
+) -> serde_json::Value {
+    let script = &output.script_pubkey;
+    json!({
+        "index": index,
+        "script_asm": asm(script, false),
+        "script_hex": to_hex(script.as_bytes()),
+        "required_signatures": required_signatures(script),
+        "type": script_type(script),
+        "addresses": addresses(script, network),
+        "value": output.value,
+    })
+}
+
+/// Addresses of an output like `getrawtransaction` of Bitcoin Core before 22.0 returned them:
+/// the P2PKH addresses of the keys for P2PK and bare multisig. Scripts without an address get
+/// `nonstandard` followed by the first 20 bytes of their SHA256 hash, like in bitcoin-etl.
+fn addresses(script: &bitcoin::Script, network: bitcoin::Network) -> Vec<String> {
+    let addresses: Vec<_> = match ScriptType::of(script) {
+        ScriptType::P2pk => script
+            .p2pk_public_key()
+            .map(|key| bitcoin::Address::p2pkh(&key, network).to_string())
+            .into_iter()
+            .collect(),
+        ScriptType::Multisig => multisig_keys(script)
+            .iter()
+            .map(|key| bitcoin::Address::p2pkh(key, network).to_string())
+            .collect(),
+        _ => bitcoin::Address::from_script(script, network)
+            .map(|address| address.to_string())
+            .into_iter()
+            .collect(),
+    };
+    if addresses.is_empty() {
+        let hash = bitcoin::hashes::sha256::Hash::hash(script.as_bytes());
+        vec![format!(
+            "nonstandard{}",
+            &to_hex(hash.as_byte_array())[..40]
+        )]
+    } else {
+        addresses
+    }
+}
+
+/// `reqSigs` of Bitcoin Core before 22.0, `None` for scripts without an address
+fn required_signatures(script: &bitcoin::Script) -> Option<u8> {
+    match ScriptType::of(script) {
+        ScriptType::P2pk => script.p2pk_public_key().map(|_| 1),
+        ScriptType::Multisig => {
+            let first = script.as_bytes()[0];
+            (!multisig_keys(script).is_empty()).then_some(first - 0x50)
+        }
+        ScriptType::P2pkh
+        | ScriptType::P2sh
+        | ScriptType::P2wpkh
+        | ScriptType::P2wsh
+        | ScriptType::P2tr
+        | ScriptType::WitnessUnknown(_) => Some(1),
+        ScriptType::OpReturn | ScriptType::NonStandard => None,
+    }
+}
+
+/// Valid public keys of a bare multisig script
+fn multisig_keys(script: &bitcoin::Script) -> Vec<bitcoin::PublicKey> {
+    script
+        .instructions()
+        .filter_map(|instruction| match instruction {
+            Ok(Instruction::PushBytes(key)) => bitcoin::PublicKey::from_slice(key.as_bytes()).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// UTC time in the format accepted by BigQuery, e.g. `2009-01-03 18:15:05 UTC`
+fn timestamp(time: u32) -> String {
+    let (year, month, day) = civil_date(time);
+    let seconds = time % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// First day of the month, e.g. `2009-01-01`
+fn month(time: u32) -> String {
+    let (year, month, _) = civil_date(time);
+    format!("{year:04}-{month:02}-01")
+}
+
+/// Year, month and day of a unix timestamp in the proleptic Gregorian calendar
+fn civil_date(time: u32) -> (u32, u32, u32) {
+    // Shifted to start on 0000-03-01, so that leap days are at the end of a year
+    let days = time / 86_400 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u32::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(1_231_006_505), "2009-01-03 18:15:05 UTC");
+        assert_eq!(month(1_231_006_505), "2009-01-01");
+        assert_eq!(timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(timestamp(951_825_600), "2000-02-29 12:00:00 UTC");
+        assert_eq!(timestamp(u32::MAX), "2106-02-07 06:28:15 UTC");
+    }
+
+    #[test]
+    fn test_addresses() {
+        let genesis = bitcoin::blockdata::constants::genesis_block(bitcoin::Network::Bitcoin);
+        let script = &genesis.txdata[0].output[0].script_pubkey;
+        assert_eq!(
+            addresses(script, bitcoin::Network::Bitcoin),
+            ["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"]
+        );
+        assert_eq!(required_signatures(script), Some(1));
+
+        let op_return = bitcoin::ScriptBuf::from_hex("6a04deadbeef").unwrap();
+        let addresses = addresses(&op_return, bitcoin::Network::Bitcoin);
+        assert_eq!(addresses.len(), 1);
+        assert!(addresses[0].starts_with("nonstandard"));
+        assert_eq!(addresses[0].len(), 51);
+        assert_eq!(required_signatures(&op_return), None);
+    }
+}
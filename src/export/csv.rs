@@ -0,0 +1,232 @@
+use crate::export::{to_hex, ExportOptions, Rows, Sink};
+use crate::parser::visitor::{BlockData, BlockVisitor};
+
+const BLOCK_COLUMNS: &[&str] = &[
+    "height",
+    "hash",
+    "prev_hash",
+    "merkle_root",
+    "version",
+    "time",
+    "bits",
+    "nonce",
+    "tx_count",
+    "size",
+    "stripped_size",
+    "weight",
+    "difficulty",
+    "chainwork",
+];
+
+const TRANSACTION_COLUMNS: &[&str] = &[
+    "height",
+    "position",
+    "txid",
+    "wtxid",
+    "version",
+    "locktime",
+    "size",
+    "vsize",
+    "weight",
+    "input_count",
+    "output_count",
+    "input_value",
+    "output_value",
+    "fee",
+    "is_coinbase",
+];
+
+const INPUT_COLUMNS: &[&str] = &[
+    "height",
+    "txid",
+    "vin",
+    "prev_txid",
+    "prev_vout",
+    "sequence",
+    "script_sig",
+    "witness",
+    "value",
+    "address",
+];
+
+const OUTPUT_COLUMNS: &[&str] = &[
+    "height",
+    "txid",
+    "vout",
+    "value",
+    "script_pubkey",
+    "script_type",
+    "address",
+];
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// CSV file of one table, switching to the next file when a block of another
+/// height range is written
+struct TableFile {
+    table: &'static str,
+    columns: &'static [&'static str],
+    current: Option<(std::path::PathBuf, csv::Writer<Sink>)>,
+}
+
+impl TableFile {
+    fn new(table: &'static str, columns: &'static [&'static str]) -> Self {
+        Self {
+            table,
+            columns,
+            current: None,
+        }
+    }
+
+    fn writer(
+        &mut self,
+        options: &ExportOptions,
+        height: u64,
+    ) -> anyhow::Result<&mut csv::Writer<Sink>> {
+        let mut path = options.file_stem(self.table, height).into_os_string();
+        path.push(".csv");
+        if options.gzip {
+            path.push(".gz");
+        }
+        let path = std::path::PathBuf::from(path);
+        if self.current.as_ref().map(|(p, _)| p) != Some(&path) {
+            self.finish()?;
+            tracing::debug!(target: "export", "Writing {}", path.display());
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(Sink::create(&path, options.gzip)?);
+            writer.write_record(self.columns)?;
+            self.current = Some((path, writer));
+        }
+        Ok(&mut self.current.as_mut().expect("file was just opened").1)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        if let Some((_, writer)) = self.current.take() {
+            writer
+                .into_inner()
+                .map_err(csv::IntoInnerError::into_error)?
+                .finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes blocks, transactions, inputs and outputs to one CSV file per table and height range.
+/// Hashes are hex encoded like in Bitcoin Core, scripts and witnesses as raw hex.
+pub struct CsvExporter {
+    options: ExportOptions,
+    network: bitcoin::Network,
+    blocks: TableFile,
+    transactions: TableFile,
+    inputs: TableFile,
+    outputs: TableFile,
+}
+
+impl CsvExporter {
+    #[must_use]
+    pub fn new(options: ExportOptions, network: bitcoin::Network) -> Self {
+        Self {
+            options,
+            network,
+            blocks: TableFile::new("blocks", BLOCK_COLUMNS),
+            transactions: TableFile::new("transactions", TRANSACTION_COLUMNS),
+            inputs: TableFile::new("inputs", INPUT_COLUMNS),
+            outputs: TableFile::new("outputs", OUTPUT_COLUMNS),
+        }
+    }
+}
+
+impl BlockVisitor for CsvExporter {
+    fn on_resume(&mut self, checkpoint: u64) -> anyhow::Result<u64> {
+        Ok(self.options.range_start(checkpoint + 1))
+    }
+
+    fn on_start(&mut self, _height: u64) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.options.dir)?;
+        Ok(())
+    }
+
+    fn on_block(&mut self, data: &BlockData) -> anyhow::Result<()> {
+        let rows = Rows::new(data, self.network)?;
+        let height = data.height;
+
+        let block = rows.block;
+        self.blocks.writer(&self.options, height)?.write_record([
+            block.height.to_string(),
+            block.hash.to_string(),
+            block.prev_hash.to_string(),
+            block.merkle_root.to_string(),
+            block.version.to_string(),
+            block.time.to_string(),
+            format!("{:08x}", block.bits),
+            block.nonce.to_string(),
+            block.tx_count.to_string(),
+            block.size.to_string(),
+            block.stripped_size.to_string(),
+            block.weight.to_string(),
+            block.difficulty.to_string(),
+            crate::parser::pow::work_to_hex(block.chainwork),
+        ])?;
+
+        let writer = self.transactions.writer(&self.options, height)?;
+        for tx in rows.transactions {
+            writer.write_record([
+                tx.height.to_string(),
+                tx.position.to_string(),
+                tx.txid.to_string(),
+                tx.wtxid.to_string(),
+                tx.version.to_string(),
+                tx.locktime.to_string(),
+                tx.size.to_string(),
+                tx.vsize.to_string(),
+                tx.weight.to_string(),
+                tx.input_count.to_string(),
+                tx.output_count.to_string(),
+                optional(tx.input_value),
+                tx.output_value.to_string(),
+                optional(tx.fee),
+                tx.is_coinbase.to_string(),
+            ])?;
+        }
+
+        let writer = self.inputs.writer(&self.options, height)?;
+        for input in rows.inputs {
+            writer.write_record([
+                input.height.to_string(),
+                input.txid.to_string(),
+                input.vin.to_string(),
+                input.prev_txid.to_string(),
+                input.prev_vout.to_string(),
+                input.sequence.to_string(),
+                to_hex(&input.script_sig),
+                optional(input.witness.as_deref().map(to_hex)),
+                optional(input.value),
+                input.address.unwrap_or_default(),
+            ])?;
+        }
+
+        let writer = self.outputs.writer(&self.options, height)?;
+        for output in rows.outputs {
+            writer.write_record([
+                output.height.to_string(),
+                output.txid.to_string(),
+                output.vout.to_string(),
+                output.value.to_string(),
+                to_hex(&output.script_pubkey),
+                output.script_type.to_string(),
+                output.address.unwrap_or_default(),
+            ])?;
+        }
+        Ok(())
+    }
+
+    fn on_complete(&mut self, _height: u64) -> anyhow::Result<()> {
+        self.blocks.finish()?;
+        self.transactions.finish()?;
+        self.inputs.finish()?;
+        self.outputs.finish()
+    }
+}
@@ -0,0 +1,249 @@
+use std::io::Write;
+
+use serde_json::{json, Value};
+
+use crate::export::rpc::{amount, asm, descriptor, float, script_type};
+use crate::export::{to_hex, ExportOptions, Sink};
+use crate::parser::undo::SpentOutput;
+use crate::parser::visitor::{BlockData, BlockVisitor};
+
+/// Newline-delimited JSON file of one table, switching to the next file when a block of
+/// another height range is written
+pub(crate) struct JsonLines {
+    table: &'static str,
+    extension: &'static str,
+    current: Option<(std::path::PathBuf, Sink)>,
+}
+
+impl JsonLines {
+    pub(crate) fn new(table: &'static str, extension: &'static str) -> Self {
+        Self {
+            table,
+            extension,
+            current: None,
+        }
+    }
+
+    pub(crate) fn write(
+        &mut self,
+        options: &ExportOptions,
+        height: u64,
+        value: &Value,
+    ) -> anyhow::Result<()> {
+        let mut path = options.file_stem(self.table, height).into_os_string();
+        path.push(".");
+        path.push(self.extension);
+        if options.gzip {
+            path.push(".gz");
+        }
+        let path = std::path::PathBuf::from(path);
+        if self.current.as_ref().map(|(p, _)| p) != Some(&path) {
+            self.finish()?;
+            tracing::debug!(target: "export", "Writing {}", path.display());
+            let sink = Sink::create(&path, options.gzip)?;
+            self.current = Some((path, sink));
+        }
+        let sink = &mut self.current.as_mut().expect("file was just opened").1;
+        serde_json::to_writer(&mut *sink, value)?;
+        sink.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub(crate) fn finish(&mut self) -> anyhow::Result<()> {
+        if let Some((_, sink)) = self.current.take() {
+            sink.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes one JSON object per line and block in the format of `getblock <hash> 3`,
+/// which is `getblock <hash> 2` with the spent outputs as `prevout` of every input.
+pub struct NdjsonExporter {
+    options: ExportOptions,
+    network: bitcoin::Network,
+    blocks: JsonLines,
+}
+
+impl NdjsonExporter {
+    #[must_use]
+    pub fn new(options: ExportOptions, network: bitcoin::Network) -> Self {
+        Self {
+            options,
+            network,
+            blocks: JsonLines::new("blocks", "ndjson"),
+        }
+    }
+}
+
+impl BlockVisitor for NdjsonExporter {
+    fn on_resume(&mut self, checkpoint: u64) -> anyhow::Result<u64> {
+        Ok(self.options.range_start(checkpoint + 1))
+    }
+
+    fn on_start(&mut self, _height: u64) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.options.dir)?;
+        Ok(())
+    }
+
+    fn on_block(&mut self, data: &BlockData) -> anyhow::Result<()> {
+        let block = block_to_json(data, self.network)?;
+        self.blocks.write(&self.options, data.height, &block)
+    }
+
+    fn on_complete(&mut self, _height: u64) -> anyhow::Result<()> {
+        self.blocks.finish()
+    }
+}
+
+/// Block in the format of `getblock <hash> 3`
+pub fn block_to_json(data: &BlockData, network: bitcoin::Network) -> anyhow::Result<Value> {
+    let block = data.block;
+    let header = &block.header;
+    let mut json = json!({
+        "hash": block.block_hash().to_string(),
+        "confirmations": data.tip_height.saturating_sub(data.height) + 1,
+        "height": data.height,
+        "version": header.version.to_consensus(),
+        "versionHex": format!("{:08x}", header.version.to_consensus()),
+        "merkleroot": header.merkle_root.to_string(),
+        "time": header.time,
+        "mediantime": data.median_time,
+        "nonce": header.nonce,
+        "bits": format!("{:08x}", header.bits.to_consensus()),
+        "difficulty": float(crate::parser::pow::difficulty(header.bits.to_consensus())),
+        "chainwork": crate::parser::pow::work_to_hex(data.chainwork),
+        "nTx": block.txdata.len(),
+    });
+    let object = json.as_object_mut().expect("block is an object");
+    if data.height > 0 {
+        object.insert(
+            "previousblockhash".to_string(),
+            header.prev_blockhash.to_string().into(),
+        );
+    }
+    if let Some(next_hash) = data.next_hash {
+        object.insert("nextblockhash".to_string(), next_hash.to_string().into());
+    }
+    object.insert("strippedsize".to_string(), block.strippedsize().into());
+    object.insert("size".to_string(), block.size().into());
+    object.insert("weight".to_string(), block.weight().to_wu().into());
+
+    let mut txs = Vec::with_capacity(block.txdata.len());
+    for (position, tx) in block.txdata.iter().enumerate() {
+        let prevouts = data
+            .undo
+            .and_then(|undo| undo.prevouts(position))
+            .filter(|prevouts| prevouts.len() == tx.input.len());
+        txs.push(tx_to_json(tx, prevouts, network)?);
+    }
+    object.insert("tx".to_string(), txs.into());
+    Ok(json)
+}
+
+/// Transaction in the format of `TxToUniv` in Bitcoin Core, `prevouts` is `None` for the coinbase
+fn tx_to_json(
+    tx: &bitcoin::Transaction,
+    prevouts: Option<&[SpentOutput]>,
+    network: bitcoin::Network,
+) -> anyhow::Result<Value> {
+    let mut vin = Vec::with_capacity(tx.input.len());
+    for (index, input) in tx.input.iter().enumerate() {
+        let mut json = serde_json::Map::new();
+        if tx.is_coin_base() {
+            json.insert(
+                "coinbase".to_string(),
+                to_hex(input.script_sig.as_bytes()).into(),
+            );
+        } else {
+            json.insert(
+                "txid".to_string(),
+                input.previous_output.txid.to_string().into(),
+            );
+            json.insert("vout".to_string(), input.previous_output.vout.into());
+            json.insert(
+                "scriptSig".to_string(),
+                json!({
+                    "asm": asm(&input.script_sig, true),
+                    "hex": to_hex(input.script_sig.as_bytes()),
+                }),
+            );
+        }
+        if !input.witness.is_empty() {
+            let items: Vec<_> = input.witness.iter().map(to_hex).collect();
+            json.insert("txinwitness".to_string(), items.into());
+        }
+        if let Some(prevout) = prevouts.and_then(|prevouts| prevouts.get(index)) {
+            json.insert(
+                "prevout".to_string(),
+                json!({
+                    "generated": prevout.is_coinbase,
+                    "height": prevout.height,
+                    "value": amount(prevout.txout.value),
+                    "scriptPubKey": script_pubkey_to_json(&prevout.txout.script_pubkey, network),
+                }),
+            );
+        }
+        json.insert(
+            "sequence".to_string(),
+            input.sequence.to_consensus_u32().into(),
+        );
+        vin.push(Value::Object(json));
+    }
+
+    let vout: Vec<_> = tx
+        .output
+        .iter()
+        .enumerate()
+        .map(|(n, output)| {
+            json!({
+                "value": amount(output.value),
+                "n": n,
+                "scriptPubKey": script_pubkey_to_json(&output.script_pubkey, network),
+            })
+        })
+        .collect();
+
+    let mut json = json!({
+        "txid": tx.txid().to_string(),
+        "hash": tx.wtxid().to_string(),
+        "version": tx.version,
+        "size": tx.size(),
+        "vsize": tx.vsize(),
+        "weight": tx.weight().to_wu(),
+        "locktime": tx.lock_time.to_consensus_u32(),
+        "vin": vin,
+        "vout": vout,
+    });
+    let object = json.as_object_mut().expect("transaction is an object");
+    if let Some(prevouts) = prevouts {
+        let input_value: u64 = prevouts.iter().map(|p| p.txout.value).sum();
+        let output_value: u64 = tx.output.iter().map(|output| output.value).sum();
+        let fee = input_value.checked_sub(output_value).ok_or_else(|| {
+            anyhow::anyhow!("transaction {} spends more than its inputs", tx.txid())
+        })?;
+        object.insert("fee".to_string(), amount(fee));
+    }
+    object.insert(
+        "hex".to_string(),
+        to_hex(&bitcoin::consensus::serialize(tx)).into(),
+    );
+    Ok(json)
+}
+
+/// Output script in the format of `ScriptToUniv` in Bitcoin Core
+fn script_pubkey_to_json(script: &bitcoin::Script, network: bitcoin::Network) -> Value {
+    let mut json = json!({
+        "asm": asm(script, false),
+        "desc": descriptor(script, network),
+        "hex": to_hex(script.as_bytes()),
+    });
+    let object = json.as_object_mut().expect("script is an object");
+    if !script.is_p2pk() {
+        if let Ok(address) = bitcoin::Address::from_script(script, network) {
+            object.insert("address".to_string(), address.to_string().into());
+        }
+    }
+    object.insert("type".to_string(), script_type(script).into());
+    json
+}
@@ -0,0 +1,357 @@
+use std::io::Write;
+
+use crate::parser::script::ScriptType;
+use crate::parser::visitor::{BlockData, BlockVisitor};
+
+pub mod crypto_bitcoin;
+pub mod csv;
+pub mod json;
+pub mod parquet;
+pub mod rpc;
+
+/// File format written by `--export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Parquet,
+    /// One `getblock` JSON object per line
+    Ndjson,
+    /// Tables and fields of the public `crypto_bitcoin` BigQuery dataset as JSON lines
+    CryptoBitcoin,
+}
+
+impl ExportFormat {
+    pub const ALL: [&'static str; 4] = ["csv", "parquet", "ndjson", "crypto-bitcoin"];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Parquet => "parquet",
+            Self::Ndjson => "ndjson",
+            Self::CryptoBitcoin => "crypto-bitcoin",
+        }
+    }
+
+    /// Whether the files can be compressed with `--export-gzip`
+    #[must_use]
+    pub fn is_text(self) -> bool {
+        match self {
+            Self::Csv | Self::Ndjson | Self::CryptoBitcoin => true,
+            Self::Parquet => false,
+        }
+    }
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "parquet" => Ok(Self::Parquet),
+            "ndjson" => Ok(Self::Ndjson),
+            "crypto-bitcoin" => Ok(Self::CryptoBitcoin),
+            _ => anyhow::bail!("unknown export format '{s}'"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Directory the files are written to, created if it doesn't exist
+    pub dir: std::path::PathBuf,
+    /// Number of blocks per file, `None` writes a single file per table
+    pub rotate: Option<u64>,
+    pub gzip: bool,
+    /// Maximum number of rows per row group of Parquet files
+    pub row_group_size: usize,
+}
+
+impl ExportOptions {
+    /// First height of the file containing `height`
+    #[must_use]
+    pub fn range_start(&self, height: u64) -> u64 {
+        self.rotate.map_or(0, |blocks| height - height % blocks)
+    }
+
+    /// Path of the file for `table` containing `height` without the extension
+    #[must_use]
+    pub fn file_stem(&self, table: &str, height: u64) -> std::path::PathBuf {
+        match self.rotate {
+            Some(blocks) => {
+                let start = self.range_start(height);
+                self.dir
+                    .join(format!("{table}_{start:09}-{:09}", start + blocks - 1))
+            }
+            None => self.dir.join(table),
+        }
+    }
+}
+
+/// Creates the visitor writing the blocks in the configured format.
+#[must_use]
+pub fn visitor(options: &ExportOptions, network: bitcoin::Network) -> Box<dyn BlockVisitor> {
+    match options.format {
+        ExportFormat::Csv => Box::new(csv::CsvExporter::new(options.clone(), network)),
+        ExportFormat::Parquet => Box::new(parquet::ParquetExporter::new(options.clone(), network)),
+        ExportFormat::Ndjson => Box::new(json::NdjsonExporter::new(options.clone(), network)),
+        ExportFormat::CryptoBitcoin => Box::new(crypto_bitcoin::CryptoBitcoinExporter::new(
+            options.clone(),
+            network,
+        )),
+    }
+}
+
+/// Output file, optionally gzip compressed
+pub enum Sink {
+    Plain(std::io::BufWriter<std::fs::File>),
+    Gzip(flate2::write::GzEncoder<std::io::BufWriter<std::fs::File>>),
+}
+
+impl Sink {
+    /// Creates `path` or truncates it if it exists. Files are only ever written as a whole, a
+    /// resumed run writes the file of the height range it continues in again.
+    pub fn create(path: &std::path::Path, gzip: bool) -> std::io::Result<Self> {
+        let writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        Ok(if gzip {
+            Self::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            ))
+        } else {
+            Self::Plain(writer)
+        })
+    }
+
+    /// Writes all buffered data, including the gzip trailer.
+    pub fn finish(self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(mut writer) => writer.flush(),
+            Self::Gzip(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(writer) => writer.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(writer) => writer.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockRow {
+    pub height: u64,
+    pub hash: bitcoin::BlockHash,
+    pub prev_hash: bitcoin::BlockHash,
+    pub merkle_root: bitcoin::hash_types::TxMerkleNode,
+    pub version: i32,
+    pub time: u32,
+    pub bits: u32,
+    pub nonce: u32,
+    pub tx_count: u64,
+    pub size: u64,
+    pub stripped_size: u64,
+    pub weight: u64,
+    pub difficulty: f64,
+    pub chainwork: bitcoin::Work,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionRow {
+    pub height: u64,
+    pub position: u64,
+    pub txid: bitcoin::Txid,
+    pub wtxid: bitcoin::Wtxid,
+    pub version: i32,
+    pub locktime: u32,
+    pub size: u64,
+    pub vsize: u64,
+    pub weight: u64,
+    pub input_count: u64,
+    pub output_count: u64,
+    /// `None` for the coinbase and if the undo data isn't available
+    pub input_value: Option<u64>,
+    pub output_value: u64,
+    pub fee: Option<u64>,
+    pub is_coinbase: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputRow {
+    pub height: u64,
+    pub txid: bitcoin::Txid,
+    pub vin: u64,
+    pub prev_txid: bitcoin::Txid,
+    pub prev_vout: u32,
+    pub sequence: u32,
+    pub script_sig: Vec<u8>,
+    /// Consensus encoded witness stack, `None` if empty
+    pub witness: Option<Vec<u8>>,
+    /// Value and address of the spent output from the undo data
+    pub value: Option<u64>,
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputRow {
+    pub height: u64,
+    pub txid: bitcoin::Txid,
+    pub vout: u32,
+    pub value: u64,
+    pub script_pubkey: Vec<u8>,
+    pub script_type: ScriptType,
+    pub address: Option<String>,
+}
+
+/// Rows of all exported tables for a single block
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rows {
+    pub block: BlockRow,
+    pub transactions: Vec<TransactionRow>,
+    pub inputs: Vec<InputRow>,
+    pub outputs: Vec<OutputRow>,
+}
+
+impl Rows {
+    pub fn new(data: &BlockData, network: bitcoin::Network) -> anyhow::Result<Self> {
+        let BlockData {
+            height,
+            block,
+            undo,
+            ..
+        } = *data;
+        let address = |script: &bitcoin::Script| {
+            bitcoin::Address::from_script(script, network)
+                .ok()
+                .map(|address| address.to_string())
+        };
+        let mut rows = Self {
+            block: BlockRow {
+                height,
+                hash: block.block_hash(),
+                prev_hash: block.header.prev_blockhash,
+                merkle_root: block.header.merkle_root,
+                version: block.header.version.to_consensus(),
+                time: block.header.time,
+                bits: block.header.bits.to_consensus(),
+                nonce: block.header.nonce,
+                tx_count: block.txdata.len().try_into()?,
+                size: block.size().try_into()?,
+                stripped_size: block.strippedsize().try_into()?,
+                weight: block.weight().to_wu(),
+                difficulty: crate::parser::pow::difficulty(block.header.bits.to_consensus()),
+                chainwork: data.chainwork,
+            },
+            transactions: Vec::with_capacity(block.txdata.len()),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        };
+
+        for (position, tx) in block.txdata.iter().enumerate() {
+            let txid = tx.txid();
+            let prevouts = undo
+                .and_then(|undo| undo.prevouts(position))
+                .filter(|prevouts| prevouts.len() == tx.input.len());
+            let input_value = prevouts.map(|prevouts| prevouts.iter().map(|p| p.txout.value).sum());
+            let output_value: u64 = tx.output.iter().map(|output| output.value).sum();
+            rows.transactions.push(TransactionRow {
+                height,
+                position: position.try_into()?,
+                txid,
+                wtxid: tx.wtxid(),
+                version: tx.version,
+                locktime: tx.lock_time.to_consensus_u32(),
+                size: tx.size().try_into()?,
+                vsize: tx.vsize().try_into()?,
+                weight: tx.weight().to_wu(),
+                input_count: tx.input.len().try_into()?,
+                output_count: tx.output.len().try_into()?,
+                input_value,
+                output_value,
+                fee: input_value.and_then(|value: u64| value.checked_sub(output_value)),
+                is_coinbase: tx.is_coin_base(),
+            });
+            for (vin, input) in tx.input.iter().enumerate() {
+                let prevout = prevouts.and_then(|prevouts| prevouts.get(vin));
+                rows.inputs.push(InputRow {
+                    height,
+                    txid,
+                    vin: vin.try_into()?,
+                    prev_txid: input.previous_output.txid,
+                    prev_vout: input.previous_output.vout,
+                    sequence: input.sequence.to_consensus_u32(),
+                    script_sig: input.script_sig.to_bytes(),
+                    witness: (!input.witness.is_empty())
+                        .then(|| bitcoin::consensus::serialize(&input.witness)),
+                    value: prevout.map(|p| p.txout.value),
+                    address: prevout.and_then(|p| address(&p.txout.script_pubkey)),
+                });
+            }
+            for (vout, output) in tx.output.iter().enumerate() {
+                rows.outputs.push(OutputRow {
+                    height,
+                    txid,
+                    vout: vout.try_into()?,
+                    value: output.value,
+                    script_pubkey: output.script_pubkey.to_bytes(),
+                    script_type: ScriptType::of(&output.script_pubkey),
+                    address: address(&output.script_pubkey),
+                });
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// Lowercase hex encoding
+#[must_use]
+pub fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        // Writing to a String cannot fail
+        let _ = write!(hex, "{b:02x}");
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_stem() {
+        let mut options = ExportOptions {
+            format: ExportFormat::Csv,
+            dir: std::path::PathBuf::from("out"),
+            rotate: None,
+            gzip: false,
+            row_group_size: 1024,
+        };
+        assert_eq!(
+            options.file_stem("blocks", 12_345),
+            std::path::Path::new("out").join("blocks")
+        );
+        options.rotate = Some(10_000);
+        assert_eq!(
+            options.file_stem("blocks", 12_345),
+            std::path::Path::new("out").join("blocks_000010000-000019999")
+        );
+        assert_eq!(
+            options.file_stem("blocks", 9_999),
+            std::path::Path::new("out").join("blocks_000000000-000009999")
+        );
+    }
+}
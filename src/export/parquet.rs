@@ -0,0 +1,333 @@
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, BinaryArray, BooleanArray, FixedSizeBinaryArray, Float64Array, Int32Array,
+    RecordBatch, StringArray, TimestampSecondArray, UInt32Array, UInt64Array,
+};
+use bitcoin::hashes::Hash;
+
+use crate::export::{BlockRow, ExportOptions, InputRow, OutputRow, Rows, TransactionRow};
+use crate::parser::visitor::{BlockData, BlockVisitor};
+
+/// Hash in the byte order displayed by Bitcoin Core, so that its hex encoding matches the RPC
+fn display_order<H: Hash<Bytes = [u8; 32]>>(hash: H) -> [u8; 32] {
+    let mut bytes = hash.to_byte_array();
+    bytes.reverse();
+    bytes
+}
+
+fn fixed_binary(values: impl Iterator<Item = [u8; 32]>) -> anyhow::Result<ArrayRef> {
+    Ok(Arc::new(
+        FixedSizeBinaryArray::try_from_sparse_iter_with_size(values.map(Some), 32)?,
+    ))
+}
+
+fn hashes<H: Hash<Bytes = [u8; 32]>>(hashes: impl Iterator<Item = H>) -> anyhow::Result<ArrayRef> {
+    fixed_binary(hashes.map(display_order))
+}
+
+fn u64s(values: impl Iterator<Item = u64>) -> ArrayRef {
+    Arc::new(UInt64Array::from_iter_values(values))
+}
+
+fn nullable_u64s(values: impl Iterator<Item = Option<u64>>) -> ArrayRef {
+    Arc::new(values.collect::<UInt64Array>())
+}
+
+fn u32s(values: impl Iterator<Item = u32>) -> ArrayRef {
+    Arc::new(UInt32Array::from_iter_values(values))
+}
+
+fn i32s(values: impl Iterator<Item = i32>) -> ArrayRef {
+    Arc::new(Int32Array::from_iter_values(values))
+}
+
+fn binaries<'a>(values: impl Iterator<Item = &'a [u8]>) -> ArrayRef {
+    Arc::new(BinaryArray::from_iter_values(values))
+}
+
+fn nullable_binaries<'a>(values: impl Iterator<Item = Option<&'a [u8]>>) -> ArrayRef {
+    Arc::new(values.collect::<BinaryArray>())
+}
+
+fn strings<'a>(values: impl Iterator<Item = &'a str>) -> ArrayRef {
+    Arc::new(StringArray::from_iter_values(values))
+}
+
+fn nullable_strings<'a>(values: impl Iterator<Item = Option<&'a str>>) -> ArrayRef {
+    Arc::new(values.collect::<StringArray>())
+}
+
+fn blocks_batch(rows: &[BlockRow]) -> anyhow::Result<RecordBatch> {
+    Ok(RecordBatch::try_from_iter_with_nullable([
+        ("height", u64s(rows.iter().map(|r| r.height)), false),
+        ("hash", hashes(rows.iter().map(|r| r.hash))?, false),
+        (
+            "prev_hash",
+            hashes(rows.iter().map(|r| r.prev_hash))?,
+            false,
+        ),
+        (
+            "merkle_root",
+            hashes(rows.iter().map(|r| r.merkle_root))?,
+            false,
+        ),
+        ("version", i32s(rows.iter().map(|r| r.version)), false),
+        (
+            "time",
+            Arc::new(
+                TimestampSecondArray::from_iter_values(rows.iter().map(|r| i64::from(r.time)))
+                    .with_timezone("UTC"),
+            ) as ArrayRef,
+            false,
+        ),
+        ("bits", u32s(rows.iter().map(|r| r.bits)), false),
+        ("nonce", u32s(rows.iter().map(|r| r.nonce)), false),
+        ("tx_count", u64s(rows.iter().map(|r| r.tx_count)), false),
+        ("size", u64s(rows.iter().map(|r| r.size)), false),
+        (
+            "stripped_size",
+            u64s(rows.iter().map(|r| r.stripped_size)),
+            false,
+        ),
+        ("weight", u64s(rows.iter().map(|r| r.weight)), false),
+        (
+            "difficulty",
+            Arc::new(Float64Array::from_iter_values(
+                rows.iter().map(|r| r.difficulty),
+            )) as ArrayRef,
+            false,
+        ),
+        (
+            "chainwork",
+            fixed_binary(rows.iter().map(|r| r.chainwork.to_be_bytes()))?,
+            false,
+        ),
+    ])?)
+}
+
+fn transactions_batch(rows: &[TransactionRow]) -> anyhow::Result<RecordBatch> {
+    Ok(RecordBatch::try_from_iter_with_nullable([
+        ("height", u64s(rows.iter().map(|r| r.height)), false),
+        ("position", u64s(rows.iter().map(|r| r.position)), false),
+        ("txid", hashes(rows.iter().map(|r| r.txid))?, false),
+        ("wtxid", hashes(rows.iter().map(|r| r.wtxid))?, false),
+        ("version", i32s(rows.iter().map(|r| r.version)), false),
+        ("locktime", u32s(rows.iter().map(|r| r.locktime)), false),
+        ("size", u64s(rows.iter().map(|r| r.size)), false),
+        ("vsize", u64s(rows.iter().map(|r| r.vsize)), false),
+        ("weight", u64s(rows.iter().map(|r| r.weight)), false),
+        (
+            "input_count",
+            u64s(rows.iter().map(|r| r.input_count)),
+            false,
+        ),
+        (
+            "output_count",
+            u64s(rows.iter().map(|r| r.output_count)),
+            false,
+        ),
+        (
+            "input_value",
+            nullable_u64s(rows.iter().map(|r| r.input_value)),
+            true,
+        ),
+        (
+            "output_value",
+            u64s(rows.iter().map(|r| r.output_value)),
+            false,
+        ),
+        ("fee", nullable_u64s(rows.iter().map(|r| r.fee)), true),
+        (
+            "is_coinbase",
+            Arc::new(BooleanArray::from(
+                rows.iter().map(|r| r.is_coinbase).collect::<Vec<_>>(),
+            )) as ArrayRef,
+            false,
+        ),
+    ])?)
+}
+
+fn inputs_batch(rows: &[InputRow]) -> anyhow::Result<RecordBatch> {
+    Ok(RecordBatch::try_from_iter_with_nullable([
+        ("height", u64s(rows.iter().map(|r| r.height)), false),
+        ("txid", hashes(rows.iter().map(|r| r.txid))?, false),
+        ("vin", u64s(rows.iter().map(|r| r.vin)), false),
+        (
+            "prev_txid",
+            hashes(rows.iter().map(|r| r.prev_txid))?,
+            false,
+        ),
+        ("prev_vout", u32s(rows.iter().map(|r| r.prev_vout)), false),
+        ("sequence", u32s(rows.iter().map(|r| r.sequence)), false),
+        (
+            "script_sig",
+            binaries(rows.iter().map(|r| r.script_sig.as_slice())),
+            false,
+        ),
+        (
+            "witness",
+            nullable_binaries(rows.iter().map(|r| r.witness.as_deref())),
+            true,
+        ),
+        ("value", nullable_u64s(rows.iter().map(|r| r.value)), true),
+        (
+            "address",
+            nullable_strings(rows.iter().map(|r| r.address.as_deref())),
+            true,
+        ),
+    ])?)
+}
+
+fn outputs_batch(rows: &[OutputRow]) -> anyhow::Result<RecordBatch> {
+    Ok(RecordBatch::try_from_iter_with_nullable([
+        ("height", u64s(rows.iter().map(|r| r.height)), false),
+        ("txid", hashes(rows.iter().map(|r| r.txid))?, false),
+        ("vout", u32s(rows.iter().map(|r| r.vout)), false),
+        ("value", u64s(rows.iter().map(|r| r.value)), false),
+        (
+            "script_pubkey",
+            binaries(rows.iter().map(|r| r.script_pubkey.as_slice())),
+            false,
+        ),
+        (
+            "script_type",
+            strings(rows.iter().map(|r| r.script_type.as_str())),
+            false,
+        ),
+        (
+            "address",
+            nullable_strings(rows.iter().map(|r| r.address.as_deref())),
+            true,
+        ),
+    ])?)
+}
+
+/// Parquet file of one table. Rows are buffered until a row group is full
+/// or a block of another height range is written.
+struct TableFile<R> {
+    table: &'static str,
+    to_batch: fn(&[R]) -> anyhow::Result<RecordBatch>,
+    rows: Vec<R>,
+    stem: Option<std::path::PathBuf>,
+    writer: Option<parquet::arrow::ArrowWriter<std::fs::File>>,
+}
+
+impl<R> TableFile<R> {
+    fn new(table: &'static str, to_batch: fn(&[R]) -> anyhow::Result<RecordBatch>) -> Self {
+        Self {
+            table,
+            to_batch,
+            rows: Vec::new(),
+            stem: None,
+            writer: None,
+        }
+    }
+
+    fn push(
+        &mut self,
+        options: &ExportOptions,
+        height: u64,
+        rows: impl IntoIterator<Item = R>,
+    ) -> anyhow::Result<()> {
+        let stem = options.file_stem(self.table, height);
+        if self.stem.as_ref() != Some(&stem) {
+            self.finish(options)?;
+            self.stem = Some(stem);
+        }
+        self.rows.extend(rows);
+        if self.rows.len() >= options.row_group_size {
+            self.write_row_group(options)?;
+        }
+        Ok(())
+    }
+
+    fn write_row_group(&mut self, options: &ExportOptions) -> anyhow::Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let batch = (self.to_batch)(&self.rows)?;
+        self.rows.clear();
+        if self.writer.is_none() {
+            let stem = self.stem.as_ref().expect("rows belong to a height range");
+            let mut path = stem.as_os_str().to_os_string();
+            path.push(".parquet");
+            let path = std::path::PathBuf::from(path);
+            tracing::debug!(target: "export", "Writing {}", path.display());
+            let properties = parquet::file::properties::WriterProperties::builder()
+                .set_compression(parquet::basic::Compression::SNAPPY)
+                .set_max_row_group_size(options.row_group_size)
+                .build();
+            self.writer = Some(parquet::arrow::ArrowWriter::try_new(
+                std::fs::File::create(path)?,
+                batch.schema(),
+                Some(properties),
+            )?);
+        }
+        let writer = self.writer.as_mut().expect("writer was just created");
+        writer.write(&batch)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self, options: &ExportOptions) -> anyhow::Result<()> {
+        self.write_row_group(options)?;
+        if let Some(writer) = self.writer.take() {
+            writer.close()?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes blocks, transactions, inputs and outputs to one Parquet file per table and height range.
+/// Amounts are unsigned 64 bit integers in satoshis, hashes 32 byte fixed-size binaries in the
+/// byte order displayed by Bitcoin Core.
+pub struct ParquetExporter {
+    options: ExportOptions,
+    network: bitcoin::Network,
+    blocks: TableFile<BlockRow>,
+    transactions: TableFile<TransactionRow>,
+    inputs: TableFile<InputRow>,
+    outputs: TableFile<OutputRow>,
+}
+
+impl ParquetExporter {
+    #[must_use]
+    pub fn new(options: ExportOptions, network: bitcoin::Network) -> Self {
+        Self {
+            options,
+            network,
+            blocks: TableFile::new("blocks", blocks_batch),
+            transactions: TableFile::new("transactions", transactions_batch),
+            inputs: TableFile::new("inputs", inputs_batch),
+            outputs: TableFile::new("outputs", outputs_batch),
+        }
+    }
+}
+
+impl BlockVisitor for ParquetExporter {
+    fn on_resume(&mut self, checkpoint: u64) -> anyhow::Result<u64> {
+        Ok(self.options.range_start(checkpoint + 1))
+    }
+
+    fn on_start(&mut self, _height: u64) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.options.dir)?;
+        Ok(())
+    }
+
+    fn on_block(&mut self, data: &BlockData) -> anyhow::Result<()> {
+        let rows = Rows::new(data, self.network)?;
+        self.blocks.push(&self.options, data.height, [rows.block])?;
+        self.transactions
+            .push(&self.options, data.height, rows.transactions)?;
+        self.inputs.push(&self.options, data.height, rows.inputs)?;
+        self.outputs.push(&self.options, data.height, rows.outputs)
+    }
+
+    fn on_complete(&mut self, _height: u64) -> anyhow::Result<()> {
+        self.blocks.finish(&self.options)?;
+        self.transactions.finish(&self.options)?;
+        self.inputs.finish(&self.options)?;
+        self.outputs.finish(&self.options)
+    }
+}
@@ -0,0 +1,364 @@
+use bitcoin::blockdata::opcodes::all::{
+    OP_CLTV, OP_CSV, OP_INVALIDOPCODE, OP_PUSHNUM_1, OP_PUSHNUM_16, OP_PUSHNUM_NEG1, OP_RETURN_187,
+};
+use bitcoin::blockdata::script::Instruction;
+
+use crate::export::to_hex;
+use crate::parser::script::ScriptType;
+
+/// Scripts larger than this are unspendable
+const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// Characters allowed in descriptors, in the order used by the checksum
+const DESCRIPTOR_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Amount in BTC with 8 decimals, as printed by `ValueFromAmount`
+#[must_use]
+pub fn amount(sats: u64) -> serde_json::Value {
+    let number = format!("{}.{:08}", sats / 100_000_000, sats % 100_000_000);
+    serde_json::Value::Number(number.parse().expect("amounts are valid numbers"))
+}
+
+/// Number with 16 significant digits, as printed by `UniValue` for doubles
+#[must_use]
+pub fn float(value: f64) -> serde_json::Value {
+    let scientific = format!("{value:.15e}");
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("scientific notation has an exponent");
+    let exponent: i32 = exponent.parse().expect("exponent is an integer");
+    let sign = if mantissa.starts_with('-') { "-" } else { "" };
+    let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    let trim = |s: String| {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            s
+        }
+    };
+    let number = if digits.bytes().all(|digit| digit == b'0') {
+        "0".to_string()
+    } else if !(-4..16).contains(&exponent) {
+        let mantissa = trim(format!("{}.{}", &digits[..1], &digits[1..]));
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        format!("{sign}{mantissa}e{exponent_sign}{:02}", exponent.abs())
+    } else if exponent >= 0 {
+        let (integer, fraction) = digits.split_at(usize::try_from(exponent).unwrap_or(0) + 1);
+        format!("{sign}{}", trim(format!("{integer}.{fraction}")))
+    } else {
+        let zeros = "0".repeat(usize::try_from(-exponent - 1).unwrap_or(0));
+        format!("{sign}{}", trim(format!("0.{zeros}{digits}")))
+    };
+    serde_json::Value::Number(number.parse().expect("floats are valid numbers"))
+}
+
+/// `type` of a `scriptPubKey`, following `GetTxnOutputType` in Bitcoin Core
+#[must_use]
+pub fn script_type(script: &bitcoin::Script) -> &'static str {
+    match ScriptType::of(script) {
+        ScriptType::P2pk => "pubkey",
+        ScriptType::P2pkh => "pubkeyhash",
+        ScriptType::P2sh => "scripthash",
+        ScriptType::P2wpkh => "witness_v0_keyhash",
+        ScriptType::P2wsh => "witness_v0_scripthash",
+        ScriptType::P2tr => "witness_v1_taproot",
+        ScriptType::Multisig => "multisig",
+        ScriptType::OpReturn if is_push_only(&script.as_bytes()[1..]) => "nulldata",
+        ScriptType::WitnessUnknown(_) => "witness_unknown",
+        ScriptType::OpReturn | ScriptType::NonStandard => "nonstandard",
+    }
+}
+
+/// Whether the script only consists of pushes, counting `OP_RESERVED` as one like Bitcoin Core
+fn is_push_only(script: &[u8]) -> bool {
+    bitcoin::Script::from_bytes(script)
+        .instructions()
+        .all(|instruction| match instruction {
+            Ok(Instruction::PushBytes(_)) => true,
+            Ok(Instruction::Op(op)) => op.to_u8() <= OP_PUSHNUM_16.to_u8(),
+            Err(_) => false,
+        })
+}
+
+/// Human readable script, following `ScriptToAsmStr` in Bitcoin Core.
+/// With `decode_sighash`, the sighash type of signatures is printed like `[ALL]`.
+#[must_use]
+pub fn asm(script: &bitcoin::Script, decode_sighash: bool) -> String {
+    let unspendable = script.is_op_return() || script.len() > MAX_SCRIPT_SIZE;
+    let mut parts = Vec::new();
+    for instruction in script.instructions() {
+        let part = match instruction {
+            Ok(Instruction::PushBytes(bytes)) => {
+                let bytes = bytes.as_bytes();
+                match bytes.split_last() {
+                    _ if bytes.len() <= 4 => script_num(bytes).to_string(),
+                    Some((hash_type, signature))
+                        if decode_sighash
+                            && !unspendable
+                            && is_valid_signature_encoding(bytes)
+                            && sighash_name(*hash_type).is_some() =>
+                    {
+                        format!(
+                            "{}[{}]",
+                            to_hex(signature),
+                            sighash_name(*hash_type).unwrap_or_default()
+                        )
+                    }
+                    _ => to_hex(bytes),
+                }
+            }
+            Ok(Instruction::Op(op)) => op_name(op),
+            Err(_) => {
+                parts.push("[error]".to_string());
+                break;
+            }
+        };
+        parts.push(part);
+    }
+    parts.join(" ")
+}
+
+/// Opcode names as used by `GetOpName` in Bitcoin Core
+fn op_name(op: bitcoin::blockdata::opcodes::All) -> String {
+    let code = op.to_u8();
+    if op == OP_PUSHNUM_NEG1 {
+        "-1".to_string()
+    } else if (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(&code) {
+        (code - OP_PUSHNUM_1.to_u8() + 1).to_string()
+    } else if op == OP_CLTV {
+        "OP_CHECKLOCKTIMEVERIFY".to_string()
+    } else if op == OP_CSV {
+        "OP_CHECKSEQUENCEVERIFY".to_string()
+    } else if (OP_RETURN_187.to_u8()..OP_INVALIDOPCODE.to_u8()).contains(&code) {
+        "OP_UNKNOWN".to_string()
+    } else {
+        op.to_string()
+    }
+}
+
+/// Decodes a little endian sign-magnitude number, also if it isn't minimally encoded.
+fn script_num(bytes: &[u8]) -> i64 {
+    let Some(last) = bytes.last() else {
+        return 0;
+    };
+    let value = bytes
+        .iter()
+        .rev()
+        .fold(0_i64, |acc, byte| (acc << 8) | i64::from(*byte));
+    if last & 0x80 == 0 {
+        value
+    } else {
+        -(value & !(0x80_i64 << (8 * (bytes.len() - 1))))
+    }
+}
+
+fn sighash_name(hash_type: u8) -> Option<&'static str> {
+    match hash_type {
+        0x01 => Some("ALL"),
+        0x81 => Some("ALL|ANYONECANPAY"),
+        0x02 => Some("NONE"),
+        0x82 => Some("NONE|ANYONECANPAY"),
+        0x03 => Some("SINGLE"),
+        0x83 => Some("SINGLE|ANYONECANPAY"),
+        _ => None,
+    }
+}
+
+/// Strict DER encoding of a signature followed by the sighash type, as defined by BIP66
+fn is_valid_signature_encoding(sig: &[u8]) -> bool {
+    if sig.len() < 9 || sig.len() > 73 || sig[0] != 0x30 || usize::from(sig[1]) != sig.len() - 3 {
+        return false;
+    }
+    let len_r = usize::from(sig[3]);
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    let len_s = usize::from(sig[5 + len_r]);
+    if len_r + len_s + 7 != sig.len() {
+        return false;
+    }
+    sig[2] == 0x02
+        && len_r != 0
+        && sig[4] & 0x80 == 0
+        && !(len_r > 1 && sig[4] == 0x00 && sig[5] & 0x80 == 0)
+        && sig[len_r + 4] == 0x02
+        && len_s != 0
+        && sig[len_r + 6] & 0x80 == 0
+        && !(len_s > 1 && sig[len_r + 6] == 0x00 && sig[len_r + 7] & 0x80 == 0)
+}
+
+/// Output descriptor with checksum, following `InferDescriptor` in Bitcoin Core
+/// without any keys or scripts known to the wallet
+#[must_use]
+pub fn descriptor(script: &bitcoin::Script, network: bitcoin::Network) -> String {
+    let bytes = script.as_bytes();
+    let inferred = match ScriptType::of(script) {
+        ScriptType::P2pk => {
+            let key = &bytes[1..bytes.len() - 1];
+            bitcoin::PublicKey::from_slice(key)
+                .ok()
+                .map(|_| format!("pk({})", to_hex(key)))
+        }
+        ScriptType::P2tr => {
+            let key = &bytes[2..];
+            bitcoin::key::XOnlyPublicKey::from_slice(key)
+                .ok()
+                .map(|_| format!("rawtr({})", to_hex(key)))
+        }
+        ScriptType::Multisig => multi(script),
+        _ => None,
+    };
+    let descriptor = inferred
+        .or_else(|| {
+            bitcoin::Address::from_script(script, network)
+                .ok()
+                .map(|address| format!("addr({address})"))
+        })
+        .unwrap_or_else(|| format!("raw({})", to_hex(bytes)));
+    with_checksum(&descriptor)
+}
+
+/// `multi(k,key,...)` if all keys of a bare multisig script are valid
+fn multi(script: &bitcoin::Script) -> Option<String> {
+    let instructions: Vec<_> = script.instructions().collect::<Result<_, _>>().ok()?;
+    let [Instruction::Op(m), keys @ .., _, _] = instructions.as_slice() else {
+        return None;
+    };
+    let mut parts = vec![(m.to_u8() - OP_PUSHNUM_1.to_u8() + 1).to_string()];
+    for key in keys {
+        let Instruction::PushBytes(key) = key else {
+            return None;
+        };
+        bitcoin::PublicKey::from_slice(key.as_bytes()).ok()?;
+        parts.push(to_hex(key.as_bytes()));
+    }
+    Some(format!("multi({})", parts.join(",")))
+}
+
+/// Appends the checksum defined by BIP380.
+fn with_checksum(descriptor: &str) -> String {
+    const GENERATOR: [u64; 5] = [
+        0xf5_dee5_1989,
+        0xa9_fdca_3312,
+        0x1b_ab10_e32d,
+        0x37_06b1_677a,
+        0x64_4d62_6ffd,
+    ];
+    let polymod = |checksum: u64, value: u64| {
+        let top = checksum >> 35;
+        let mut checksum = ((checksum & 0x07_ffff_ffff) << 5) ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+        checksum
+    };
+
+    let mut checksum = 1;
+    let mut groups = Vec::with_capacity(3);
+    for c in descriptor.chars() {
+        let Some(position) = DESCRIPTOR_CHARSET.find(c) else {
+            return descriptor.to_string();
+        };
+        let position = position as u64;
+        checksum = polymod(checksum, position & 31);
+        groups.push(position >> 5);
+        if groups.len() == 3 {
+            checksum = polymod(checksum, groups[0] * 9 + groups[1] * 3 + groups[2]);
+            groups.clear();
+        }
+    }
+    match groups.as_slice() {
+        [a] => checksum = polymod(checksum, *a),
+        [a, b] => checksum = polymod(checksum, a * 3 + b),
+        _ => {}
+    }
+    for _ in 0..8 {
+        checksum = polymod(checksum, 0);
+    }
+    checksum ^= 1;
+
+    let suffix: String = (0..8)
+        .map(|i| char::from(CHECKSUM_CHARSET[((checksum >> (5 * (7 - i))) & 31) as usize]))
+        .collect();
+    format!("{descriptor}#{suffix}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(hex: &str) -> bitcoin::ScriptBuf {
+        bitcoin::ScriptBuf::from_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn test_amount_and_float() {
+        assert_eq!(amount(5_000_000_000).to_string(), "50.00000000");
+        assert_eq!(amount(1).to_string(), "0.00000001");
+        assert_eq!(float(1.0).to_string(), "1");
+        assert_eq!(
+            float(16_307.420_938_523_983).to_string(),
+            "16307.42093852398"
+        );
+        assert_eq!(float(0.000_123).to_string(), "0.000123");
+        assert_eq!(float(1.5e-7).to_string(), "1.5e-07");
+        assert_eq!(float(0.0).to_string(), "0");
+    }
+
+    #[test]
+    fn test_asm() {
+        let p2pkh = script("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac");
+        assert_eq!(
+            asm(&p2pkh, false),
+            "OP_DUP OP_HASH160 62e907b15cbf27d5425399ebf6f0fb50ebb88f18 OP_EQUALVERIFY OP_CHECKSIG"
+        );
+        assert_eq!(
+            asm(&script("0051b1b2bb4f"), false),
+            "0 1 OP_CHECKLOCKTIMEVERIFY OP_CHECKSEQUENCEVERIFY OP_UNKNOWN -1"
+        );
+        assert_eq!(asm(&script("03ffff001d"), false), "65535 [error]");
+        assert_eq!(asm(&script("0381f600"), false), "63105");
+        assert_eq!(asm(&script("0181"), false), "-1");
+
+        let script_sig = script("47304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901");
+        assert_eq!(
+            asm(&script_sig, true),
+            "304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d09[ALL]"
+        );
+        assert!(asm(&script_sig, false).ends_with("1d0901"));
+    }
+
+    #[test]
+    fn test_descriptor() {
+        assert_eq!(with_checksum("raw(deadbeef)"), "raw(deadbeef)#89f8spxm");
+
+        let genesis = bitcoin::blockdata::constants::genesis_block(bitcoin::Network::Bitcoin);
+        let script_pubkey = &genesis.txdata[0].output[0].script_pubkey;
+        assert_eq!(
+            descriptor(script_pubkey, bitcoin::Network::Bitcoin),
+            "pk(04678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f)#vlz6ztea"
+        );
+        assert_eq!(script_type(script_pubkey), "pubkey");
+
+        let p2pkh = script("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac");
+        assert_eq!(
+            descriptor(&p2pkh, bitcoin::Network::Bitcoin),
+            with_checksum("addr(1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa)")
+        );
+        assert_eq!(
+            with_checksum("addr(1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa)"),
+            "addr(1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa)#632p52jr"
+        );
+
+        assert_eq!(
+            descriptor(&script("6a04deadbeef"), bitcoin::Network::Bitcoin),
+            with_checksum("raw(6a04deadbeef)")
+        );
+        assert_eq!(script_type(&script("6a04deadbeef")), "nulldata");
+        assert_eq!(script_type(&script("6a76")), "nonstandard");
+    }
+}
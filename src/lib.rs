@@ -3,7 +3,9 @@ use clap::{Arg, Command};
 use crate::parser::types::{Bitcoin, CoinType};
 
 pub mod db;
+pub mod export;
 pub mod parser;
+pub mod query;
 
 #[derive(Copy, Clone)]
 #[cfg_attr(test, derive(PartialEq, Debug))]
@@ -36,54 +38,200 @@ impl std::fmt::Display for BlockHeightRange {
     }
 }
 
+/// How blocks are checked against the consensus rules while parsing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Verify {
+    Off,
+    /// Stops at the first inconsistency
+    Strict,
+    /// Records all inconsistencies and prints a summary of them at the end
+    Report,
+}
+
 pub struct ParserOptions {
     pub db_url: String,
     pub coin: CoinType,
-    pub verify: bool,
+    pub verify: Verify,
+    /// Also executes all input scripts, requires `verify` not to be `Off`
+    pub verify_scripts: bool,
+    /// Numbers of blocks the hashrate is estimated over
+    pub hashrate_windows: Vec<u64>,
+    /// Writes the database, disabled by the `verify` and `export` subcommands
+    pub write_db: bool,
+    /// Number of blocks written to the database per transaction
+    pub batch_size: usize,
+    /// Drops the secondary indexes while parsing and creates them at the end
+    pub defer_indexes: bool,
+    pub export: Option<export::ExportOptions>,
     pub blockchain_dir: std::path::PathBuf,
     pub range: BlockHeightRange,
 }
 
+/// Subcommand given on the command line with its options
+pub enum Subcommand {
+    /// `parse`, `verify` and `export` all run the parser, only their options differ
+    Parse(ParserOptions),
+    Query(query::QueryOptions),
+    /// Prints information about the blockchain directory
+    Info(ParserOptions),
+    /// Lists the chain tips of the block index
+    Tips(ParserOptions),
+}
+
 #[must_use]
 pub fn command() -> Command {
     let coins = ["bitcoin", "testnet3"];
     Command::new("bitcoin-blockparser")
     .version(clap::crate_version!())
-    .arg(Arg::new("db-url")
-        .short('u')
-        .long("db-url")
-        .help("The URL of the database (default in-memory)"))
-    .arg(Arg::new("verify")
-        .long("verify")
-        .action(clap::ArgAction::SetTrue)
-        .value_parser(clap::value_parser!(bool))
-        .help("Verifies merkle roots and block hashes"))
+    .subcommand_required(true)
+    .arg_required_else_help(true)
     .arg(Arg::new("verbosity")
         .short('v')
+        .global(true)
         .action(clap::ArgAction::Count)
         .help("Increases verbosity level. Info=0, Debug=1, Trace=2 (default: 0)"))
     .arg(Arg::new("coin")
         .short('c')
         .long("coin")
+        .global(true)
         .value_name("NAME")
         .value_parser(clap::builder::PossibleValuesParser::new(coins))
         .help("Specify blockchain coin (default: bitcoin)"))
     .arg(Arg::new("blockchain-dir")
         .short('d')
         .long("blockchain-dir")
+        .global(true)
         .help("Sets blockchain directory which contains blk.dat files (default: ~/.bitcoin/blocks)"))
-    .arg(Arg::new("start")
-        .short('s')
-        .long("start")
-        .value_name("HEIGHT")
-        .value_parser(clap::value_parser!(u64))
-        .help("Specify starting block for parsing (inclusive)"))
-    .arg(Arg::new("end")
-        .short('e')
-        .long("end")
-        .value_name("HEIGHT")
-        .value_parser(clap::value_parser!(u64))
-        .help("Specify last block for parsing (inclusive) (default: all known blocks)"))
+    .subcommand(Command::new("parse")
+        .about("Parses the blocks into a database, optionally verifying and exporting them")
+        .arg(db_url_arg())
+        .arg(Arg::new("batch-size")
+            .long("batch-size")
+            .value_name("BLOCKS")
+            .default_value("100")
+            .value_parser(clap::value_parser!(u64).range(1..))
+            .help("Number of blocks written to the database in one transaction"))
+        .arg(Arg::new("defer-indexes")
+            .long("defer-indexes")
+            .action(clap::ArgAction::SetTrue)
+            .help("Drops the database indexes while parsing and recreates them at the end, \
+                   faster for long runs into a large database"))
+        .arg(Arg::new("verify")
+            .long("verify")
+            .value_name("MODE")
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value("strict")
+            .value_parser(clap::builder::PossibleValuesParser::new(["strict", "report"]))
+            .help("Verifies block hashes, merkle roots, witness commitments and block limits. \
+                   `report` records all inconsistencies instead of exiting on the first one (default: strict)"))
+        .arg(Arg::new("verify-scripts")
+            .long("verify-scripts")
+            .action(clap::ArgAction::SetTrue)
+            .value_parser(clap::value_parser!(bool))
+            .help("Executes all input scripts against their prevouts from the undo data, implies --verify"))
+        .arg(Arg::new("hashrate-window")
+            .long("hashrate-window")
+            .value_name("BLOCKS")
+            .action(clap::ArgAction::Append)
+            .value_delimiter(',')
+            .default_values(["144", "2016"])
+            .value_parser(clap::value_parser!(u64).range(1..))
+            .help("Number of blocks the hashrate is estimated over, can be given multiple times"))
+        .arg(Arg::new("export")
+            .long("export")
+            .value_name("FORMAT")
+            .value_parser(clap::builder::PossibleValuesParser::new(export::ExportFormat::ALL))
+            .help("Additionally exports blocks, transactions, inputs and outputs to files"))
+        .args(export_args(true))
+        .args(range_args()))
+    .subcommand(Command::new("verify")
+        .about("Verifies the blocks without writing a database")
+        .arg(Arg::new("report")
+            .long("report")
+            .action(clap::ArgAction::SetTrue)
+            .help("Continues past inconsistencies and prints a summary of all of them at the end"))
+        .arg(Arg::new("scripts")
+            .long("scripts")
+            .action(clap::ArgAction::SetTrue)
+            .help("Also executes all input scripts against their prevouts from the undo data"))
+        .args(range_args()))
+    .subcommand(Command::new("export")
+        .about("Exports blocks, transactions, inputs and outputs to files without writing a database")
+        .arg(Arg::new("export")
+            .value_name("FORMAT")
+            .required(true)
+            .value_parser(clap::builder::PossibleValuesParser::new(export::ExportFormat::ALL))
+            .help("Format of the exported files"))
+        .args(export_args(false))
+        .args(range_args()))
+    .subcommand(Command::new("query")
+        .about("Runs a canned query against an existing database and prints tab-separated rows")
+        .arg(Arg::new("query")
+            .value_name("QUERY")
+            .required(true)
+            .value_parser(clap::builder::PossibleValuesParser::new(query::Query::ALL))
+            .help("Query to run"))
+        .arg(db_url_arg().required(true))
+        .args(range_args()))
+    .subcommand(Command::new("info")
+        .about("Prints information about the blockchain directory"))
+    .subcommand(Command::new("tips")
+        .about("Lists the chain tips of the block index like `getchaintips` as tab-separated rows"))
+}
+
+fn db_url_arg() -> Arg {
+    Arg::new("db-url")
+        .short('u')
+        .long("db-url")
+        .help("The URL of the database, `duckdb://<path>` for DuckDB (default: in-memory SQLite)")
+}
+
+/// Options of the exported files, prefixed with `export-` for `parse` where exporting is optional
+fn export_args(parse: bool) -> [Arg; 4] {
+    let long = |name, prefixed| if parse { prefixed } else { name };
+    [
+        Arg::new("export-dir")
+            .long(long("dir", "export-dir"))
+            .value_name("DIR")
+            .requires("export")
+            .help("Directory the exported files are written to (default: current directory)"),
+        Arg::new("export-rotate")
+            .long(long("rotate", "export-rotate"))
+            .value_name("BLOCKS")
+            .requires("export")
+            .value_parser(clap::value_parser!(u64).range(1..))
+            .help("Starts a new file for every range of this many blocks (default: one file per table)"),
+        Arg::new("export-row-group-size")
+            .long(long("row-group-size", "export-row-group-size"))
+            .value_name("ROWS")
+            .requires("export")
+            .default_value("1048576")
+            .value_parser(clap::value_parser!(u64).range(1..))
+            .help("Maximum number of rows per row group of Parquet files"),
+        Arg::new("export-gzip")
+            .long(long("gzip", "export-gzip"))
+            .requires("export")
+            .action(clap::ArgAction::SetTrue)
+            .help("Compresses the exported text files with gzip"),
+    ]
+}
+
+fn range_args() -> [Arg; 2] {
+    [
+        Arg::new("start")
+            .short('s')
+            .long("start")
+            .value_name("HEIGHT")
+            .value_parser(clap::value_parser!(u64))
+            .help("Specify starting block for parsing (inclusive)"),
+        Arg::new("end")
+            .short('e')
+            .long("end")
+            .value_name("HEIGHT")
+            .value_parser(clap::value_parser!(u64))
+            .help("Specify last block for parsing (inclusive) (default: all known blocks)"),
+    ]
 }
 
 fn get_absolute_blockchain_dir(coin: &CoinType) -> std::path::PathBuf {
@@ -92,30 +240,118 @@ fn get_absolute_blockchain_dir(coin: &CoinType) -> std::path::PathBuf {
         .join(&coin.default_folder)
 }
 
-pub fn parse_args(matches: &clap::ArgMatches) -> anyhow::Result<ParserOptions> {
-    let db_url: String = matches
-        .get_one("db-url")
-        .cloned()
-        .unwrap_or_else(|| ":memory:".parse().unwrap());
-    let verify = matches.get_flag("verify");
+/// Value of the argument `id`, `None` if the subcommand doesn't have it
+fn value<T: Clone + Send + Sync + 'static>(matches: &clap::ArgMatches, id: &str) -> Option<T> {
+    matches.try_get_one::<T>(id).ok().flatten().cloned()
+}
+
+fn flag(matches: &clap::ArgMatches, id: &str) -> bool {
+    value(matches, id).unwrap_or(false)
+}
+
+fn db_url(matches: &clap::ArgMatches) -> anyhow::Result<String> {
+    let db_url = value(matches, "db-url").unwrap_or_else(|| ":memory:".to_string());
+    if db_url.starts_with(db::DUCKDB_PREFIX) && !cfg!(feature = "duckdb") {
+        anyhow::bail!("DuckDB databases require building with the `duckdb` feature");
+    }
+    Ok(db_url)
+}
+
+fn range(matches: &clap::ArgMatches) -> anyhow::Result<BlockHeightRange> {
+    BlockHeightRange::new(value(matches, "start").unwrap_or(0), value(matches, "end"))
+}
+
+pub fn parse_args(matches: &clap::ArgMatches) -> anyhow::Result<Subcommand> {
+    let Some((name, matches)) = matches.subcommand() else {
+        anyhow::bail!("no subcommand given");
+    };
+    match name {
+        "query" => Ok(Subcommand::Query(query::QueryOptions {
+            db_url: db_url(matches)?,
+            query: value::<String>(matches, "query")
+                .unwrap_or_default()
+                .parse()?,
+            range: range(matches)?,
+        })),
+        "info" => Ok(Subcommand::Info(parser_options(matches)?)),
+        "tips" => Ok(Subcommand::Tips(parser_options(matches)?)),
+        "verify" => {
+            let mut options = parser_options(matches)?;
+            options.verify = if flag(matches, "report") {
+                Verify::Report
+            } else {
+                Verify::Strict
+            };
+            options.verify_scripts = flag(matches, "scripts");
+            options.write_db = false;
+            Ok(Subcommand::Parse(options))
+        }
+        "export" => {
+            let mut options = parser_options(matches)?;
+            options.write_db = false;
+            Ok(Subcommand::Parse(options))
+        }
+        _ => Ok(Subcommand::Parse(parser_options(matches)?)),
+    }
+}
+
+/// Options of the parser from the arguments of a subcommand, defaults for the ones it doesn't have
+fn parser_options(matches: &clap::ArgMatches) -> anyhow::Result<ParserOptions> {
+    let db_url = db_url(matches)?;
+    let verify_scripts = flag(matches, "verify-scripts");
+    let verify = match value::<String>(matches, "verify").as_deref() {
+        Some("report") => Verify::Report,
+        Some(_) => Verify::Strict,
+        None if verify_scripts => Verify::Strict,
+        None => Verify::Off,
+    };
+    let mut hashrate_windows: Vec<u64> = matches
+        .try_get_many::<u64>("hashrate-window")
+        .ok()
+        .flatten()
+        .map(|windows| windows.copied().collect())
+        .unwrap_or_default();
+    hashrate_windows.sort_unstable();
+    hashrate_windows.dedup();
+    let export = value::<String>(matches, "export")
+        .map(|format| {
+            let format: export::ExportFormat = format.parse()?;
+            let gzip = flag(matches, "export-gzip");
+            if gzip && !format.is_text() {
+                anyhow::bail!("--export-gzip can't be used with {} files", format.as_str());
+            }
+            anyhow::Ok(export::ExportOptions {
+                format,
+                dir: value::<String>(matches, "export-dir")
+                    .map_or_else(|| std::path::PathBuf::from("."), std::path::PathBuf::from),
+                rotate: value(matches, "export-rotate"),
+                gzip,
+                row_group_size: value(matches, "export-row-group-size")
+                    .unwrap_or(1_048_576_u64)
+                    .try_into()?,
+            })
+        })
+        .transpose()?;
 
-    let coin = matches
-        .get_one::<String>("coin")
+    let coin = value::<String>(matches, "coin")
         .map_or_else(|| CoinType::from(Bitcoin), |v| v.parse().unwrap());
-    let blockchain_dir = match matches.get_one::<String>("blockchain-dir") {
+    let blockchain_dir = match value::<String>(matches, "blockchain-dir") {
         Some(p) => std::path::PathBuf::from(p),
         None => get_absolute_blockchain_dir(&coin),
     };
-    let start = matches.get_one::<u64>("start").copied().unwrap_or(0);
-    let end = matches.get_one::<u64>("end").copied();
-    let range = BlockHeightRange::new(start, end)?;
 
     let options = ParserOptions {
         db_url,
         coin,
         verify,
+        verify_scripts,
+        hashrate_windows,
+        write_db: true,
+        batch_size: value(matches, "batch-size").unwrap_or(100_u64).try_into()?,
+        defer_indexes: flag(matches, "defer-indexes"),
+        export,
         blockchain_dir,
-        range,
+        range: range(matches)?,
     };
     Ok(options)
 }
@@ -124,47 +360,278 @@ pub fn parse_args(matches: &clap::ArgMatches) -> anyhow::Result<ParserOptions> {
 mod tests {
     use super::*;
 
+    /// Options of the parser for the command line `args`
+    fn parse(args: &[&str]) -> anyhow::Result<ParserOptions> {
+        match parse_args(&command().try_get_matches_from(args.iter().copied())?)? {
+            Subcommand::Parse(options) | Subcommand::Info(options) | Subcommand::Tips(options) => {
+                Ok(options)
+            }
+            Subcommand::Query(_) => anyhow::bail!("the query subcommand doesn't run the parser"),
+        }
+    }
+
     #[test]
     fn test_args_coin() {
-        let args = ["bitcoin-blockparser"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse"];
+        let options = parse(&args).unwrap();
         assert_eq!(options.coin.name, "Bitcoin");
 
-        let args = ["bitcoin-blockparser", "-c", "testnet3"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse", "-c", "testnet3"];
+        let options = parse(&args).unwrap();
         assert_eq!(options.coin.name, "TestNet3");
     }
 
     #[test]
     fn test_args_db_url() {
-        let args = ["bitcoin-blockparser"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse"];
+        let options = parse(&args).unwrap();
         assert_eq!(options.db_url.to_string(), ":memory:");
 
-        let args = ["bitcoin-blockparser", "--db-url", "mydb.db"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse", "--db-url", "mydb.db"];
+        let options = parse(&args).unwrap();
         assert_eq!(options.db_url.to_string(), "mydb.db");
 
-        let args = ["bitcoin-blockparser", "-u", "postgresql://localhost:5432"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = [
+            "bitcoin-blockparser",
+            "parse",
+            "-u",
+            "postgresql://localhost:5432",
+        ];
+        let options = parse(&args).unwrap();
         assert_eq!(options.db_url.to_string(), "postgresql://localhost:5432");
+
+        let args = [
+            "bitcoin-blockparser",
+            "parse",
+            "-u",
+            "duckdb://chain.duckdb",
+        ];
+        let options = parse(&args);
+        if cfg!(feature = "duckdb") {
+            assert_eq!(options.unwrap().db_url, "duckdb://chain.duckdb");
+        } else {
+            assert!(options.is_err());
+        }
     }
 
     #[test]
     fn test_args_verify() {
+        let args = ["bitcoin-blockparser", "parse"];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.verify, Verify::Off);
+
+        let args = ["bitcoin-blockparser", "parse", "--verify"];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.verify, Verify::Strict);
+        assert!(!options.verify_scripts);
+
+        let args = ["bitcoin-blockparser", "parse", "--verify=strict"];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.verify, Verify::Strict);
+
+        let args = ["bitcoin-blockparser", "parse", "--verify=report"];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.verify, Verify::Report);
+
+        let args = ["bitcoin-blockparser", "parse", "--verify=foo"];
+        assert!(command().try_get_matches_from(args).is_err());
+
+        let args = ["bitcoin-blockparser", "parse", "--verify-scripts"];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.verify, Verify::Strict);
+        assert!(options.verify_scripts);
+    }
+
+    #[test]
+    fn test_args_hashrate_window() {
+        let args = ["bitcoin-blockparser", "parse"];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.hashrate_windows, vec![144, 2016]);
+
+        let args = ["bitcoin-blockparser", "parse", "--hashrate-window", "6,144"];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.hashrate_windows, vec![6, 144]);
+
+        let args = [
+            "bitcoin-blockparser",
+            "parse",
+            "--hashrate-window",
+            "6",
+            "--hashrate-window",
+            "1008",
+            "--hashrate-window",
+            "6",
+        ];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.hashrate_windows, vec![6, 1008]);
+
+        let args = ["bitcoin-blockparser", "parse", "--hashrate-window", "0"];
+        assert!(command().try_get_matches_from(args).is_err());
+    }
+
+    #[test]
+    fn test_args_batch_size() {
+        let args = ["bitcoin-blockparser", "parse"];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.batch_size, 100);
+        assert!(!options.defer_indexes);
+
+        let args = [
+            "bitcoin-blockparser",
+            "parse",
+            "--batch-size",
+            "1000",
+            "--defer-indexes",
+        ];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.batch_size, 1000);
+        assert!(options.defer_indexes);
+
+        let args = ["bitcoin-blockparser", "parse", "--batch-size", "0"];
+        assert!(command().try_get_matches_from(args).is_err());
+    }
+
+    #[test]
+    fn test_args_export() {
+        let args = ["bitcoin-blockparser", "parse"];
+        let options = parse(&args).unwrap();
+        assert!(options.export.is_none());
+        assert!(options.write_db);
+
+        let args = ["bitcoin-blockparser", "parse", "--export", "csv"];
+        let options = parse(&args).unwrap();
+        assert_eq!(
+            options.export,
+            Some(export::ExportOptions {
+                format: export::ExportFormat::Csv,
+                dir: std::path::PathBuf::from("."),
+                rotate: None,
+                gzip: false,
+                row_group_size: 1_048_576,
+            })
+        );
+
+        let args = [
+            "bitcoin-blockparser",
+            "export",
+            "csv",
+            "--dir",
+            "out",
+            "--rotate",
+            "1000",
+            "--gzip",
+        ];
+        let options = parse(&args).unwrap();
+        assert_eq!(
+            options.export,
+            Some(export::ExportOptions {
+                format: export::ExportFormat::Csv,
+                dir: std::path::PathBuf::from("out"),
+                rotate: Some(1000),
+                gzip: true,
+                row_group_size: 1_048_576,
+            })
+        );
+        assert!(!options.write_db);
+
+        let args = [
+            "bitcoin-blockparser",
+            "parse",
+            "--export",
+            "parquet",
+            "--export-row-group-size",
+            "10000",
+        ];
+        let options = parse(&args).unwrap();
+        let export = options.export.unwrap();
+        assert_eq!(export.format, export::ExportFormat::Parquet);
+        assert_eq!(export.row_group_size, 10_000);
+
+        let args = [
+            "bitcoin-blockparser",
+            "parse",
+            "--export",
+            "parquet",
+            "--export-gzip",
+        ];
+        assert!(parse(&args).is_err());
+
+        let args = ["bitcoin-blockparser", "export"];
+        assert!(command().try_get_matches_from(args).is_err());
+
+        let args = ["bitcoin-blockparser", "parse", "--export-gzip"];
+        assert!(command().try_get_matches_from(args).is_err());
+
+        let args = ["bitcoin-blockparser", "parse", "--export", "xml"];
+        assert!(command().try_get_matches_from(args).is_err());
+    }
+
+    #[test]
+    fn test_args_subcommands() {
         let args = ["bitcoin-blockparser"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
-        assert!(!options.verify);
+        assert!(command().try_get_matches_from(args).is_err());
+
+        let args = [
+            "bitcoin-blockparser",
+            "verify",
+            "--report",
+            "-c",
+            "testnet3",
+        ];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.verify, Verify::Report);
+        assert!(!options.verify_scripts);
+        assert!(!options.write_db);
+        assert_eq!(options.coin.name, "TestNet3");
+
+        let args = ["bitcoin-blockparser", "-d", "foo", "info"];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.blockchain_dir.to_str().unwrap(), "foo");
+
+        let args = ["bitcoin-blockparser", "info", "--start", "10"];
+        assert!(command().try_get_matches_from(args).is_err());
+
+        let args = ["bitcoin-blockparser", "tips"];
+        let subcommand = parse_args(&command().get_matches_from(args)).unwrap();
+        assert!(matches!(subcommand, Subcommand::Tips(_)));
+    }
+
+    #[test]
+    fn test_args_query() {
+        let args = [
+            "bitcoin-blockparser",
+            "query",
+            "pools",
+            "-u",
+            "chain.db",
+            "-s",
+            "100",
+        ];
+        let Subcommand::Query(options) = parse_args(&command().get_matches_from(args)).unwrap()
+        else {
+            panic!("expected the query subcommand");
+        };
+        assert_eq!(options.query, query::Query::Pools);
+        assert_eq!(options.db_url, "chain.db");
+        assert_eq!(
+            options.range,
+            BlockHeightRange {
+                start: 100,
+                end: None
+            }
+        );
+
+        let args = ["bitcoin-blockparser", "query", "pools"];
+        assert!(command().try_get_matches_from(args).is_err());
 
-        let args = ["bitcoin-blockparser", "--verify"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
-        assert!(options.verify);
+        let args = ["bitcoin-blockparser", "query", "foo", "-u", "chain.db"];
+        assert!(command().try_get_matches_from(args).is_err());
     }
 
     #[test]
     fn test_args_blockchain_dir() {
-        let args = ["bitcoin-blockparser"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse"];
+        let options = parse(&args).unwrap();
         assert_eq!(
             options.blockchain_dir,
             dirs::home_dir()
@@ -172,19 +639,19 @@ mod tests {
                 .join(std::path::Path::new(".bitcoin").join("blocks"))
         );
 
-        let args = ["bitcoin-blockparser", "-d", "foo"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse", "-d", "foo"];
+        let options = parse(&args).unwrap();
         assert_eq!(options.blockchain_dir.to_str().unwrap(), "foo");
 
-        let args = ["bitcoin-blockparser", "--blockchain-dir", "foo"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse", "--blockchain-dir", "foo"];
+        let options = parse(&args).unwrap();
         assert_eq!(options.blockchain_dir.to_str().unwrap(), "foo");
     }
 
     #[test]
     fn test_args_start() {
-        let args = ["bitcoin-blockparser"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse"];
+        let options = parse(&args).unwrap();
         assert_eq!(
             options.range,
             BlockHeightRange {
@@ -193,8 +660,8 @@ mod tests {
             }
         );
 
-        let args = ["bitcoin-blockparser", "-s", "10"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse", "-s", "10"];
+        let options = parse(&args).unwrap();
         assert_eq!(
             options.range,
             BlockHeightRange {
@@ -203,8 +670,8 @@ mod tests {
             }
         );
 
-        let args = ["bitcoin-blockparser", "--start", "10"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse", "--start", "10"];
+        let options = parse(&args).unwrap();
         assert_eq!(
             options.range,
             BlockHeightRange {
@@ -216,8 +683,8 @@ mod tests {
 
     #[test]
     fn test_args_end() {
-        let args = ["bitcoin-blockparser", "-e", "10"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse", "-e", "10"];
+        let options = parse(&args).unwrap();
         assert_eq!(
             options.range,
             BlockHeightRange {
@@ -226,8 +693,8 @@ mod tests {
             }
         );
 
-        let args = ["bitcoin-blockparser", "--end", "10"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse", "--end", "10"];
+        let options = parse(&args).unwrap();
         assert_eq!(
             options.range,
             BlockHeightRange {
@@ -239,8 +706,8 @@ mod tests {
 
     #[test]
     fn test_args_start_and_end() {
-        let args = ["bitcoin-blockparser", "-s", "1", "-e", "2"];
-        let options = parse_args(&command().get_matches_from(args)).unwrap();
+        let args = ["bitcoin-blockparser", "parse", "-s", "1", "-e", "2"];
+        let options = parse(&args).unwrap();
         assert_eq!(
             options.range,
             BlockHeightRange {
@@ -249,7 +716,7 @@ mod tests {
             }
         );
 
-        let args = ["bitcoin-blockparser", "-s", "2", "-e", "1"];
-        assert!(parse_args(&command().get_matches_from(args)).is_err());
+        let args = ["bitcoin-blockparser", "parse", "-s", "2", "-e", "1"];
+        assert!(parse(&args).is_err());
     }
 }
@@ -1,5 +1,7 @@
 use bitcoin_blockparser::parser::chain::ChainStorage;
+use bitcoin_blockparser::parser::info::{self, DatadirInfo};
 use bitcoin_blockparser::parser::BlockchainParser;
+use bitcoin_blockparser::{query, ParserOptions, Subcommand, Verify};
 use tracing_subscriber::prelude::*;
 
 fn main() {
@@ -11,21 +13,75 @@ fn main() {
         )
         .with(tracing_subscriber::fmt::layer())
         .init();
-    let options =
+    let subcommand =
         match bitcoin_blockparser::parse_args(&bitcoin_blockparser::command().get_matches()) {
-            Ok(o) => o,
+            Ok(s) => s,
             Err(desc) => {
                 tracing::error!(target: "main", "{}", desc);
                 std::process::exit(1);
             }
         };
+    match subcommand {
+        Subcommand::Parse(options) => parse(&options),
+        Subcommand::Query(options) => {
+            if let Err(e) = query::run(&options, &mut std::io::stdout().lock()) {
+                tracing::error!(target: "main", "{}", e);
+                std::process::exit(1);
+            }
+        }
+        Subcommand::Info(options) => match DatadirInfo::new(&options) {
+            Ok(info) => {
+                println!("{info}");
+                if info.xor_obfuscated {
+                    tracing::warn!(target: "main", "Block files are XOR-obfuscated with the key in xor.dat and can't be parsed");
+                }
+                if info.flags.pruned {
+                    tracing::warn!(target: "main", "Block files have been pruned, the blocks below the prune height are missing");
+                }
+            }
+            Err(e) => {
+                tracing::error!(
+                    target: "main",
+                    "Cannot load blockchain data from: '{}'. {}",
+                    options.blockchain_dir.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+        },
+        Subcommand::Tips(options) => {
+            let written = info::chain_tips(&options).and_then(|tips| {
+                Ok(info::write_chain_tips(
+                    &tips,
+                    &mut std::io::stdout().lock(),
+                )?)
+            });
+            if let Err(e) = written {
+                tracing::error!(
+                    target: "main",
+                    "Cannot load blockchain data from: '{}'. {}",
+                    options.blockchain_dir.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+}
 
+fn parse(options: &ParserOptions) {
     tracing::info!(target: "main", "Starting bitcoin-blockparser v{} ...", env!("CARGO_PKG_VERSION"));
-    if options.verify {
-        tracing::info!(target: "main", "Configured to verify merkle roots and block hashes");
+    if options.verify != Verify::Off {
+        tracing::info!(target: "main", "Configured to verify block hashes, merkle roots, witness commitments and block limits");
+    }
+    if options.verify == Verify::Report {
+        tracing::info!(target: "main", "Configured to report all verification issues and continue");
+    }
+    if options.verify_scripts {
+        tracing::info!(target: "main", "Configured to verify all input scripts");
     }
 
-    let chain_storage = match ChainStorage::new(&options) {
+    let chain_storage = match ChainStorage::new(options) {
         Ok(storage) => storage,
         Err(e) => {
             tracing::error!(
@@ -38,9 +94,31 @@ fn main() {
         }
     };
 
-    let mut parser = BlockchainParser::new(&options, chain_storage);
+    let mut parser = match BlockchainParser::new(options, chain_storage) {
+        Ok(parser) => parser,
+        Err(e) => {
+            tracing::error!(target: "main", "Cannot open database. {:?}", e);
+            std::process::exit(1);
+        }
+    };
+    let interrupted = parser.interrupt_handle();
+    if let Err(e) = ctrlc::set_handler(move || {
+        tracing::info!(target: "main", "Received termination signal, finishing current block ...");
+        interrupted.store(true, std::sync::atomic::Ordering::SeqCst);
+    }) {
+        tracing::warn!(target: "main", "Unable to install signal handler: {}", e);
+    }
     if let Err(e) = parser.start() {
-        tracing::error!("error: {e:?}");
+        tracing::error!(target: "main", "{:?}", e);
+        std::process::exit(1);
+    }
+    if parser.verification_failed() {
+        if options.write_db {
+            tracing::error!(target: "main", "Verification failed, see the verification_issues table for details.");
+        } else {
+            tracing::error!(target: "main", "Verification failed, see the issues logged above.");
+        }
+        std::process::exit(1);
     }
     tracing::info!(target: "main", "Fin.");
 }
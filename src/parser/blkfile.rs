@@ -1,11 +1,12 @@
 use std::collections::HashMap;
 use std::fs::{DirEntry, File};
-use std::io::{Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 
 use anyhow::Context;
 
 use crate::parser::reader::BlockchainRead;
+use crate::parser::undo::BlockUndo;
 
 #[derive(Debug)]
 pub struct BlkFile {
@@ -31,30 +32,82 @@ impl BlkFile {
         Ok(self.reader.as_mut().unwrap())
     }
 
+    /// Returns the reader positioned at `offset`. Sequential reads don't seek,
+    /// which would discard the buffer.
+    fn open_at(&mut self, offset: u64) -> anyhow::Result<&mut std::io::BufReader<File>> {
+        let reader = self.open()?;
+        if reader.stream_position()? != offset {
+            reader.seek(SeekFrom::Start(offset))?;
+        }
+        Ok(reader)
+    }
+
     pub fn close(&mut self) {
-        tracing::debug!(target: "blkfile", "Closing {} ...", &self.path.display());
         if self.reader.is_some() {
+            tracing::debug!(target: "blkfile", "Closing {} ...", &self.path.display());
             self.reader = None;
         }
     }
 
+    #[cfg(test)]
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        self.reader.is_some()
+    }
+
     pub fn read_header(
         &mut self,
         offset: u64,
     ) -> anyhow::Result<bitcoin::blockdata::block::Header> {
-        let reader = self.open()?;
-        reader.seek(SeekFrom::Start(offset))?;
+        let reader = self.open_at(offset)?;
         reader.read_header()
     }
 
     pub fn read_block(&mut self, offset: u64) -> anyhow::Result<bitcoin::Block> {
-        let reader = self.open()?;
-        reader.seek(SeekFrom::Start(offset))?;
+        let reader = self.open_at(offset)?;
         reader.read_block()
     }
 
+    /// Reads the magic bytes and the size preceding the block at `offset`.
+    pub fn read_framing(&mut self, offset: u64) -> anyhow::Result<(u32, u32)> {
+        let reader = self.open_at(offset.checked_sub(8).context("invalid offset")?)?;
+        let mut buf = [0u8; 8];
+        reader.read_exact(&mut buf)?;
+        Ok((
+            u32::from_le_bytes(buf[0..4].try_into()?),
+            u32::from_le_bytes(buf[4..8].try_into()?),
+        ))
+    }
+
+    pub fn read_undo(&mut self, offset: u64) -> anyhow::Result<BlockUndo> {
+        let reader = self.open_at(offset)?;
+        reader.read_block_undo()
+    }
+
     pub fn from_path(path: &Path) -> anyhow::Result<HashMap<u64, BlkFile>> {
         tracing::info!(target: "blkfile", "Reading files from {} ...", path.display());
+        let collected = BlkFile::collect(path, "blk")?;
+        tracing::trace!(target: "blkfile", "Found {} blk files", collected.len());
+        if collected.is_empty() {
+            Err(anyhow::anyhow!("No blk files found!"))
+        } else {
+            Ok(collected)
+        }
+    }
+
+    /// Collects the rev*.dat files containing the undo data of the blocks.
+    pub fn undo_from_path(path: &Path) -> anyhow::Result<HashMap<u64, BlkFile>> {
+        tracing::info!(target: "blkfile", "Reading undo files from {} ...", path.display());
+        let collected = BlkFile::collect(path, "rev")?;
+        tracing::trace!(target: "blkfile", "Found {} rev files", collected.len());
+        if collected.is_empty() {
+            Err(anyhow::anyhow!("No rev files found!"))
+        } else {
+            Ok(collected)
+        }
+    }
+
+    fn collect(path: &Path, prefix: &str) -> anyhow::Result<HashMap<u64, BlkFile>> {
         let mut collected = HashMap::with_capacity(4000);
 
         for entry in std::fs::read_dir(path)? {
@@ -72,24 +125,18 @@ impl BlkFile {
                             .to_str()
                             .context("invalid path")?,
                     );
-                    if let Some(index) = BlkFile::parse_blk_index(&file_name, "blk", ".dat") {
+                    if let Some(index) = BlkFile::parse_blk_index(&file_name, prefix, ".dat") {
                         let size = std::fs::metadata(path.as_path())?.len();
                         tracing::trace!(target: "blkfile", "Adding {} ... (index: {}, size: {})", path.display(), index, size);
                         collected.insert(index, BlkFile::new(path, size));
                     }
                 }
                 Err(msg) => {
-                    tracing::warn!(target: "blkfile", "Unable to read blk file!: {}", msg);
+                    tracing::warn!(target: "blkfile", "Unable to read {} file!: {}", prefix, msg);
                 }
             }
         }
-
-        tracing::trace!(target: "blkfile", "Found {} blk files", collected.len());
-        if collected.is_empty() {
-            Err(anyhow::anyhow!("No blk files found!"))
-        } else {
-            Ok(collected)
-        }
+        Ok(collected)
     }
 
     fn resolve_path(entry: &DirEntry) -> std::io::Result<PathBuf> {
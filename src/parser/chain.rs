@@ -1,80 +1,355 @@
+use anyhow::Context;
+
 use crate::parser::blkfile::BlkFile;
 use crate::parser::index::ChainIndex;
+use crate::parser::scheduler::{BlockRead, FileCache, ReadScheduler, MAX_OPEN_FILES, READ_WINDOW};
+use crate::parser::script;
 use crate::parser::types::CoinType;
-use crate::ParserOptions;
+use crate::parser::undo::BlockUndo;
+use crate::parser::verify::{self, IssueKind, VerificationIssue};
+use crate::{ParserOptions, Verify};
 
 pub struct ChainStorage {
     chain_index: ChainIndex,
-    blk_files: std::collections::HashMap<u64, BlkFile>,
+    blk_files: FileCache,
+    rev_files: FileCache,
+    scheduler: ReadScheduler,
     coin: CoinType,
-    verify: bool,
+    verify: Verify,
+    verify_scripts: bool,
+    issues: Vec<VerificationIssue>,
+    summary: std::collections::BTreeMap<IssueKind, u64>,
 }
 
 impl ChainStorage {
     pub fn new(options: &ParserOptions) -> anyhow::Result<Self> {
+        if options.verify_scripts && !cfg!(feature = "bitcoinconsensus") {
+            anyhow::bail!(
+                "script verification requires building with the `bitcoinconsensus` feature"
+            );
+        }
+        let rev_files = match BlkFile::undo_from_path(options.blockchain_dir.as_path()) {
+            Ok(rev_files) => rev_files,
+            Err(e) if options.verify_scripts => return Err(e),
+            Err(e) => {
+                tracing::warn!(target: "blkfile", "{}, fee statistics won't be available", e);
+                std::collections::HashMap::new()
+            }
+        };
         Ok(Self {
             chain_index: ChainIndex::new(options)?,
-            blk_files: BlkFile::from_path(options.blockchain_dir.as_path())?,
+            blk_files: FileCache::new(
+                BlkFile::from_path(options.blockchain_dir.as_path())?,
+                MAX_OPEN_FILES,
+            ),
+            rev_files: FileCache::new(rev_files, MAX_OPEN_FILES),
+            scheduler: ReadScheduler::new(READ_WINDOW),
             coin: options.coin.clone(),
             verify: options.verify,
+            verify_scripts: options.verify_scripts,
+            issues: Vec::new(),
+            summary: std::collections::BTreeMap::new(),
         })
     }
 
+    /// Returns the header at `height`, taken from the read-ahead buffer if the block has
+    /// already been read, else read on its own.
     #[must_use]
     pub fn get_header(&mut self, height: u64) -> Option<bitcoin::blockdata::block::Header> {
         let block_meta = self.chain_index.get(height)?;
-        let blk_file = self.blk_files.get_mut(&block_meta.blk_index)?;
-        let header = blk_file.read_header(block_meta.data_offset).ok()?;
+        if let Some(BlockRead {
+            block: Ok(block), ..
+        }) = self.scheduler.buffered(height)
+        {
+            return Some(block.header);
+        }
+        let blk_file = self.blk_files.get_mut(block_meta.blk_index)?;
+        blk_file.read_header(block_meta.data_offset).ok()
+    }
+
+    /// Returns the block at `height`, `None` if it isn't in the index. In report mode, a block
+    /// which can't be read is recorded as an issue and `None` is returned, otherwise it's an error
+    /// as are failed verifications.
+    pub fn get_block(&mut self, height: u64) -> anyhow::Result<Option<bitcoin::Block>> {
+        let Some(block_meta) = self.chain_index.get(height) else {
+            return Ok(None);
+        };
+        let block_hash = bitcoin::BlockHash::from_raw_hash(block_meta.block_hash);
+        let read = self
+            .scheduler
+            .take(height, &self.chain_index, &mut self.blk_files)
+            .with_context(|| format!("blk file {} is missing", block_meta.blk_index))
+            .and_then(|BlockRead { framing, block }| Ok((framing, block?)));
+
+        let (framing, block) = match read {
+            Ok(read) => read,
+            Err(e) if self.verify == Verify::Report => {
+                self.report(vec![VerificationIssue::new(
+                    height,
+                    Some(block_hash),
+                    IssueKind::Framing,
+                    format!("unable to read block {block_hash}: {e}"),
+                )]);
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(e.context(format!(
+                    "unable to read block {block_hash} at height {height}"
+                )))
+            }
+        };
+
+        if self.verify != Verify::Off {
+            let issues = self.verify(&block, height, framing);
+            self.report_or_fail(issues)?;
+        }
+
+        Ok(Some(block))
+    }
 
-        if height == self.chain_index.max_height_by_blk(block_meta.blk_index) {
-            blk_file.close();
+    /// Executes the input scripts of the block at `height` against the spent outputs in `undo`
+    /// if `--verify-scripts` is given. Failures are reported like those of [`Self::get_block`].
+    pub fn verify_scripts(
+        &mut self,
+        block: &bitcoin::Block,
+        height: u64,
+        undo: Option<&BlockUndo>,
+    ) -> anyhow::Result<()> {
+        if !self.verify_scripts || height == 0 {
+            return Ok(());
         }
-        Some(header)
+        let block_hash = block.block_hash();
+        let issue =
+            |message| VerificationIssue::new(height, Some(block_hash), IssueKind::Script, message);
+
+        let issues = match undo {
+            None => vec![issue(format!(
+                "unable to read undo data of block {block_hash}"
+            ))],
+            Some(undo) => {
+                let flags = script::script_flags(&block_hash, height, &self.coin);
+                match script::verify_scripts(block, undo, flags) {
+                    Ok(failures) => failures
+                        .iter()
+                        .map(|f| issue(format!("script verification failed for input {f}")))
+                        .collect(),
+                    Err(e) => vec![issue(e.to_string())],
+                }
+            }
+        };
+        self.report_or_fail(issues)
     }
 
+    /// Returns the outputs spent by the block at `height`, read from the rev*.dat files.
     #[must_use]
-    pub fn get_block(&mut self, height: u64) -> Option<bitcoin::Block> {
+    pub fn get_block_undo(&mut self, height: u64) -> Option<BlockUndo> {
         let block_meta = self.chain_index.get(height)?;
-        let blk_file = self.blk_files.get_mut(&block_meta.blk_index)?;
-        let block = blk_file.read_block(block_meta.data_offset).ok()?;
+        let rev_file = self.rev_files.get_mut(block_meta.blk_index)?;
+        match rev_file.read_undo(block_meta.undo_offset?) {
+            Ok(undo) => Some(undo),
+            Err(e) => {
+                tracing::warn!(target: "blkfile", "Unable to read undo data of block at height {}: {}", height, e);
+                None
+            }
+        }
+    }
+
+    /// Header and chainwork of the block at `height` as stored in the block index
+    #[must_use]
+    pub fn index_entry(
+        &self,
+        height: u64,
+    ) -> Option<(bitcoin::blockdata::block::Header, bitcoin::Work)> {
+        let block_meta = self.chain_index.get(height)?;
+        Some((block_meta.header, block_meta.chainwork?))
+    }
+
+    /// Height of the best block in the block index, ignoring `--end`
+    #[must_use]
+    pub fn tip_height(&self) -> u64 {
+        self.chain_index.tip_height()
+    }
+
+    /// Hash of the block following the one at `height` in the active chain
+    #[must_use]
+    pub fn next_block_hash(&self, height: u64) -> Option<bitcoin::BlockHash> {
+        self.chain_index
+            .next_hash(height)
+            .map(bitcoin::BlockHash::from_raw_hash)
+    }
+
+    /// Number of blocks in the index which aren't part of the active chain
+    #[must_use]
+    pub fn stale_block_count(&self) -> usize {
+        self.chain_index.stale_blocks().len()
+    }
 
-        if height == self.chain_index.max_height_by_blk(block_meta.blk_index) {
-            blk_file.close();
+    /// Header, height and, if its data is on disk, the block of the `i`-th stale block in
+    /// height order
+    #[must_use]
+    pub fn get_stale_block(
+        &mut self,
+        i: usize,
+    ) -> Option<(
+        bitcoin::blockdata::block::Header,
+        u64,
+        Option<bitcoin::Block>,
+    )> {
+        let block_meta = self.chain_index.stale_blocks().get(i)?;
+        let block = if block_meta.has_data() {
+            self.blk_files
+                .get_mut(block_meta.blk_index)
+                .and_then(|blk_file| blk_file.read_block(block_meta.data_offset).ok())
+        } else {
+            None
+        };
+        Some((block_meta.header, block_meta.height(), block))
+    }
+
+    /// Chain tips of the block index like `getchaintips`, the highest first
+    #[must_use]
+    pub fn chain_tips(&self) -> &[crate::parser::ChainTip] {
+        self.chain_index.chain_tips()
+    }
+
+    /// Drains the issues recorded in report mode since the last call.
+    pub fn take_issues(&mut self) -> Vec<VerificationIssue> {
+        std::mem::take(&mut self.issues)
+    }
+
+    /// Number of issues recorded in report mode by kind
+    #[must_use]
+    pub fn verification_summary(&self) -> &std::collections::BTreeMap<IssueKind, u64> {
+        &self.summary
+    }
+
+    fn report(&mut self, issues: Vec<VerificationIssue>) {
+        for issue in issues {
+            tracing::warn!(target: "verify", "{}", issue);
+            *self.summary.entry(issue.kind).or_default() += 1;
+            self.issues.push(issue);
         }
+    }
 
-        if self.verify {
-            self.verify(&block, height).unwrap();
+    fn report_or_fail(&mut self, issues: Vec<VerificationIssue>) -> anyhow::Result<()> {
+        if self.verify == Verify::Report {
+            self.report(issues);
+            Ok(())
+        } else {
+            Self::fail_on(&issues)
         }
+    }
 
-        Some(block)
+    fn fail_on(issues: &[VerificationIssue]) -> anyhow::Result<()> {
+        if !issues.is_empty() {
+            let issues: Vec<String> = issues.iter().map(|i| format!("  -> {i}")).collect();
+            anyhow::bail!("Verification failed!\n{}\n", issues.join("\n"));
+        }
+        Ok(())
     }
 
-    fn verify(&self, block: &bitcoin::Block, height: u64) -> anyhow::Result<()> {
-        assert!(block.check_merkle_root());
+    fn verify(
+        &self,
+        block: &bitcoin::Block,
+        height: u64,
+        framing: anyhow::Result<(u32, u32)>,
+    ) -> Vec<VerificationIssue> {
+        let block_hash = block.block_hash();
+        let mut issues = Vec::new();
+        let mut issue = |kind, message| {
+            issues.push(VerificationIssue::new(
+                height,
+                Some(block_hash),
+                kind,
+                message,
+            ));
+        };
+
+        match framing {
+            Ok((magic, _)) if magic != self.coin.magic => issue(
+                IssueKind::Framing,
+                format!(
+                    "magic bytes of block {block_hash} don't match!\n  -> expected: {:#010x}\n  -> got: {magic:#010x}",
+                    self.coin.magic
+                ),
+            ),
+            Ok((_, size)) if usize::try_from(size).ok() != Some(block.size()) => issue(
+                IssueKind::Framing,
+                format!(
+                    "size of block {block_hash} doesn't match!\n  -> expected: {size}\n  -> got: {}",
+                    block.size()
+                ),
+            ),
+            Ok(_) => {}
+            Err(e) => issue(
+                IssueKind::Framing,
+                format!("unable to read framing of block {block_hash}: {e}"),
+            ),
+        }
+
+        if let Some(block_meta) = self.chain_index.get(height) {
+            if block_hash.as_raw_hash() != &block_meta.block_hash {
+                issue(
+                    IssueKind::Hash,
+                    format!(
+                        "block hash doesn't match the index!\n  -> expected: {}\n  -> got: {}",
+                        &block_meta.block_hash, &block_hash
+                    ),
+                );
+            }
+            if block_meta.tx_count != block.txdata.len() as u64 {
+                issue(
+                    IssueKind::TxCount,
+                    format!(
+                        "tx_count for block {block_hash} doesn't match the index!\n  -> expected: {}\n  -> got: {}",
+                        block_meta.tx_count,
+                        block.txdata.len()
+                    ),
+                );
+            }
+        }
+
+        if !block.check_merkle_root() {
+            issue(
+                IssueKind::Merkle,
+                format!("merkle root of block {block_hash} doesn't match!"),
+            );
+        }
+        if let Err(e) = verify::check_block(block)
+            .and_then(|()| verify::check_contextual(block, height, &self.coin))
+        {
+            issue(IssueKind::Consensus, e.to_string());
+        }
+
         if height == 0 {
-            if block.header.block_hash().as_raw_hash() != &self.coin.genesis_hash {
-                anyhow::bail!(
-                    "Genesis block hash doesn't match!\n  -> expected: {}\n  -> got: {}\n",
-                    &self.coin.genesis_hash,
-                    &block.header.block_hash(),
+            if block_hash.as_raw_hash() != &self.coin.genesis_hash {
+                issue(
+                    IssueKind::Hash,
+                    format!(
+                        "Genesis block hash doesn't match!\n  -> expected: {}\n  -> got: {}",
+                        &self.coin.genesis_hash, &block_hash,
+                    ),
                 );
             }
         } else {
-            let prev_hash = self
-                .chain_index
-                .get(height - 1)
-                .expect("unable to fetch prev block in chain index")
-                .block_hash;
-            if block.header.prev_blockhash.as_raw_hash() != &prev_hash {
-                anyhow::bail!(
-                    "prev_hash for block {} doesn't match!\n  -> expected: {}\n  -> got: {}\n",
-                    &block.header.block_hash(),
-                    &block.header.prev_blockhash,
-                    &prev_hash
-                );
+            match self.chain_index.get(height - 1) {
+                Some(prev) if block.header.prev_blockhash.as_raw_hash() != &prev.block_hash => {
+                    issue(
+                        IssueKind::Linkage,
+                        format!(
+                            "prev_hash for block {} doesn't match!\n  -> expected: {}\n  -> got: {}",
+                            &block_hash, &prev.block_hash, &block.header.prev_blockhash
+                        ),
+                    );
+                }
+                Some(_) => {}
+                None => issue(
+                    IssueKind::Linkage,
+                    format!("unable to fetch prev block of {block_hash} in chain index"),
+                ),
             }
         }
-        Ok(())
+        issues
     }
 
     pub(crate) fn max_height(&self) -> u64 {
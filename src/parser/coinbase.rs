@@ -0,0 +1,168 @@
+use bitcoin::blockdata::opcodes::all::{OP_PUSHNUM_1, OP_PUSHNUM_16};
+use bitcoin::blockdata::script::Instruction;
+
+use crate::parser::opreturn::OpReturn;
+use crate::parser::verify;
+
+/// Merged mining header of auxiliary proof-of-work chains like Namecoin
+const AUXPOW_MARKER: [u8; 4] = [0xfa, 0xbe, b'm', b'm'];
+
+/// Minimum length of a printable ASCII run to be reported as a tag
+const MIN_TAG_LENGTH: usize = 4;
+
+/// Information extracted from a coinbase transaction to attribute the block to a pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoinbaseInfo {
+    /// Printable ASCII runs of the scriptSig
+    pub tags: Vec<String>,
+    /// Number encoded by the first push of the scriptSig, the height since BIP34
+    pub bip34_height: Option<u64>,
+    /// Length of the push following the height
+    pub extranonce_length: Option<usize>,
+    /// All outputs in output order, also those without an address
+    pub payouts: Vec<Payout>,
+    pub witness_commitment: bool,
+    /// Markers of merged mined chains, `auxpow` and `rsk`
+    pub merge_mining: Vec<&'static str>,
+}
+
+/// An output of a coinbase
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payout {
+    pub value: u64,
+    pub script_pubkey: bitcoin::ScriptBuf,
+    /// Address of the script, `None` for e.g. P2PK, bare multisig and `OP_RETURN` outputs
+    pub address: Option<String>,
+}
+
+impl CoinbaseInfo {
+    /// Returns `None` if the transaction has no inputs.
+    #[must_use]
+    pub fn new(coinbase: &bitcoin::Transaction, network: bitcoin::Network) -> Option<Self> {
+        let script_sig = coinbase.input.first()?.script_sig.as_script();
+        let pushes: Vec<Option<&[u8]>> = script_sig
+            .instructions()
+            .map_while(Result::ok)
+            .take(2)
+            .map(|instruction| match instruction {
+                Instruction::PushBytes(bytes) => Some(bytes.as_bytes()),
+                Instruction::Op(_) => None,
+            })
+            .collect();
+
+        let bip34_height = script_sig
+            .instructions()
+            .next()
+            .and_then(Result::ok)
+            .and_then(|first| match first {
+                Instruction::PushBytes(bytes) => script_num(bytes.as_bytes()),
+                Instruction::Op(op)
+                    if (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(&op.to_u8()) =>
+                {
+                    Some(u64::from(op.to_u8() - OP_PUSHNUM_1.to_u8() + 1))
+                }
+                Instruction::Op(_) => None,
+            });
+
+        let mut merge_mining = Vec::new();
+        if script_sig
+            .as_bytes()
+            .windows(AUXPOW_MARKER.len())
+            .any(|w| w == AUXPOW_MARKER)
+        {
+            merge_mining.push("auxpow");
+        }
+        if coinbase.output.iter().any(|output| {
+            OpReturn::from_script(&output.script_pubkey).is_some_and(|o| o.protocol == Some("rsk"))
+        }) {
+            merge_mining.push("rsk");
+        }
+
+        Some(Self {
+            tags: tags(script_sig),
+            bip34_height,
+            extranonce_length: pushes.get(1).copied().flatten().map(<[u8]>::len),
+            payouts: coinbase
+                .output
+                .iter()
+                .map(|output| Payout {
+                    value: output.value,
+                    script_pubkey: output.script_pubkey.clone(),
+                    address: bitcoin::Address::from_script(&output.script_pubkey, network)
+                        .ok()
+                        .map(|address| address.to_string()),
+                })
+                .collect(),
+            witness_commitment: verify::witness_commitment(coinbase).is_some(),
+            merge_mining,
+        })
+    }
+}
+
+/// Decodes a minimally encoded, non-negative script number of up to 8 bytes.
+fn script_num(bytes: &[u8]) -> Option<u64> {
+    let (last, _) = bytes.split_last()?;
+    if bytes.len() > 8 || last & 0x80 != 0 {
+        return None;
+    }
+    Some(
+        bytes
+            .iter()
+            .rev()
+            .fold(0, |acc, byte| (acc << 8) | u64::from(*byte)),
+    )
+}
+
+/// Printable ASCII runs of the pushed data, or of the raw script if it can't be parsed
+fn tags(script_sig: &bitcoin::Script) -> Vec<String> {
+    let pushes: Result<Vec<&[u8]>, _> = script_sig
+        .instructions()
+        .filter_map(|instruction| match instruction {
+            Ok(Instruction::PushBytes(bytes)) => Some(Ok(bytes.as_bytes())),
+            Ok(Instruction::Op(_)) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect();
+    pushes
+        .unwrap_or_else(|_| vec![script_sig.as_bytes()])
+        .into_iter()
+        .flat_map(|data| data.split(|byte| !(0x20..0x7f).contains(byte)))
+        .filter(|run| run.len() >= MIN_TAG_LENGTH)
+        .map(|run| String::from_utf8_lossy(run).trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_genesis_coinbase() {
+        let genesis = bitcoin::blockdata::constants::genesis_block(
+            bitcoin::network::constants::Network::Bitcoin,
+        );
+        let info = CoinbaseInfo::new(&genesis.txdata[0], bitcoin::Network::Bitcoin).unwrap();
+        assert_eq!(
+            info.tags,
+            vec!["The Times 03/Jan/2009 Chancellor on brink of second bailout for banks"]
+        );
+        // pre BIP34 coinbases start with nBits
+        assert_eq!(info.bip34_height, Some(0x1d00_ffff));
+        assert_eq!(info.extranonce_length, Some(1));
+        assert_eq!(info.payouts.len(), 1);
+        assert_eq!(info.payouts[0].value, 5_000_000_000);
+        assert!(info.payouts[0].script_pubkey.is_p2pk());
+        assert!(info.payouts[0].address.is_none());
+        assert!(!info.witness_commitment);
+        assert!(info.merge_mining.is_empty());
+    }
+
+    #[test]
+    fn test_script_num() {
+        assert_eq!(script_num(&[]), None);
+        assert_eq!(script_num(&[0x1b, 0x79, 0x03]), Some(227_611));
+        assert_eq!(script_num(&[0xff, 0x00]), Some(255));
+        assert_eq!(script_num(&[0x81]), None);
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::Context;
+use bitcoin::blockdata::constants::WITNESS_SCALE_FACTOR;
+
+use crate::parser::undo::BlockUndo;
+
+/// Number of blocks after which the subsidy halves
+const SUBSIDY_HALVING_INTERVAL: u64 = 210_000;
+
+/// Fee statistics of a block, computed like `getblockstats` in Bitcoin Core.
+/// Fees are in satoshis, feerates in sat/vB. The coinbase is excluded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeStats {
+    pub total_fee: u64,
+    pub subsidy: u64,
+    pub avg_fee: u64,
+    pub min_fee: u64,
+    pub max_fee: u64,
+    pub median_fee: u64,
+    /// Total fee divided by the total virtual size
+    pub avg_feerate: u64,
+    pub min_feerate: u64,
+    pub max_feerate: u64,
+    /// Feerates at the 10th, 25th, 50th, 75th and 90th percentile, weighted by transaction weight
+    pub feerate_percentiles: [u64; 5],
+}
+
+impl FeeStats {
+    /// Computes the statistics from the `(fee, weight)` pairs of all non-coinbase transactions.
+    #[must_use]
+    pub fn new(height: u64, txs: &[(u64, u64)]) -> Self {
+        let scale = WITNESS_SCALE_FACTOR as u64;
+        let total_fee: u64 = txs.iter().map(|(fee, _)| fee).sum();
+        let total_weight: u64 = txs.iter().map(|(_, weight)| weight).sum();
+        let feerate = |fee: u64, weight: u64| (fee * scale).checked_div(weight).unwrap_or(0);
+
+        let mut fees: Vec<u64> = txs.iter().map(|(fee, _)| *fee).collect();
+        fees.sort_unstable();
+        let mut feerates: Vec<(u64, u64)> = txs
+            .iter()
+            .map(|&(fee, weight)| (feerate(fee, weight), weight))
+            .collect();
+        feerates.sort_unstable();
+
+        Self {
+            total_fee,
+            subsidy: subsidy(height),
+            avg_fee: total_fee.checked_div(txs.len() as u64).unwrap_or(0),
+            min_fee: fees.first().copied().unwrap_or(0),
+            max_fee: fees.last().copied().unwrap_or(0),
+            median_fee: truncated_median(&fees),
+            avg_feerate: feerate(total_fee, total_weight),
+            min_feerate: feerates.first().map_or(0, |(feerate, _)| *feerate),
+            max_feerate: feerates.last().map_or(0, |(feerate, _)| *feerate),
+            feerate_percentiles: percentiles_by_weight(&feerates, total_weight),
+        }
+    }
+}
+
+/// Computes the fee statistics of `block` from the prevouts in its undo data.
+pub fn fee_stats(
+    block: &bitcoin::Block,
+    undo: &BlockUndo,
+    height: u64,
+) -> anyhow::Result<FeeStats> {
+    if undo.txdata.len() + 1 != block.txdata.len() {
+        anyhow::bail!(
+            "undo data covers {} transactions, block has {}",
+            undo.txdata.len(),
+            block.txdata.len().saturating_sub(1)
+        );
+    }
+    let mut txs = Vec::with_capacity(undo.txdata.len());
+    for (index, tx) in block.txdata.iter().enumerate().skip(1) {
+        let prevouts = undo.prevouts(index).unwrap_or_default();
+        if prevouts.len() != tx.input.len() {
+            anyhow::bail!(
+                "undo data covers {} inputs, transaction {} has {}",
+                prevouts.len(),
+                tx.txid(),
+                tx.input.len()
+            );
+        }
+        let spent: u64 = prevouts.iter().map(|p| p.txout.value).sum();
+        let created: u64 = tx.output.iter().map(|o| o.value).sum();
+        let fee = spent
+            .checked_sub(created)
+            .with_context(|| format!("transaction {} creates more than it spends", tx.txid()))?;
+        txs.push((fee, tx.weight().to_wu()));
+    }
+    Ok(FeeStats::new(height, &txs))
+}
+
+/// Block subsidy at `height`, following `GetBlockSubsidy` in Bitcoin Core
+#[must_use]
+pub fn subsidy(height: u64) -> u64 {
+    let halvings = height / SUBSIDY_HALVING_INTERVAL;
+    if halvings >= 64 {
+        return 0;
+    }
+    (50 * bitcoin::Amount::ONE_BTC.to_sat()) >> halvings
+}
+
+/// Median of sorted values, averaging the two middle values for an even count
+fn truncated_median(sorted: &[u64]) -> u64 {
+    match sorted.len() {
+        0 => 0,
+        n if n % 2 == 0 => sorted[n / 2 - 1].midpoint(sorted[n / 2]),
+        n => sorted[n / 2],
+    }
+}
+
+/// Follows `CalculatePercentilesByWeight` in Bitcoin Core, `sorted` contains `(feerate, weight)`.
+fn percentiles_by_weight(sorted: &[(u64, u64)], total_weight: u64) -> [u64; 5] {
+    let mut result = [0; 5];
+    let Some((last, _)) = sorted.last() else {
+        return result;
+    };
+    let total_weight = total_weight as f64;
+    let thresholds = [
+        total_weight / 10.0,
+        total_weight / 4.0,
+        total_weight / 2.0,
+        total_weight * 3.0 / 4.0,
+        total_weight * 9.0 / 10.0,
+    ];
+
+    let mut next = 0;
+    let mut cumulative_weight = 0;
+    for (feerate, weight) in sorted {
+        cumulative_weight += weight;
+        while next < result.len() && cumulative_weight as f64 >= thresholds[next] {
+            result[next] = *feerate;
+            next += 1;
+        }
+    }
+    for percentile in &mut result[next..] {
+        *percentile = *last;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsidy() {
+        assert_eq!(subsidy(0), 5_000_000_000);
+        assert_eq!(subsidy(209_999), 5_000_000_000);
+        assert_eq!(subsidy(210_000), 2_500_000_000);
+        assert_eq!(subsidy(840_000), 312_500_000);
+        assert_eq!(subsidy(64 * 210_000), 0);
+    }
+
+    #[test]
+    fn test_fee_stats() {
+        let empty = FeeStats::new(0, &[]);
+        assert_eq!(empty.total_fee, 0);
+        assert_eq!(empty.subsidy, 5_000_000_000);
+        assert_eq!(empty.min_feerate, 0);
+        assert_eq!(empty.feerate_percentiles, [0; 5]);
+
+        // feerates of 1, 10 and 100 sat/vB
+        let stats = FeeStats::new(1, &[(1_000, 4_000), (2_000, 800), (40_000, 1_600)]);
+        assert_eq!(stats.total_fee, 43_000);
+        assert_eq!(stats.avg_fee, 14_333);
+        assert_eq!(stats.min_fee, 1_000);
+        assert_eq!(stats.max_fee, 40_000);
+        assert_eq!(stats.median_fee, 2_000);
+        assert_eq!(stats.avg_feerate, 43_000 * 4 / 6_400);
+        assert_eq!(stats.min_feerate, 1);
+        assert_eq!(stats.max_feerate, 100);
+        assert_eq!(stats.feerate_percentiles, [1, 1, 1, 10, 100]);
+    }
+}
@@ -1,56 +1,79 @@
-use std::io::Read;
+use std::collections::{HashMap, HashSet};
 
 use bitcoin::hashes::{sha256d, Hash};
 
 use rusty_leveldb::{LdbIterator, Options, DB};
 
+use crate::parser::reader::BlockchainRead;
 use crate::ParserOptions;
 
+/// Validity levels, stored in the lowest bits of the status
+const BLOCK_VALID_MASK: u64 = 7;
 const BLOCK_VALID_CHAIN: u64 = 4;
+const BLOCK_VALID_SCRIPTS: u64 = 5;
 const BLOCK_HAVE_DATA: u64 = 8;
+const BLOCK_HAVE_UNDO: u64 = 16;
+const BLOCK_FAILED_MASK: u64 = 32 | 64;
 
 pub struct ChainIndex {
     max_height: u64,
-    block_index: std::collections::HashMap<u64, BlockIndexRecord>,
-    max_height_blk_index: std::collections::HashMap<u64, u64>,
+    /// Height of the best known block, which may be beyond the parsed range
+    tip_height: u64,
+    tip_hash: sha256d::Hash,
+    /// Hash of the block following `max_height`
+    next_hash: Option<sha256d::Hash>,
+    block_index: HashMap<u64, BlockIndexRecord>,
+    /// Blocks which aren't part of the active chain, ordered by height
+    stale_blocks: Vec<BlockIndexRecord>,
+    chain_tips: Vec<ChainTip>,
+    flags: IndexFlags,
+    /// Number of block records in the index
+    record_count: usize,
 }
 
 impl ChainIndex {
     pub fn new(options: &ParserOptions) -> anyhow::Result<Self> {
         let path = options.blockchain_dir.join("index");
-        let mut block_index = get_block_index(&path)?;
-        let mut max_height_blk_index = std::collections::HashMap::new();
-
-        for (height, index_record) in &block_index {
-            match max_height_blk_index.get(&index_record.blk_index) {
-                Some(cur_height) if height > cur_height => {
-                    max_height_blk_index.insert(index_record.blk_index, *height);
-                }
-                None => {
-                    max_height_blk_index.insert(index_record.blk_index, *height);
-                }
-                _ => {}
-            }
-        }
+        let contents = IndexContents::read(&path)?;
+        let chain_tips = contents.chain_tips();
+        let flags = contents.flags.clone();
+        let record_count = contents.records.len();
+        let (mut block_index, stale_blocks) = contents.into_active_chain();
+        compute_chainwork(&mut block_index);
 
         let min_height = options.range.start;
-        let max_known_height = *block_index.keys().max().unwrap();
+        let Some(max_known_height) = block_index.keys().max().copied() else {
+            anyhow::bail!("block index at {} has no active chain", path.display());
+        };
+        let tip_hash = block_index[&max_known_height].block_hash;
         let max_height = match options.range.end {
             Some(height) if height < max_known_height => height,
             Some(_) | None => max_known_height,
         };
+        let next_hash = block_index
+            .get(&(max_height + 1))
+            .map(|record| record.block_hash);
 
         if !options.range.is_default() {
             tracing::info!(target: "index", "Trimming block index from height {} to {} ...", min_height, max_height);
+            // keep the blocks preceding the range, the hashrate and median time are computed over them
+            let max_window = options.hashrate_windows.iter().max().copied().unwrap_or(0);
+            let preceding = max_window.max(crate::parser::pow::MEDIAN_TIME_SPAN - 1);
             block_index.retain(|height, _| {
-                *height >= min_height.saturating_sub(1) && *height <= max_height
+                *height >= min_height.saturating_sub(1 + preceding) && *height <= max_height
             });
         }
 
         Ok(Self {
             max_height,
+            tip_height: max_known_height,
+            tip_hash,
+            next_hash,
             block_index,
-            max_height_blk_index,
+            stale_blocks,
+            chain_tips,
+            flags,
+            record_count,
         })
     }
 
@@ -62,8 +85,39 @@ impl ChainIndex {
         self.max_height
     }
 
-    pub fn max_height_by_blk(&self, blk_index: u64) -> u64 {
-        *self.max_height_blk_index.get(&blk_index).unwrap()
+    pub fn tip_height(&self) -> u64 {
+        self.tip_height
+    }
+
+    pub fn tip_hash(&self) -> bitcoin::BlockHash {
+        bitcoin::BlockHash::from_raw_hash(self.tip_hash)
+    }
+
+    /// Hash of the block following the one at `height`, also beyond the parsed range
+    pub fn next_hash(&self, height: u64) -> Option<sha256d::Hash> {
+        if height == self.max_height {
+            self.next_hash
+        } else {
+            self.get(height + 1).map(|record| record.block_hash)
+        }
+    }
+
+    /// Records that aren't part of the active chain in height order, including headers-only
+    /// records whose data was never downloaded or has been pruned
+    pub fn stale_blocks(&self) -> &[BlockIndexRecord] {
+        &self.stale_blocks
+    }
+
+    pub fn chain_tips(&self) -> &[ChainTip] {
+        &self.chain_tips
+    }
+
+    pub fn flags(&self) -> &IndexFlags {
+        &self.flags
+    }
+
+    pub fn record_count(&self) -> usize {
+        self.record_count
     }
 }
 
@@ -71,23 +125,55 @@ pub struct BlockIndexRecord {
     pub block_hash: sha256d::Hash,
     pub blk_index: u64,
     pub data_offset: u64,
+    pub undo_offset: Option<u64>,
+    pub header: bitcoin::blockdata::block::Header,
+    /// Cumulative work up to and including this block, `None` if a preceding block is missing
+    pub chainwork: Option<bitcoin::Work>,
     version: u64,
     height: u64,
     status: u64,
-    tx_count: u64,
+    pub tx_count: u64,
 }
 
 impl BlockIndexRecord {
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// Whether the block is stored in a blk file
+    pub fn has_data(&self) -> bool {
+        self.status & BLOCK_HAVE_DATA > 0
+    }
+
+    /// Whether the block has been validated up to `level` and not been marked as failed
+    fn is_valid(&self, level: u64) -> bool {
+        self.status & BLOCK_FAILED_MASK == 0 && self.status & BLOCK_VALID_MASK >= level
+    }
+
     fn from(key: &[u8], values: &[u8]) -> anyhow::Result<Self> {
         let mut reader = std::io::Cursor::new(values);
 
         let block_hash: [u8; 32] = key.try_into().expect("leveldb: malformed blockhash");
-        let version = read_varint(&mut reader)?;
-        let height = read_varint(&mut reader)?;
-        let status = read_varint(&mut reader)?;
-        let tx_count = read_varint(&mut reader)?;
-        let blk_index = read_varint(&mut reader)?;
-        let data_offset = read_varint(&mut reader)?;
+        let version = reader.read_varint()?;
+        let height = reader.read_varint()?;
+        let status = reader.read_varint()?;
+        let tx_count = reader.read_varint()?;
+        let blk_index = if status & (BLOCK_HAVE_DATA | BLOCK_HAVE_UNDO) > 0 {
+            reader.read_varint()?
+        } else {
+            0
+        };
+        let data_offset = if status & BLOCK_HAVE_DATA > 0 {
+            reader.read_varint()?
+        } else {
+            0
+        };
+        let undo_offset = if status & BLOCK_HAVE_UNDO > 0 {
+            Some(reader.read_varint()?)
+        } else {
+            None
+        };
+        let header = reader.read_header()?;
 
         Ok(BlockIndexRecord {
             block_hash: sha256d::Hash::from_byte_array(block_hash),
@@ -97,6 +183,9 @@ impl BlockIndexRecord {
             tx_count,
             blk_index,
             data_offset,
+            undo_offset,
+            header,
+            chainwork: None,
         })
     }
 }
@@ -111,53 +200,319 @@ impl std::fmt::Debug for BlockIndexRecord {
             .field("n_tx", &self.tx_count)
             .field("n_file", &self.blk_index)
             .field("n_data_pos", &self.data_offset)
+            .field("n_undo_pos", &self.undo_offset)
+            .field("header", &self.header)
+            .field("chainwork", &self.chainwork)
             .finish()
     }
 }
 
-pub fn get_block_index(
-    path: &std::path::Path,
-) -> anyhow::Result<std::collections::HashMap<u64, BlockIndexRecord>> {
-    tracing::info!(target: "index", "Reading index from {} ...", path.display());
-
-    let mut block_index = std::collections::HashMap::with_capacity(1_000_000);
-    let mut db_iter = DB::open(path, Options::default())?.new_iter()?;
-    let (mut key, mut value) = (vec![], vec![]);
-
-    while db_iter.advance() {
-        db_iter.current(&mut key, &mut value);
-        if is_block_index_record(&key) {
-            let record = BlockIndexRecord::from(&key[1..], &value)?;
-            if record.status & (BLOCK_VALID_CHAIN | BLOCK_HAVE_DATA) > 0 {
-                block_index.insert(record.height, record);
+/// Flags stored in the block index next to the block records
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IndexFlags {
+    /// Set by Bitcoin Core before v0.17, newer versions keep the txindex in `indexes/txindex`
+    pub txindex: bool,
+    /// Block files have been deleted by `-prune`
+    pub pruned: bool,
+    /// A reindex was started and hasn't completed
+    pub reindexing: bool,
+}
+
+/// Status of a chain tip as reported by `getchaintips`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipStatus {
+    /// Tip of the active chain
+    Active,
+    /// Fully validated branch which isn't part of the active chain
+    ValidFork,
+    /// All blocks of the branch are on disk, but they haven't been fully validated
+    ValidHeaders,
+    /// Some blocks of the branch have never been downloaded
+    HeadersOnly,
+    /// The branch contains an invalid block
+    Invalid,
+}
+
+impl TipStatus {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::ValidFork => "valid-fork",
+            Self::ValidHeaders => "valid-headers",
+            Self::HeadersOnly => "headers-only",
+            Self::Invalid => "invalid",
+        }
+    }
+}
+
+impl std::fmt::Display for TipStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A block of the index without successors, or the tip of the active chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainTip {
+    pub height: u64,
+    pub hash: bitcoin::BlockHash,
+    /// Number of blocks since the branch forked off the active chain, 0 for the active tip
+    pub branch_len: u64,
+    pub status: TipStatus,
+}
+
+/// All block records of the index including stale and headers-only ones, and its flags
+struct IndexContents {
+    records: Vec<BlockIndexRecord>,
+    flags: IndexFlags,
+}
+
+impl IndexContents {
+    fn read(path: &std::path::Path) -> anyhow::Result<Self> {
+        tracing::info!(target: "index", "Reading index from {} ...", path.display());
+        let mut records = Vec::with_capacity(1_000_000);
+        let mut flags = IndexFlags::default();
+        let mut db_iter = DB::open(path, Options::default())?.new_iter()?;
+        let (mut key, mut value) = (vec![], vec![]);
+        while db_iter.advance() {
+            db_iter.current(&mut key, &mut value);
+            match key.first() {
+                Some(b'b') => records.push(BlockIndexRecord::from(&key[1..], &value)?),
+                // the name is serialized with a length prefix
+                Some(b'F') => match key.get(2..) {
+                    Some(b"txindex") => flags.txindex = value == b"1",
+                    Some(b"prunedblockfiles") => flags.pruned = value == b"1",
+                    _ => {}
+                },
+                Some(b'R') => flags.reindexing = value == b"1",
+                _ => {}
             }
         }
+        records.sort_by_key(|record| record.height);
+        Ok(Self { records, flags })
+    }
+
+    fn by_hash(&self) -> HashMap<sha256d::Hash, &BlockIndexRecord> {
+        self.records
+            .iter()
+            .map(|record| (record.block_hash, record))
+            .collect()
+    }
+
+    /// The fully validated block with the most work. Of several blocks with the same work, the
+    /// node keeps the one it received first, which the index doesn't tell.
+    fn active_tip(
+        &self,
+        by_hash: &HashMap<sha256d::Hash, &BlockIndexRecord>,
+    ) -> Option<&BlockIndexRecord> {
+        let mut work = HashMap::with_capacity(self.records.len());
+        // the records are sorted by height, so the predecessors come first
+        for record in &self.records {
+            let prev = by_hash
+                .get(&record.header.prev_blockhash.to_raw_hash())
+                .and_then(|prev| work.get(&prev.block_hash))
+                .copied();
+            let chainwork = match prev {
+                Some(prev) => prev + record.header.work(),
+                None => record.header.work(),
+            };
+            work.insert(record.block_hash, chainwork);
+        }
+        self.records
+            .iter()
+            .filter(|record| record.has_data() && record.is_valid(BLOCK_VALID_CHAIN))
+            .max_by_key(|record| work[&record.block_hash])
+    }
+
+    fn active_chain(&self) -> HashSet<sha256d::Hash> {
+        let by_hash = self.by_hash();
+        self.active_tip(&by_hash)
+            .map(|tip| {
+                ancestors(&by_hash, tip)
+                    .map(|record| record.block_hash)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Tips of all branches in the index like `getchaintips`, the highest first
+    fn chain_tips(&self) -> Vec<ChainTip> {
+        let by_hash = self.by_hash();
+        let active_tip = self.active_tip(&by_hash).map(|tip| tip.block_hash);
+        let chain: HashSet<_> = active_tip
+            .and_then(|hash| by_hash.get(&hash))
+            .map(|tip| {
+                ancestors(&by_hash, tip)
+                    .map(|record| record.block_hash)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let parents: HashSet<_> = self
+            .records
+            .iter()
+            .map(|record| record.header.prev_blockhash.to_raw_hash())
+            .collect();
+
+        let mut tips: Vec<ChainTip> = self
+            .records
+            .iter()
+            .filter(|record| {
+                !parents.contains(&record.block_hash) || Some(record.block_hash) == active_tip
+            })
+            .map(|tip| {
+                let branch: Vec<_> = ancestors(&by_hash, tip)
+                    .take_while(|record| !chain.contains(&record.block_hash))
+                    .collect();
+                let status = if Some(tip.block_hash) == active_tip {
+                    TipStatus::Active
+                } else if tip.status & BLOCK_FAILED_MASK > 0 {
+                    TipStatus::Invalid
+                } else if branch.iter().any(|record| !record.has_data()) {
+                    TipStatus::HeadersOnly
+                } else if tip.is_valid(BLOCK_VALID_SCRIPTS) {
+                    TipStatus::ValidFork
+                } else {
+                    TipStatus::ValidHeaders
+                };
+                ChainTip {
+                    height: tip.height,
+                    hash: bitcoin::BlockHash::from_raw_hash(tip.block_hash),
+                    branch_len: branch.len() as u64,
+                    status,
+                }
+            })
+            .collect();
+        tips.sort_by_key(|tip| std::cmp::Reverse(tip.height));
+        tips
+    }
+
+    /// Splits the records into the active chain by height and the stale blocks
+    fn into_active_chain(self) -> (HashMap<u64, BlockIndexRecord>, Vec<BlockIndexRecord>) {
+        let chain = self.active_chain();
+        let (active, stale): (Vec<_>, Vec<_>) = self
+            .records
+            .into_iter()
+            .partition(|record| chain.contains(&record.block_hash));
+        tracing::info!(target: "index", "Got longest chain with {} blocks and {} stale blocks ...", active.len(), stale.len());
+        let block_index = active
+            .into_iter()
+            .map(|record| (record.height, record))
+            .collect();
+        (block_index, stale)
     }
-    tracing::info!(target: "index", "Got longest chain with {} blocks ...", block_index.len());
-    Ok(block_index)
 }
 
-#[inline]
-fn is_block_index_record(data: &[u8]) -> bool {
-    *data.first().unwrap() == b'b'
+/// The block and its predecessors back to the first one missing from the index
+fn ancestors<'a>(
+    by_hash: &'a HashMap<sha256d::Hash, &'a BlockIndexRecord>,
+    record: &'a BlockIndexRecord,
+) -> impl Iterator<Item = &'a BlockIndexRecord> {
+    std::iter::successors(Some(record), |record| {
+        by_hash
+            .get(&record.header.prev_blockhash.to_raw_hash())
+            .copied()
+    })
 }
 
-/// TODO: this is a wonky 1:1 translation from https://github.com/bitcoin/bitcoin
-/// It is NOT the same as CompactSize.
-fn read_varint(reader: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<u64> {
-    let mut n = 0;
-    loop {
-        let mut buf = [0; 1];
-        reader.read_exact(&mut buf)?;
-        let ch_data = buf[0];
-        assert!(n <= u64::MAX >> 7, "size too large");
-        n = (n << 7) | u64::from(ch_data & 0x7F);
-        if ch_data & 0x80 > 0 {
-            assert!(n != u64::MAX, "size too large");
-            n += 1;
-        } else {
+fn compute_chainwork(block_index: &mut HashMap<u64, BlockIndexRecord>) {
+    let mut prev: Option<bitcoin::Work> = None;
+    for height in 0.. {
+        let Some(record) = block_index.get_mut(&height) else {
             break;
+        };
+        let chainwork = match prev {
+            Some(prev) => prev + record.header.work(),
+            None => record.header.work(),
+        };
+        record.chainwork = Some(chainwork);
+        prev = Some(chainwork);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: u64 = BLOCK_VALID_SCRIPTS | BLOCK_HAVE_DATA;
+    const VALID_TREE: u64 = 2;
+    const VALID_TRANSACTIONS: u64 = 3;
+
+    fn record(
+        height: u64,
+        prev: Option<&BlockIndexRecord>,
+        status: u64,
+        nonce: u32,
+    ) -> BlockIndexRecord {
+        let header = bitcoin::blockdata::block::Header {
+            version: bitcoin::blockdata::block::Version::ONE,
+            prev_blockhash: prev.map_or_else(bitcoin::BlockHash::all_zeros, |prev| {
+                bitcoin::BlockHash::from_raw_hash(prev.block_hash)
+            }),
+            merkle_root: bitcoin::hash_types::TxMerkleNode::all_zeros(),
+            time: 1_231_006_505,
+            bits: bitcoin::CompactTarget::from_consensus(0x207f_ffff),
+            nonce,
+        };
+        BlockIndexRecord {
+            block_hash: header.block_hash().to_raw_hash(),
+            blk_index: 0,
+            data_offset: 0,
+            undo_offset: None,
+            header,
+            chainwork: None,
+            version: 0,
+            height,
+            status,
+            tx_count: 1,
         }
     }
-    Ok(n)
+
+    #[test]
+    fn test_chain_tips() {
+        // a0 - a1 - a2 - a3 - a4   active chain up to a3, header of a4 only
+        //        |    \- d3        header only
+        //        |- b2             fully validated fork
+        //        |- c2             downloaded but not validated
+        //        \- e2 - e3        e3 is invalid
+        let a0 = record(0, None, VALID, 0);
+        let a1 = record(1, Some(&a0), VALID, 1);
+        let a2 = record(2, Some(&a1), VALID, 2);
+        let b2 = record(2, Some(&a1), VALID, 3);
+        let c2 = record(2, Some(&a1), VALID_TRANSACTIONS | BLOCK_HAVE_DATA, 4);
+        let e2 = record(2, Some(&a1), VALID, 5);
+        let a3 = record(3, Some(&a2), VALID, 6);
+        let d3 = record(3, Some(&a2), VALID_TREE, 7);
+        let e3 = record(3, Some(&e2), VALID_TREE | BLOCK_HAVE_DATA | 32, 8);
+        let a4 = record(4, Some(&a3), VALID_TREE, 9);
+        let hash = |record: &BlockIndexRecord| bitcoin::BlockHash::from_raw_hash(record.block_hash);
+        let expected = [
+            (hash(&a4), 4, 1, TipStatus::HeadersOnly),
+            (hash(&a3), 3, 0, TipStatus::Active),
+            (hash(&d3), 3, 1, TipStatus::HeadersOnly),
+            (hash(&e3), 3, 2, TipStatus::Invalid),
+            (hash(&b2), 2, 1, TipStatus::ValidFork),
+            (hash(&c2), 2, 1, TipStatus::ValidHeaders),
+        ];
+        let contents = IndexContents {
+            records: vec![a0, a1, a2, b2, c2, e2, a3, d3, e3, a4],
+            flags: IndexFlags::default(),
+        };
+
+        let tips = contents.chain_tips();
+        assert_eq!(tips.len(), expected.len());
+        assert!(tips.windows(2).all(|w| w[0].height >= w[1].height));
+        for (hash, height, branch_len, status) in expected {
+            let tip = tips.iter().find(|tip| tip.hash == hash).unwrap();
+            assert_eq!(
+                (tip.height, tip.branch_len, tip.status),
+                (height, branch_len, status)
+            );
+        }
+
+        let (block_index, stale) = contents.into_active_chain();
+        assert_eq!(block_index.len(), 4);
+        assert_eq!(block_index[&3].block_hash, expected[1].0.to_raw_hash());
+        assert_eq!(stale.len(), 6);
+    }
 }
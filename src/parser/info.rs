@@ -0,0 +1,126 @@
+use anyhow::Context;
+
+use crate::parser::blkfile::BlkFile;
+use crate::parser::index::{ChainIndex, ChainTip, IndexFlags};
+use crate::ParserOptions;
+
+/// Summary of a blockchain directory, printed by the `info` subcommand
+#[derive(Debug)]
+pub struct DatadirInfo {
+    pub coin: String,
+    pub blockchain_dir: std::path::PathBuf,
+    pub blk_files: usize,
+    /// Total size of the blk files in bytes
+    pub blk_size: u64,
+    pub rev_files: usize,
+    /// Total size of the rev files in bytes
+    pub rev_size: u64,
+    /// Block records in the index, including stale and headers-only blocks
+    pub index_records: usize,
+    pub tip_height: u64,
+    pub tip_hash: bitcoin::BlockHash,
+    /// Blocks in the index that aren't part of the active chain, see `ChainIndex::stale_blocks`
+    pub stale_blocks: usize,
+    pub flags: IndexFlags,
+    /// Block files are XOR-ed with the key in `xor.dat`, which the parser can't read
+    pub xor_obfuscated: bool,
+}
+
+impl DatadirInfo {
+    pub fn new(options: &ParserOptions) -> anyhow::Result<Self> {
+        let blk_files = BlkFile::from_path(&options.blockchain_dir)?;
+        let rev_files = BlkFile::undo_from_path(&options.blockchain_dir).unwrap_or_default();
+        let index = ChainIndex::new(options)?;
+        let tip_height = index.tip_height();
+        let tip_hash = index.tip_hash();
+
+        let mut flags = index.flags().clone();
+        // since v0.17 the txindex is a separate database next to the blocks dir
+        flags.txindex |= options
+            .blockchain_dir
+            .parent()
+            .is_some_and(|datadir| datadir.join("indexes").join("txindex").is_dir());
+
+        Ok(Self {
+            coin: options.coin.name.clone(),
+            blockchain_dir: options.blockchain_dir.clone(),
+            blk_files: blk_files.len(),
+            blk_size: blk_files.values().map(|f| f.size).sum(),
+            rev_files: rev_files.len(),
+            rev_size: rev_files.values().map(|f| f.size).sum(),
+            index_records: index.record_count(),
+            tip_height,
+            tip_hash,
+            stale_blocks: index.stale_blocks().len(),
+            flags,
+            xor_obfuscated: is_xor_obfuscated(&options.blockchain_dir)?,
+        })
+    }
+}
+
+/// Chain tips of the block index like `getchaintips`, the highest first
+pub fn chain_tips(options: &ParserOptions) -> anyhow::Result<Vec<ChainTip>> {
+    Ok(ChainIndex::new(options)?.chain_tips().to_vec())
+}
+
+/// Writes `tips` as tab-separated rows with a header, like the `query` subcommand
+pub fn write_chain_tips(tips: &[ChainTip], out: &mut impl std::io::Write) -> std::io::Result<()> {
+    writeln!(out, "height\thash\tbranchlen\tstatus")?;
+    for tip in tips {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            tip.height, tip.hash, tip.branch_len, tip.status
+        )?;
+    }
+    Ok(())
+}
+
+/// Bitcoin Core v28 and later obfuscate new block files with a random key unless started with
+/// `-blocksxor=0`, in which case the key is all zeros.
+fn is_xor_obfuscated(blockchain_dir: &std::path::Path) -> anyhow::Result<bool> {
+    match std::fs::read(blockchain_dir.join("xor.dat")) {
+        Ok(key) => Ok(key.iter().any(|byte| *byte != 0)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e).context("cannot read xor.dat"),
+    }
+}
+
+fn yes_no(flag: bool) -> &'static str {
+    if flag {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+fn gib(bytes: u64) -> f64 {
+    bytes as f64 / f64::from(1 << 30)
+}
+
+impl std::fmt::Display for DatadirInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "coin:           {}", self.coin)?;
+        writeln!(f, "blocks dir:     {}", self.blockchain_dir.display())?;
+        writeln!(
+            f,
+            "blk files:      {} ({:.2} GiB)",
+            self.blk_files,
+            gib(self.blk_size)
+        )?;
+        writeln!(
+            f,
+            "rev files:      {} ({:.2} GiB)",
+            self.rev_files,
+            gib(self.rev_size)
+        )?;
+        writeln!(f, "index records:  {}", self.index_records)?;
+        writeln!(f, "tip height:     {}", self.tip_height)?;
+        writeln!(f, "tip hash:       {}", self.tip_hash)?;
+        writeln!(f, "stale blocks:   {}", self.stale_blocks)?;
+        writeln!(f, "txindex:        {}", yes_no(self.flags.txindex))?;
+        writeln!(f, "pruned:         {}", yes_no(self.flags.pruned))?;
+        writeln!(f, "reindexing:     {}", yes_no(self.flags.reindexing))?;
+        write!(f, "xor obfuscated: {}", yes_no(self.xor_obfuscated))
+    }
+}
@@ -1,15 +1,29 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use bitcoin_pool_identification::PoolIdentification;
-
 use crate::parser::chain::ChainStorage;
-use crate::ParserOptions;
+use crate::parser::undo::BlockUndo;
+use crate::parser::visitor::{BlockData, BlockVisitor};
+use crate::{ParserOptions, Verify};
 
 mod blkfile;
 pub mod chain;
+pub mod coinbase;
+pub mod fees;
 mod index;
+pub mod info;
+pub mod opreturn;
+pub mod pow;
 pub mod reader;
+mod scheduler;
+pub mod script;
 pub mod types;
+pub mod undo;
+pub mod verify;
+pub mod visitor;
+
+pub use index::{ChainTip, IndexFlags, TipStatus};
 
 struct WorkerStats {
     pub started_at: Instant,
@@ -27,23 +41,87 @@ impl WorkerStats {
     }
 }
 
+/// Visitor and the first height it's passed blocks from
+struct Visitor {
+    inner: Box<dyn BlockVisitor>,
+    first_height: u64,
+}
+
+impl Visitor {
+    fn new(inner: Box<dyn BlockVisitor>) -> Self {
+        Self {
+            inner,
+            first_height: 0,
+        }
+    }
+
+    /// Visitors which resumed after `height` already got the block in an earlier run.
+    fn wants(&self, height: u64) -> bool {
+        height >= self.first_height
+    }
+}
+
 pub struct BlockchainParser {
     chain_storage: ChainStorage,
     stats: WorkerStats,
     cur_height: u64,
     db: crate::db::Db,
+    coin: String,
+    visitors: Vec<Visitor>,
+    verify: Verify,
+    interrupted: Arc<AtomicBool>,
+    hashrate_windows: Vec<u64>,
+    work: pow::WorkTracker,
+    median_time: pow::MedianTime,
 }
 
 impl BlockchainParser {
-    #[must_use]
-    pub fn new(options: &ParserOptions, chain_storage: ChainStorage) -> Self {
+    pub fn new(options: &ParserOptions, chain_storage: ChainStorage) -> anyhow::Result<Self> {
         tracing::info!(target: "parser", "Parsing {} blockchain ...", options.coin.name);
-        Self {
+        let db = crate::db::Db::open(&options.db_url)?;
+        let mut visitors = Vec::new();
+        if options.write_db {
+            visitors.push(Visitor::new(Box::new(crate::db::writer::DbWriter::new(
+                db.clone(),
+                options.coin.name.clone(),
+                options.coin.network,
+                options.batch_size,
+                options.defer_indexes,
+            ))));
+        }
+        if let Some(export) = &options.export {
+            visitors.push(Visitor::new(crate::export::visitor(
+                export,
+                options.coin.network,
+            )));
+        }
+        Ok(Self {
             chain_storage,
             stats: WorkerStats::new(options.range.start),
             cur_height: options.range.start,
-            db: crate::db::Db::open(&options.db_url),
-        }
+            db,
+            coin: options.coin.name.clone(),
+            visitors,
+            verify: options.verify,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            hashrate_windows: options.hashrate_windows.clone(),
+            work: pow::WorkTracker::new(
+                options.hashrate_windows.iter().max().copied().unwrap_or(0),
+            ),
+            median_time: pow::MedianTime::default(),
+        })
+    }
+
+    /// Returns a flag which stops the parser after the current block when set,
+    /// e.g. from a signal handler. Buffered rows are written and checkpointed before returning.
+    #[must_use]
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupted)
+    }
+
+    #[must_use]
+    pub fn interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
     }
 
     #[must_use]
@@ -51,52 +129,118 @@ impl BlockchainParser {
         &self.db
     }
 
+    /// Adds a visitor which is called for every block after the ones added before,
+    /// the database writer and the exporter always come first.
+    pub fn add_visitor(&mut self, visitor: impl BlockVisitor + 'static) {
+        self.visitors.push(Visitor::new(Box::new(visitor)));
+    }
+
     pub fn start(&mut self) -> anyhow::Result<()> {
         tracing::debug!(target: "parser", "Starting worker ...");
 
-        self.on_start(self.cur_height);
-        let block_buffer_size = 2;
-        let mut blocks = Vec::with_capacity(block_buffer_size);
+        self.resume()?;
+        self.init_work()?;
+        self.on_start(self.cur_height)?;
         while let Some(header) = self.chain_storage.get_header(self.cur_height) {
-            Self::on_header(&header, self.cur_height);
-            let block = self.chain_storage.get_block(self.cur_height).unwrap();
+            if self.interrupted() {
+                tracing::info!(target: "parser", "Interrupted, stopping at height {} ...", self.cur_height);
+                break;
+            }
+            self.on_header(&header, self.cur_height)?;
+            let chainwork = self.work.push(&header);
+            let median_time = self.median_time.push(header.time);
+            let block = self.chain_storage.get_block(self.cur_height)?;
+            let undo = match block {
+                // the genesis block has no undo data
+                Some(_) if self.cur_height == 0 => Some(BlockUndo { txdata: Vec::new() }),
+                Some(_) => self.chain_storage.get_block_undo(self.cur_height),
+                None => None,
+            };
+            if let Some(block) = &block {
+                self.chain_storage
+                    .verify_scripts(block, self.cur_height, undo.as_ref())?;
+            }
+            self.on_issues()?;
+            // unreadable blocks are only skipped in report mode
+            let Some(block) = block else {
+                self.print_progress(self.cur_height);
+                self.cur_height += 1;
+                continue;
+            };
 
-            let turnover: u64 = block
-                .txdata
+            let hashrates: Vec<(u64, f64)> = self
+                .hashrate_windows
                 .iter()
-                .flat_map(|tx| tx.output.iter().map(|output| output.value))
-                .sum();
-
-            let miner_reward = block
-                .coinbase()
-                .map_or_else(|| 0, |cb| cb.output.iter().map(|output| output.value).sum());
-
-            let pool = block.identify_pool().map(|p| p.name);
-
-            tracing::trace!(target: "parser", "on_block(height={}) called", self.cur_height);
-            blocks.push(crate::db::Block {
-                height: self.cur_height.try_into()?,
-                version: block.header.version.to_consensus(),
-                time: block.header.time.try_into()?,
-                encoded_target: block.header.bits.to_consensus().try_into()?,
-                nonce: block.header.nonce.try_into()?,
-                tx_count: block.txdata.len().try_into()?,
-                size: block.size().try_into()?,
-                weight: block.weight().to_wu().try_into()?,
-                turnover: turnover.try_into()?,
-                miner_reward: miner_reward.try_into()?,
-                pool,
-            });
-            if blocks.len() == block_buffer_size {
-                self.db.insert_blocks(blocks)?;
-                blocks = Vec::with_capacity(block_buffer_size);
-            }
+                .map(|window| (*window, self.work.hashrate(*window)))
+                .collect();
+            self.on_block(&BlockData {
+                height: self.cur_height,
+                block: &block,
+                undo: undo.as_ref(),
+                chainwork,
+                hashrates: &hashrates,
+                median_time,
+                tip_height: self.chain_storage.tip_height(),
+                next_hash: self.chain_storage.next_block_hash(self.cur_height),
+            })?;
             self.print_progress(self.cur_height);
             self.cur_height += 1;
         }
 
-        self.db.insert_blocks(blocks)?;
-        self.on_complete(self.cur_height.saturating_sub(1));
+        if !self.interrupted() {
+            self.on_stale_blocks()?;
+        }
+        self.on_issues()?;
+        self.on_complete(self.cur_height.saturating_sub(1))
+    }
+
+    /// Continues after the checkpoint if the database already contains parsed blocks.
+    fn resume(&mut self) -> anyhow::Result<()> {
+        let Some(checkpoint) = self.db.checkpoint()? else {
+            return Ok(());
+        };
+        if checkpoint.coin != self.coin {
+            anyhow::bail!(
+                "database contains {} data, unable to resume parsing {}",
+                checkpoint.coin,
+                self.coin
+            );
+        }
+        let next_height = u64::try_from(checkpoint.height)? + 1;
+        if next_height <= self.cur_height {
+            return Ok(());
+        }
+        tracing::info!(target: "parser", "Resuming from checkpoint at height {} ...", checkpoint.height);
+        // visitors may need blocks before the checkpoint to complete their output
+        let mut start = next_height;
+        for visitor in &mut self.visitors {
+            visitor.first_height = visitor
+                .inner
+                .on_resume(next_height - 1)?
+                .clamp(self.cur_height, next_height);
+            start = start.min(visitor.first_height);
+        }
+        if start < next_height {
+            tracing::info!(target: "parser", "Visiting blocks again from height {} ...", start);
+        }
+        self.cur_height = start;
+        self.stats.last_height = start;
+        Ok(())
+    }
+
+    /// Loads the chainwork and times of the blocks preceding the first one to parse from the block index.
+    fn init_work(&mut self) -> anyhow::Result<()> {
+        let max_window = self.hashrate_windows.iter().max().copied().unwrap_or(0);
+        let first = self
+            .cur_height
+            .saturating_sub(max_window.max(pow::MEDIAN_TIME_SPAN - 1));
+        for height in first..self.cur_height {
+            let (header, chainwork) = self.chain_storage.index_entry(height).ok_or_else(|| {
+                anyhow::anyhow!("unable to determine the chainwork at height {height}")
+            })?;
+            self.work.push_chainwork(header.time, chainwork);
+            self.median_time.push(header.time);
+        }
         Ok(())
     }
 
@@ -107,23 +251,93 @@ impl BlockchainParser {
             .saturating_sub(self.cur_height)
     }
 
-    fn on_start(&mut self, height: u64) {
+    /// Whether verification in report mode found any inconsistencies
+    #[must_use]
+    pub fn verification_failed(&self) -> bool {
+        !self.chain_storage.verification_summary().is_empty()
+    }
+
+    fn on_start(&mut self, height: u64) -> anyhow::Result<()> {
         let now = Instant::now();
         self.stats.started_at = now;
         self.stats.last_log = now;
         tracing::info!(target: "parser", "Processing blocks starting from height {} ...", height);
         tracing::trace!(target: "parser", "on_start() called");
+        for visitor in &mut self.visitors {
+            visitor.inner.on_start(height.max(visitor.first_height))?;
+        }
+        Ok(())
     }
 
-    fn on_header(_header: &bitcoin::blockdata::block::Header, height: u64) {
+    fn on_header(
+        &mut self,
+        header: &bitcoin::blockdata::block::Header,
+        height: u64,
+    ) -> anyhow::Result<()> {
         tracing::trace!(target: "parser", "on_header(height={}) called", height);
+        for visitor in self.visitors.iter_mut().filter(|v| v.wants(height)) {
+            visitor.inner.on_header(header, height)?;
+        }
+        Ok(())
+    }
+
+    fn on_block(&mut self, data: &BlockData) -> anyhow::Result<()> {
+        tracing::trace!(target: "parser", "on_block(height={}) called", data.height);
+        for visitor in self.visitors.iter_mut().filter(|v| v.wants(data.height)) {
+            visitor.inner.on_block(data)?;
+        }
+        Ok(())
+    }
+
+    /// Passes on the inconsistencies recorded in report mode since the last call.
+    fn on_issues(&mut self) -> anyhow::Result<()> {
+        for issue in self.chain_storage.take_issues() {
+            for visitor in self.visitors.iter_mut().filter(|v| v.wants(issue.height)) {
+                visitor.inner.on_issue(&issue)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Passes on the blocks of the index which aren't part of the active chain.
+    fn on_stale_blocks(&mut self) -> anyhow::Result<()> {
+        let count = self.chain_storage.stale_block_count();
+        tracing::debug!(target: "parser", "Processing {} stale blocks ...", count);
+        for i in 0..count {
+            let Some((header, height, block)) = self.chain_storage.get_stale_block(i) else {
+                continue;
+            };
+            for visitor in &mut self.visitors {
+                visitor
+                    .inner
+                    .on_stale_block(&header, height, block.as_ref())?;
+            }
+        }
+        Ok(())
     }
 
-    fn on_complete(&mut self, height: u64) {
+    fn on_complete(&mut self, height: u64) -> anyhow::Result<()> {
+        for visitor in &mut self.visitors {
+            visitor.inner.on_complete(height)?;
+        }
         tracing::info!(target: "parser", "Done. Processed blocks up to height {} in {:.2} minutes.",
         height, self.stats.started_at.elapsed().as_secs_f32() / 60.0);
 
+        if self.verify == Verify::Report {
+            let summary = self.chain_storage.verification_summary();
+            if summary.is_empty() {
+                tracing::info!(target: "parser", "Verification found no issues.");
+            } else {
+                let total: u64 = summary.values().sum();
+                tracing::warn!(target: "parser", "Verification found {} issues:", total);
+                for (kind, count) in summary {
+                    tracing::warn!(target: "parser", "  {:>10}: {}", kind.as_str(), count);
+                }
+            }
+        }
+
         tracing::trace!(target: "parser", "on_complete() called");
+        Ok(())
     }
 
     fn print_progress(&mut self, height: u64) {
@@ -0,0 +1,137 @@
+use bitcoin::blockdata::opcodes::all::{OP_PUSHNUM_13, OP_RETURN};
+use bitcoin::blockdata::script::Instruction;
+
+/// Known protocol prefixes of the pushed data, see e.g. https://opreturn.org.
+/// Short markers are only listed together with the version or operation byte following them,
+/// on their own they match ordinary text. Protocols without such a byte, like CoinSpark (`SPK`)
+/// and Eternity Wall (`EW `), and Counterparty, whose data is encrypted with the txid of the
+/// first input, aren't detected.
+const PROTOCOL_PREFIXES: &[(&[u8], &str)] = &[
+    (b"omni", "omni"),
+    (b"\xaa\x21\xa9\xed", "witness_commitment"),
+    (b"RSKBLOCK:", "rsk"),
+    // magic bytes and operation of SIP-001 and SIP-007
+    (b"X2[", "stacks"),
+    (b"X2^", "stacks"),
+    (b"X2_", "stacks"),
+    (b"X2p", "stacks"),
+    (b"X2x", "stacks"),
+    (b"X2$", "stacks"),
+    (b"X2#", "stacks"),
+    // marker and version 1.0
+    (b"OA\x01\x00", "open_assets"),
+    (b"DOCPROOF", "proof_of_existence"),
+    (b"FACTOM00", "factom"),
+    (b"ASCRIBE", "ascribe"),
+    (b"VeriBlock", "veriblock"),
+];
+
+/// An `OP_RETURN` output split into its payload and the data pushed by it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpReturn {
+    /// Script bytes following `OP_RETURN`
+    pub payload: Vec<u8>,
+    /// Concatenation of all pushed bytes, `None` if the payload isn't a valid push sequence
+    pub data: Option<Vec<u8>>,
+    pub protocol: Option<&'static str>,
+}
+
+impl OpReturn {
+    /// Returns `None` if the script doesn't start with `OP_RETURN`.
+    #[must_use]
+    pub fn from_script(script: &bitcoin::Script) -> Option<Self> {
+        let (&first, payload) = script.as_bytes().split_first()?;
+        if first != OP_RETURN.to_u8() {
+            return None;
+        }
+
+        let mut data = Vec::with_capacity(payload.len());
+        let mut valid = true;
+        for instruction in bitcoin::Script::from_bytes(payload).instructions() {
+            match instruction {
+                Ok(Instruction::PushBytes(bytes)) => data.extend_from_slice(bytes.as_bytes()),
+                Ok(Instruction::Op(_)) => {}
+                Err(_) => {
+                    valid = false;
+                    break;
+                }
+            }
+        }
+        let data = valid.then_some(data);
+
+        let protocol = if payload.first() == Some(&OP_PUSHNUM_13.to_u8()) {
+            Some("runes")
+        } else {
+            data.as_deref().and_then(protocol)
+        };
+        Some(Self {
+            payload: payload.to_vec(),
+            data,
+            protocol,
+        })
+    }
+}
+
+fn protocol(data: &[u8]) -> Option<&'static str> {
+    PROTOCOL_PREFIXES
+        .iter()
+        .find(|(prefix, _)| data.starts_with(prefix))
+        .map(|(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op_return(hex: &str) -> Option<OpReturn> {
+        OpReturn::from_script(&bitcoin::ScriptBuf::from_hex(hex).unwrap())
+    }
+
+    #[test]
+    fn test_op_return() {
+        assert!(op_return("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").is_none());
+        assert!(op_return("").is_none());
+
+        let empty = op_return("6a").unwrap();
+        assert!(empty.payload.is_empty());
+        assert_eq!(empty.data, Some(vec![]));
+        assert!(empty.protocol.is_none());
+
+        let hello = op_return("6a0568656c6c6f05776f726c64").unwrap();
+        assert_eq!(hello.data.as_deref(), Some(&b"helloworld"[..]));
+        assert!(hello.protocol.is_none());
+
+        let omni = op_return("6a146f6d6e69000000000000001f000000002b752ee0").unwrap();
+        assert_eq!(omni.protocol, Some("omni"));
+
+        let runes = op_return("6a5d0614c0a2331441").unwrap();
+        assert_eq!(runes.protocol, Some("runes"));
+
+        let open_assets = op_return("6a0a4f41010002a0860100ac").unwrap();
+        assert_eq!(open_assets.protocol, Some("open_assets"));
+
+        let stacks = op_return("6a0458325b00").unwrap();
+        assert_eq!(stacks.protocol, Some("stacks"));
+
+        // text starting like a short marker
+        for text in [
+            "OAuth",
+            "X2 is out",
+            "id card",
+            "Fair",
+            "SPKR 1",
+            "EW hello",
+        ] {
+            let script = bitcoin::ScriptBuf::new_op_return(
+                &bitcoin::script::PushBytesBuf::try_from(text.as_bytes().to_vec()).unwrap(),
+            );
+            assert!(OpReturn::from_script(&script).unwrap().protocol.is_none());
+        }
+
+        // push exceeding the script
+        let truncated = op_return("6a0568656c").unwrap();
+        assert_eq!(truncated.payload, vec![0x05, 0x68, 0x65, 0x6c]);
+        assert!(truncated.data.is_none());
+        assert!(truncated.protocol.is_none());
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::VecDeque;
+
+/// Number of blocks the median time past is computed over
+pub const MEDIAN_TIME_SPAN: u64 = 11;
+
+/// Difficulty relative to the minimum difficulty, following `GetDifficulty` in Bitcoin Core
+#[must_use]
+pub fn difficulty(bits: u32) -> f64 {
+    let mantissa = bits & 0x00ff_ffff;
+    if mantissa == 0 {
+        return 0.0;
+    }
+    let mut shift = (bits >> 24) & 0xff;
+    let mut difficulty = f64::from(0x0000_ffff) / f64::from(mantissa);
+    while shift < 29 {
+        difficulty *= 256.0;
+        shift += 1;
+    }
+    while shift > 29 {
+        difficulty /= 256.0;
+        shift -= 1;
+    }
+    difficulty
+}
+
+/// Big endian hex encoding, as used for `chainwork` by Bitcoin Core
+#[must_use]
+pub fn work_to_hex(work: bitcoin::Work) -> String {
+    crate::export::to_hex(&work.to_be_bytes())
+}
+
+fn work_to_f64(work: bitcoin::Work) -> f64 {
+    work.to_be_bytes()
+        .iter()
+        .fold(0.0, |acc, b| acc * 256.0 + f64::from(*b))
+}
+
+/// Keeps the time and cumulative chainwork of the most recent blocks to compute the chainwork
+/// of the next block and to estimate the hashrate.
+pub struct WorkTracker {
+    history: VecDeque<(u32, bitcoin::Work)>,
+    capacity: usize,
+}
+
+impl WorkTracker {
+    /// `max_window` is the largest window the hashrate is estimated over.
+    #[must_use]
+    pub fn new(max_window: u64) -> Self {
+        let capacity = usize::try_from(max_window).unwrap_or(usize::MAX - 1) + 1;
+        Self {
+            history: VecDeque::with_capacity(capacity.min(100_000)),
+            capacity,
+        }
+    }
+
+    /// Adds the next block and returns its chainwork.
+    pub fn push(&mut self, header: &bitcoin::blockdata::block::Header) -> bitcoin::Work {
+        let chainwork = match self.history.back() {
+            Some((_, prev)) => *prev + header.work(),
+            None => header.work(),
+        };
+        self.push_chainwork(header.time, chainwork);
+        chainwork
+    }
+
+    /// Adds a block whose chainwork is already known.
+    pub fn push_chainwork(&mut self, time: u32, chainwork: bitcoin::Work) {
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back((time, chainwork));
+    }
+
+    /// Chainwork of the most recent block
+    #[must_use]
+    pub fn chainwork(&self) -> Option<bitcoin::Work> {
+        self.history.back().map(|(_, chainwork)| *chainwork)
+    }
+
+    /// Estimated hashes per second over the last `window` blocks, following
+    /// `getnetworkhashps` in Bitcoin Core. Uses fewer blocks if not enough are known.
+    #[must_use]
+    pub fn hashrate(&self, window: u64) -> f64 {
+        let count = usize::try_from(window)
+            .unwrap_or(usize::MAX)
+            .min(self.history.len().saturating_sub(1));
+        let (Some((_, last)), Some((_, first))) = (
+            self.history.back(),
+            self.history
+                .get(self.history.len().saturating_sub(count + 1)),
+        ) else {
+            return 0.0;
+        };
+        let times = self.history.iter().rev().take(count + 1).map(|(t, _)| *t);
+        let (min_time, max_time) = times.fold((u32::MAX, u32::MIN), |(min, max), t| {
+            (min.min(t), max.max(t))
+        });
+        if count == 0 || max_time == min_time {
+            return 0.0;
+        }
+        (work_to_f64(*last) - work_to_f64(*first)) / f64::from(max_time - min_time)
+    }
+}
+
+/// Keeps the timestamps of the most recent blocks, following `GetMedianTimePast` in Bitcoin Core
+#[derive(Default)]
+pub struct MedianTime {
+    times: VecDeque<u32>,
+}
+
+impl MedianTime {
+    /// Adds the next block and returns its median time past.
+    pub fn push(&mut self, time: u32) -> u32 {
+        if self.times.len() as u64 == MEDIAN_TIME_SPAN {
+            self.times.pop_front();
+        }
+        self.times.push_back(time);
+        let mut sorted: Vec<u32> = self.times.iter().copied().collect();
+        sorted.sort_unstable();
+        sorted[sorted.len() / 2]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_difficulty() {
+        assert!((difficulty(0x1d00_ffff) - 1.0).abs() < f64::EPSILON);
+        assert!((difficulty(0x1b04_04cb) - 16_307.420_938_523_983).abs() < 1e-9);
+        assert!(difficulty(0x1d00_0000).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_work_tracker() {
+        let mut header = bitcoin::blockdata::constants::genesis_block(
+            bitcoin::network::constants::Network::Bitcoin,
+        )
+        .header;
+        let mut tracker = WorkTracker::new(2);
+        let chainwork = tracker.push(&header);
+        assert_eq!(
+            work_to_hex(chainwork),
+            "0000000000000000000000000000000000000000000000000000000100010001"
+        );
+        assert!(tracker.hashrate(2).abs() < f64::EPSILON);
+
+        for _ in 0..3 {
+            header.time += 600;
+            tracker.push(&header);
+        }
+        assert_eq!(tracker.history.len(), 3);
+        let work = work_to_f64(header.work());
+        assert!((tracker.hashrate(2) - 2.0 * work / 1200.0).abs() < 1e-6);
+        assert!((tracker.hashrate(1) - work / 600.0).abs() < 1e-6);
+        assert!((tracker.hashrate(10) - tracker.hashrate(2)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_median_time() {
+        let mut median_time = MedianTime::default();
+        assert_eq!(median_time.push(100), 100);
+        assert_eq!(median_time.push(50), 100);
+        assert_eq!(median_time.push(75), 75);
+        for time in 1..=11 {
+            median_time.push(time * 1000);
+        }
+        assert_eq!(median_time.times.len(), 11);
+        assert_eq!(median_time.push(500), 6000);
+    }
+}
@@ -1,12 +1,46 @@
 use bitcoin::consensus::Decodable;
 
+use crate::parser::undo::BlockUndo;
+
 pub trait BlockchainRead: std::io::Read {
     fn read_block(&mut self) -> anyhow::Result<bitcoin::Block> {
-        Ok(bitcoin::Block::consensus_decode(self).unwrap())
+        Ok(bitcoin::Block::consensus_decode(self)?)
     }
 
     fn read_header(&mut self) -> anyhow::Result<bitcoin::blockdata::block::Header> {
-        Ok(bitcoin::blockdata::block::Header::consensus_decode(self).unwrap())
+        Ok(bitcoin::blockdata::block::Header::consensus_decode(self)?)
+    }
+
+    fn read_block_undo(&mut self) -> anyhow::Result<BlockUndo> {
+        BlockUndo::read_from(self)
+    }
+
+    fn read_compact_size(&mut self) -> anyhow::Result<u64> {
+        Ok(bitcoin::VarInt::consensus_decode(self)?.0)
+    }
+
+    /// TODO: this is a wonky 1:1 translation from https://github.com/bitcoin/bitcoin
+    /// It is NOT the same as CompactSize.
+    fn read_varint(&mut self) -> anyhow::Result<u64> {
+        let mut n = 0;
+        loop {
+            let mut buf = [0; 1];
+            self.read_exact(&mut buf)?;
+            let ch_data = buf[0];
+            if n > u64::MAX >> 7 {
+                anyhow::bail!("varint too large");
+            }
+            n = (n << 7) | u64::from(ch_data & 0x7F);
+            if ch_data & 0x80 > 0 {
+                if n == u64::MAX {
+                    anyhow::bail!("varint too large");
+                }
+                n += 1;
+            } else {
+                break;
+            }
+        }
+        Ok(n)
     }
 }
 
@@ -0,0 +1,162 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::parser::blkfile::BlkFile;
+use crate::parser::index::ChainIndex;
+
+/// Number of consecutive heights which are read ahead in disk order
+pub const READ_WINDOW: u64 = 64;
+
+/// Maximum number of blk (or rev) files kept open at the same time
+pub const MAX_OPEN_FILES: usize = 8;
+
+/// A set of blk or rev files of which at most `capacity` are kept open.
+/// Accessing a file marks it as most recently used, the least recently used one gets closed.
+pub struct FileCache {
+    files: HashMap<u64, BlkFile>,
+    lru: VecDeque<u64>,
+    capacity: usize,
+}
+
+impl FileCache {
+    #[must_use]
+    pub fn new(files: HashMap<u64, BlkFile>, capacity: usize) -> Self {
+        Self {
+            files,
+            lru: VecDeque::with_capacity(capacity + 1),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn get_mut(&mut self, index: u64) -> Option<&mut BlkFile> {
+        if !self.files.contains_key(&index) {
+            return None;
+        }
+        if let Some(pos) = self.lru.iter().position(|i| *i == index) {
+            self.lru.remove(pos);
+        }
+        self.lru.push_back(index);
+        while self.lru.len() > self.capacity {
+            if let Some(file) = self.lru.pop_front().and_then(|i| self.files.get_mut(&i)) {
+                file.close();
+            }
+        }
+        self.files.get_mut(&index)
+    }
+
+    /// Number of files currently open
+    #[cfg(test)]
+    #[must_use]
+    pub fn open_count(&self) -> usize {
+        self.files.values().filter(|f| f.is_open()).count()
+    }
+}
+
+/// Result of reading a single block from disk
+pub struct BlockRead {
+    /// Magic bytes and size preceding the block
+    pub framing: anyhow::Result<(u32, u32)>,
+    pub block: anyhow::Result<bitcoin::Block>,
+}
+
+/// Reads blocks ahead in (file, offset) order and hands them out by height.
+///
+/// Blocks are stored in the order they arrived, not in height order. Reading a window of
+/// heights sorted by their position on disk keeps the reads sequential within a file.
+pub struct ReadScheduler {
+    window: u64,
+    buffer: HashMap<u64, BlockRead>,
+}
+
+impl ReadScheduler {
+    #[must_use]
+    pub fn new(window: u64) -> Self {
+        Self {
+            window: window.max(1),
+            buffer: HashMap::new(),
+        }
+    }
+
+    /// Returns the block at `height` if it has already been read ahead
+    #[must_use]
+    pub fn buffered(&self, height: u64) -> Option<&BlockRead> {
+        self.buffer.get(&height)
+    }
+
+    /// Removes the block at `height` from the buffer, reading the window starting at
+    /// `height` if necessary.
+    pub fn take(
+        &mut self,
+        height: u64,
+        index: &ChainIndex,
+        files: &mut FileCache,
+    ) -> Option<BlockRead> {
+        if !self.buffer.contains_key(&height) {
+            self.fill(height, index, files);
+        }
+        self.buffer.remove(&height)
+    }
+
+    /// Reads the blocks of the window starting at `start` which aren't buffered yet and
+    /// drops the ones outside of it.
+    fn fill(&mut self, start: u64, index: &ChainIndex, files: &mut FileCache) {
+        let window = start..start.saturating_add(self.window);
+        self.buffer.retain(|height, _| window.contains(height));
+        let reads = plan(
+            window
+                .map_while(|height| index.get(height).map(|meta| (height, meta)))
+                .filter(|(height, _)| !self.buffer.contains_key(height))
+                .map(|(height, meta)| (height, meta.blk_index, meta.data_offset)),
+        );
+        tracing::trace!(target: "blkfile", "Reading {} blocks from height {} ...", reads.len(), start);
+
+        for (height, blk_index, offset) in reads {
+            let Some(blk_file) = files.get_mut(blk_index) else {
+                continue;
+            };
+            let framing = blk_file.read_framing(offset);
+            let block = blk_file.read_block(offset);
+            self.buffer.insert(height, BlockRead { framing, block });
+        }
+    }
+}
+
+/// Orders `(height, blk_index, offset)` reads by their position on disk.
+fn plan(reads: impl Iterator<Item = (u64, u64, u64)>) -> Vec<(u64, u64, u64)> {
+    let mut reads: Vec<_> = reads.collect();
+    reads.sort_unstable_by_key(|&(_, blk_index, offset)| (blk_index, offset));
+    reads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        let reads = vec![(10, 1, 500), (11, 0, 900), (12, 1, 100), (13, 0, 200)];
+        assert_eq!(
+            plan(reads.into_iter()),
+            vec![(13, 0, 200), (11, 0, 900), (12, 1, 100), (10, 1, 500)]
+        );
+    }
+
+    #[test]
+    fn test_file_cache_closes_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..3 {
+            std::fs::write(dir.path().join(format!("blk0000{i}.dat")), [0u8; 16]).unwrap();
+        }
+        let mut cache = FileCache::new(BlkFile::from_path(dir.path()).unwrap(), 2);
+
+        cache.get_mut(0).unwrap().read_framing(8).unwrap();
+        cache.get_mut(1).unwrap().read_framing(8).unwrap();
+        cache.get_mut(0).unwrap().read_framing(8).unwrap();
+        assert_eq!(cache.open_count(), 2);
+
+        cache.get_mut(2).unwrap().read_framing(8).unwrap();
+        assert_eq!(cache.open_count(), 2);
+        assert!(cache.files[&0].is_open());
+        assert!(!cache.files[&1].is_open());
+        assert!(cache.get_mut(3).is_none());
+    }
+}
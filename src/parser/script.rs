@@ -0,0 +1,402 @@
+use std::collections::BTreeMap;
+
+use bitcoin::blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_PUSHNUM_1, OP_PUSHNUM_16};
+use bitcoin::blockdata::script::Instruction;
+
+use crate::parser::types::CoinType;
+use crate::parser::undo::BlockUndo;
+
+// Script verification flags as defined by libbitcoinconsensus
+pub const VERIFY_NONE: u32 = 0;
+pub const VERIFY_P2SH: u32 = 1 << 0;
+pub const VERIFY_DERSIG: u32 = 1 << 2;
+pub const VERIFY_NULLDUMMY: u32 = 1 << 4;
+pub const VERIFY_CHECKLOCKTIMEVERIFY: u32 = 1 << 9;
+pub const VERIFY_CHECKSEQUENCEVERIFY: u32 = 1 << 10;
+pub const VERIFY_WITNESS: u32 = 1 << 11;
+pub const VERIFY_TAPROOT: u32 = 1 << 17;
+
+/// Output script templates, roughly following `TxoutType` in Bitcoin Core
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ScriptType {
+    P2pk,
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    /// Bare m-of-n multisig
+    Multisig,
+    OpReturn,
+    /// Witness program of the given version without defined semantics
+    WitnessUnknown(u8),
+    NonStandard,
+}
+
+impl ScriptType {
+    #[must_use]
+    pub fn of(script: &bitcoin::Script) -> Self {
+        if script.is_p2pk() {
+            ScriptType::P2pk
+        } else if script.is_p2pkh() {
+            ScriptType::P2pkh
+        } else if script.is_p2sh() {
+            ScriptType::P2sh
+        } else if script.is_v0_p2wpkh() {
+            ScriptType::P2wpkh
+        } else if script.is_v0_p2wsh() {
+            ScriptType::P2wsh
+        } else if script.is_v1_p2tr() {
+            ScriptType::P2tr
+        } else if let Some(version) = script
+            .witness_version()
+            .filter(|version| version.to_num() != 0 && script.is_witness_program())
+        {
+            ScriptType::WitnessUnknown(version.to_num())
+        } else if script.is_op_return() {
+            ScriptType::OpReturn
+        } else if is_multisig(script) {
+            ScriptType::Multisig
+        } else {
+            ScriptType::NonStandard
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ScriptType::P2pk => "p2pk",
+            ScriptType::P2pkh => "p2pkh",
+            ScriptType::P2sh => "p2sh",
+            ScriptType::P2wpkh => "p2wpkh",
+            ScriptType::P2wsh => "p2wsh",
+            ScriptType::P2tr => "p2tr",
+            ScriptType::Multisig => "multisig",
+            ScriptType::OpReturn => "op_return",
+            ScriptType::WitnessUnknown(version) => WITNESS_UNKNOWN
+                .get(usize::from(version))
+                .copied()
+                .unwrap_or("witness_unknown"),
+            ScriptType::NonStandard => "nonstandard",
+        }
+    }
+}
+
+/// Names of the unknown witness programs by version, v0 is always known
+const WITNESS_UNKNOWN: [&str; 17] = [
+    "witness_v0_unknown",
+    "witness_v1_unknown",
+    "witness_v2_unknown",
+    "witness_v3_unknown",
+    "witness_v4_unknown",
+    "witness_v5_unknown",
+    "witness_v6_unknown",
+    "witness_v7_unknown",
+    "witness_v8_unknown",
+    "witness_v9_unknown",
+    "witness_v10_unknown",
+    "witness_v11_unknown",
+    "witness_v12_unknown",
+    "witness_v13_unknown",
+    "witness_v14_unknown",
+    "witness_v15_unknown",
+    "witness_v16_unknown",
+];
+
+impl std::fmt::Display for ScriptType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// `OP_m <pubkey>... OP_n OP_CHECKMULTISIG` with 1 <= m <= n <= 16
+fn is_multisig(script: &bitcoin::Script) -> bool {
+    let Ok(instructions) = script.instructions().collect::<Result<Vec<_>, _>>() else {
+        return false;
+    };
+    let [Instruction::Op(m), keys @ .., Instruction::Op(n), Instruction::Op(checkmultisig)] =
+        instructions.as_slice()
+    else {
+        return false;
+    };
+    let pushnum = |op: &bitcoin::blockdata::opcodes::All| {
+        (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8())
+            .contains(&op.to_u8())
+            .then(|| usize::from(op.to_u8() - OP_PUSHNUM_1.to_u8() + 1))
+    };
+    match (pushnum(m), pushnum(n)) {
+        (Some(m), Some(n)) => {
+            *checkmultisig == OP_CHECKMULTISIG
+                && m <= n
+                && keys.len() == n
+                && keys.iter().all(|key| {
+                    matches!(key, Instruction::PushBytes(key) if key.len() == 33 || key.len() == 65)
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Number and value of the outputs created and spent in a block for a single script type
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ScriptStats {
+    pub output_count: u64,
+    pub output_value: u64,
+    pub input_count: u64,
+    pub input_value: u64,
+}
+
+/// Aggregates the created outputs and, if the undo data is given, the spent outputs of a block
+/// by script type.
+#[must_use]
+pub fn script_stats(
+    block: &bitcoin::Block,
+    undo: Option<&BlockUndo>,
+) -> BTreeMap<ScriptType, ScriptStats> {
+    let mut stats: BTreeMap<ScriptType, ScriptStats> = BTreeMap::new();
+    for output in block.txdata.iter().flat_map(|tx| &tx.output) {
+        let entry = stats
+            .entry(ScriptType::of(&output.script_pubkey))
+            .or_default();
+        entry.output_count += 1;
+        entry.output_value += output.value;
+    }
+    for prevout in undo
+        .iter()
+        .flat_map(|u| &u.txdata)
+        .flat_map(|tx| &tx.prevouts)
+    {
+        let entry = stats
+            .entry(ScriptType::of(&prevout.txout.script_pubkey))
+            .or_default();
+        entry.input_count += 1;
+        entry.input_value += prevout.txout.value;
+    }
+    stats
+}
+
+/// An input whose script failed to validate against its prevout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptFailure {
+    pub txid: bitcoin::Txid,
+    pub input_index: usize,
+    pub reason: String,
+}
+
+impl std::fmt::Display for ScriptFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.txid, self.input_index, self.reason)
+    }
+}
+
+/// Returns the script verification flags for a block, following `GetBlockScriptFlags` in
+/// Bitcoin Core: P2SH, witness and taproot rules apply to every block but a few exceptions,
+/// the remaining soft forks from their activation height on.
+#[must_use]
+pub fn script_flags(block_hash: &bitcoin::BlockHash, height: u64, coin: &CoinType) -> u32 {
+    let mut flags = *coin
+        .script_flag_exceptions
+        .get(block_hash.as_raw_hash())
+        .unwrap_or(&(VERIFY_P2SH | VERIFY_WITNESS | VERIFY_TAPROOT));
+    if height >= coin.bip66_height {
+        flags |= VERIFY_DERSIG;
+    }
+    if height >= coin.bip65_height {
+        flags |= VERIFY_CHECKLOCKTIMEVERIFY;
+    }
+    if height >= coin.csv_height {
+        flags |= VERIFY_CHECKSEQUENCEVERIFY;
+    }
+    if height >= coin.segwit_height {
+        flags |= VERIFY_NULLDUMMY;
+    }
+    flags
+}
+
+/// Executes the scripts of all inputs in the block against the outputs they spend.
+#[cfg(feature = "bitcoinconsensus")]
+pub fn verify_scripts(
+    block: &bitcoin::Block,
+    undo: &BlockUndo,
+    flags: u32,
+) -> anyhow::Result<Vec<ScriptFailure>> {
+    let mut failures = Vec::new();
+    if undo.txdata.len() + 1 != block.txdata.len() {
+        failures.push(ScriptFailure {
+            txid: block.txdata[0].txid(),
+            input_index: 0,
+            reason: format!(
+                "undo data covers {} transactions, block has {}",
+                undo.txdata.len(),
+                block.txdata.len() - 1
+            ),
+        });
+        return Ok(failures);
+    }
+
+    for (index, tx) in block.txdata.iter().enumerate().skip(1) {
+        let prevouts = undo.prevouts(index).unwrap_or_default();
+        if prevouts.len() != tx.input.len() {
+            failures.push(ScriptFailure {
+                txid: tx.txid(),
+                input_index: 0,
+                reason: format!(
+                    "undo data covers {} inputs, transaction has {}",
+                    prevouts.len(),
+                    tx.input.len()
+                ),
+            });
+            continue;
+        }
+
+        let serialized = bitcoin::consensus::serialize(tx);
+        let mut utxos = Vec::with_capacity(prevouts.len());
+        for prevout in prevouts {
+            utxos.push(bitcoinconsensus::Utxo {
+                script_pubkey: prevout.txout.script_pubkey.as_bytes().as_ptr(),
+                script_pubkey_len: prevout.txout.script_pubkey.len().try_into()?,
+                value: prevout.txout.value.try_into()?,
+            });
+        }
+        for (input_index, prevout) in prevouts.iter().enumerate() {
+            if let Err(e) = bitcoinconsensus::verify_with_flags(
+                prevout.txout.script_pubkey.as_bytes(),
+                prevout.txout.value,
+                &serialized,
+                Some(&utxos),
+                input_index,
+                flags,
+            ) {
+                failures.push(ScriptFailure {
+                    txid: tx.txid(),
+                    input_index,
+                    reason: format!("{e:?}"),
+                });
+            }
+        }
+    }
+    Ok(failures)
+}
+
+#[cfg(not(feature = "bitcoinconsensus"))]
+pub fn verify_scripts(
+    _block: &bitcoin::Block,
+    _undo: &BlockUndo,
+    _flags: u32,
+) -> anyhow::Result<Vec<ScriptFailure>> {
+    anyhow::bail!("script verification requires building with the `bitcoinconsensus` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::{Bitcoin, TestNet3};
+    use bitcoin::hashes::Hash;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_script_type() {
+        let script = |hex: &str| bitcoin::ScriptBuf::from_hex(hex).unwrap();
+        assert_eq!(
+            ScriptType::of(&script(
+                "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac"
+            )),
+            ScriptType::P2pkh
+        );
+        assert_eq!(
+            ScriptType::of(&script("0014751e76e8199196d454941c45d1b3a323f1433bd6")),
+            ScriptType::P2wpkh
+        );
+        assert_eq!(
+            ScriptType::of(&script(
+                "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+            )),
+            ScriptType::P2tr
+        );
+        assert_eq!(
+            ScriptType::of(&script("52020001")),
+            ScriptType::WitnessUnknown(2)
+        );
+        assert_eq!(
+            ScriptType::of(&script("60020001")).as_str(),
+            "witness_v16_unknown"
+        );
+        // a v1 program other than the 32 byte taproot output
+        assert_eq!(
+            ScriptType::of(&script("51020001")),
+            ScriptType::WitnessUnknown(1)
+        );
+        assert_eq!(
+            ScriptType::of(&script("6a0b68656c6c6f20776f726c64")),
+            ScriptType::OpReturn
+        );
+        let key = "21033b7b0cb5a16e2f2e4c1a1ac0a4b4bc6b0a5e5a0f0a2d6cbe9d2e7f0b1a3c4d5e";
+        assert_eq!(
+            ScriptType::of(&script(&format!("51{key}{key}52ae"))),
+            ScriptType::Multisig
+        );
+        assert_eq!(
+            ScriptType::of(&script(&format!("53{key}{key}52ae"))),
+            ScriptType::NonStandard
+        );
+        assert_eq!(ScriptType::of(&script("")), ScriptType::NonStandard);
+    }
+
+    #[test]
+    fn test_script_stats() {
+        let block = bitcoin::blockdata::constants::genesis_block(
+            bitcoin::network::constants::Network::Bitcoin,
+        );
+        let undo = BlockUndo {
+            txdata: vec![crate::parser::undo::TxUndo {
+                prevouts: vec![crate::parser::undo::SpentOutput {
+                    height: 0,
+                    is_coinbase: true,
+                    txout: block.txdata[0].output[0].clone(),
+                }],
+            }],
+        };
+        let stats = script_stats(&block, Some(&undo));
+        assert_eq!(stats.len(), 1);
+        assert_eq!(
+            stats[&ScriptType::P2pk],
+            ScriptStats {
+                output_count: 1,
+                output_value: 5_000_000_000,
+                input_count: 1,
+                input_value: 5_000_000_000,
+            }
+        );
+        assert_eq!(script_stats(&block, None)[&ScriptType::P2pk].input_count, 0);
+    }
+
+    #[test]
+    fn test_script_flags() {
+        let coin = CoinType::from(Bitcoin);
+        let hash = bitcoin::BlockHash::all_zeros();
+        assert_eq!(
+            script_flags(&hash, 0, &coin),
+            VERIFY_P2SH | VERIFY_WITNESS | VERIFY_TAPROOT
+        );
+        assert_eq!(
+            script_flags(&hash, 481_824, &coin),
+            VERIFY_P2SH
+                | VERIFY_WITNESS
+                | VERIFY_TAPROOT
+                | VERIFY_DERSIG
+                | VERIFY_CHECKLOCKTIMEVERIFY
+                | VERIFY_CHECKSEQUENCEVERIFY
+                | VERIFY_NULLDUMMY
+        );
+
+        let bip16_exception = bitcoin::BlockHash::from_str(
+            "00000000000002dc756eebf4f49723ed8d30cc28a5f108eb94b1ba88ac4f9c22",
+        )
+        .unwrap();
+        assert_eq!(script_flags(&bip16_exception, 170_060, &coin), VERIFY_NONE);
+        assert_eq!(
+            script_flags(&bip16_exception, 170_060, &CoinType::from(TestNet3)),
+            VERIFY_P2SH | VERIFY_WITNESS | VERIFY_TAPROOT
+        );
+    }
+}
@@ -1,7 +1,10 @@
 use bitcoin::hashes::sha256d;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use crate::parser::script;
+
 pub trait Coin {
     fn name(&self) -> String;
 
@@ -12,6 +15,27 @@ pub trait Coin {
     fn genesis(&self) -> sha256d::Hash;
 
     fn default_folder(&self) -> PathBuf;
+
+    /// Network used to encode addresses
+    fn network(&self) -> bitcoin::Network;
+
+    /// Height from which the coinbase must commit to the block height (BIP34)
+    fn bip34_height(&self) -> u64;
+
+    /// Height from which segregated witness rules are enforced (BIP141)
+    fn segwit_height(&self) -> u64;
+
+    /// Height from which strict DER signatures are enforced (BIP66)
+    fn bip66_height(&self) -> u64;
+
+    /// Height from which OP_CHECKLOCKTIMEVERIFY is enforced (BIP65)
+    fn bip65_height(&self) -> u64;
+
+    /// Height from which OP_CHECKSEQUENCEVERIFY is enforced (BIP112)
+    fn csv_height(&self) -> u64;
+
+    /// Blocks which are validated with a reduced set of script flags
+    fn script_flag_exceptions(&self) -> HashMap<sha256d::Hash, u32>;
 }
 
 pub struct Bitcoin;
@@ -34,6 +58,42 @@ impl Coin for Bitcoin {
     fn default_folder(&self) -> PathBuf {
         Path::new(".bitcoin").join("blocks")
     }
+    fn network(&self) -> bitcoin::Network {
+        bitcoin::Network::Bitcoin
+    }
+    fn bip34_height(&self) -> u64 {
+        227_931
+    }
+    fn segwit_height(&self) -> u64 {
+        481_824
+    }
+    fn bip66_height(&self) -> u64 {
+        363_725
+    }
+    fn bip65_height(&self) -> u64 {
+        388_381
+    }
+    fn csv_height(&self) -> u64 {
+        419_328
+    }
+    fn script_flag_exceptions(&self) -> HashMap<sha256d::Hash, u32> {
+        HashMap::from([
+            (
+                sha256d::Hash::from_str(
+                    "00000000000002dc756eebf4f49723ed8d30cc28a5f108eb94b1ba88ac4f9c22",
+                )
+                .unwrap(),
+                script::VERIFY_NONE,
+            ),
+            (
+                sha256d::Hash::from_str(
+                    "0000000000000000000f14c35b2d841e986ab5441de8c585d5ffe55ea1e395ad",
+                )
+                .unwrap(),
+                script::VERIFY_P2SH | script::VERIFY_WITNESS,
+            ),
+        ])
+    }
 }
 
 impl Coin for TestNet3 {
@@ -53,6 +113,33 @@ impl Coin for TestNet3 {
     fn default_folder(&self) -> PathBuf {
         Path::new(".bitcoin").join("testnet3")
     }
+    fn network(&self) -> bitcoin::Network {
+        bitcoin::Network::Testnet
+    }
+    fn bip34_height(&self) -> u64 {
+        21_111
+    }
+    fn segwit_height(&self) -> u64 {
+        834_624
+    }
+    fn bip66_height(&self) -> u64 {
+        330_776
+    }
+    fn bip65_height(&self) -> u64 {
+        581_885
+    }
+    fn csv_height(&self) -> u64 {
+        770_112
+    }
+    fn script_flag_exceptions(&self) -> HashMap<sha256d::Hash, u32> {
+        HashMap::from([(
+            sha256d::Hash::from_str(
+                "00000000dd30457c001f4095d208cc1296b0eed002427aa599874af7a432b105",
+            )
+            .unwrap(),
+            script::VERIFY_NONE,
+        )])
+    }
 }
 
 #[derive(Clone)]
@@ -62,6 +149,13 @@ pub struct CoinType {
     pub version_id: u8,
     pub genesis_hash: sha256d::Hash,
     pub default_folder: PathBuf,
+    pub network: bitcoin::Network,
+    pub bip34_height: u64,
+    pub segwit_height: u64,
+    pub bip66_height: u64,
+    pub bip65_height: u64,
+    pub csv_height: u64,
+    pub script_flag_exceptions: HashMap<sha256d::Hash, u32>,
 }
 
 impl Default for CoinType {
@@ -78,6 +172,13 @@ impl<T: Coin> From<T> for CoinType {
             version_id: coin.version_id(),
             genesis_hash: coin.genesis(),
             default_folder: coin.default_folder(),
+            network: coin.network(),
+            bip34_height: coin.bip34_height(),
+            segwit_height: coin.segwit_height(),
+            bip66_height: coin.bip66_height(),
+            bip65_height: coin.bip65_height(),
+            csv_height: coin.csv_height(),
+            script_flag_exceptions: coin.script_flag_exceptions(),
         }
     }
 }
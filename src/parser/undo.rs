@@ -0,0 +1,231 @@
+use bitcoin::blockdata::opcodes::all::{
+    OP_CHECKSIG, OP_DUP, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160, OP_RETURN,
+};
+
+use crate::parser::reader::BlockchainRead;
+
+/// Number of special script types used by Bitcoin Core's script compression
+const SPECIAL_SCRIPTS: u64 = 6;
+
+/// Maximum size of a spendable script in bytes
+const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// Undo data of a block as stored in the rev*.dat files.
+/// Contains the outputs spent by every transaction except the coinbase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockUndo {
+    pub txdata: Vec<TxUndo>,
+}
+
+/// Outputs spent by a single transaction, in input order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxUndo {
+    pub prevouts: Vec<SpentOutput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpentOutput {
+    /// Height of the block which created the output
+    pub height: u32,
+    pub is_coinbase: bool,
+    pub txout: bitcoin::TxOut,
+}
+
+impl BlockUndo {
+    pub fn read_from<R: std::io::Read + ?Sized>(reader: &mut R) -> anyhow::Result<Self> {
+        // counts of corrupt data may be arbitrarily large, so nothing is allocated up front
+        let tx_count = reader.read_compact_size()?;
+        let mut txdata = Vec::new();
+        for _ in 0..tx_count {
+            let prevout_count = reader.read_compact_size()?;
+            let mut prevouts = Vec::new();
+            for _ in 0..prevout_count {
+                prevouts.push(SpentOutput::read_from(reader)?);
+            }
+            txdata.push(TxUndo { prevouts });
+        }
+        Ok(Self { txdata })
+    }
+
+    /// Returns the spent outputs of the transaction at `index` in the block.
+    /// The coinbase (index 0) has no undo data.
+    #[must_use]
+    pub fn prevouts(&self, index: usize) -> Option<&[SpentOutput]> {
+        index
+            .checked_sub(1)
+            .and_then(|i| self.txdata.get(i))
+            .map(|tx| tx.prevouts.as_slice())
+    }
+
+    /// Sum of all spent output values
+    #[must_use]
+    pub fn total_spent(&self) -> u64 {
+        self.txdata
+            .iter()
+            .flat_map(|tx| tx.prevouts.iter().map(|p| p.txout.value))
+            .sum()
+    }
+}
+
+impl SpentOutput {
+    fn read_from<R: std::io::Read + ?Sized>(reader: &mut R) -> anyhow::Result<Self> {
+        let code = reader.read_varint()?;
+        let height = u32::try_from(code >> 1)?;
+        if height > 0 {
+            // legacy transaction version, always zero in recent versions
+            reader.read_varint()?;
+        }
+        let value = decompress_amount(reader.read_varint()?);
+        let script_pubkey = read_compressed_script(reader)?;
+        Ok(Self {
+            height,
+            is_coinbase: code & 1 == 1,
+            txout: bitcoin::TxOut {
+                value,
+                script_pubkey,
+            },
+        })
+    }
+}
+
+/// Reverses `CompressAmount` from Bitcoin Core.
+fn decompress_amount(mut x: u64) -> u64 {
+    if x == 0 {
+        return 0;
+    }
+    x -= 1;
+    let mut e = x % 10;
+    x /= 10;
+    let mut n = if e < 9 {
+        let d = (x % 9) + 1;
+        x /= 9;
+        x * 10 + d
+    } else {
+        x + 1
+    };
+    while e > 0 {
+        n *= 10;
+        e -= 1;
+    }
+    n
+}
+
+/// Reverses `ScriptCompression` from Bitcoin Core.
+fn read_compressed_script<R: std::io::Read + ?Sized>(
+    reader: &mut R,
+) -> anyhow::Result<bitcoin::ScriptBuf> {
+    let size = reader.read_varint()?;
+    let script = match size {
+        0x00 => {
+            let mut hash = [0u8; 20];
+            reader.read_exact(&mut hash)?;
+            [
+                &[OP_DUP.to_u8(), OP_HASH160.to_u8(), 20][..],
+                &hash,
+                &[OP_EQUALVERIFY.to_u8(), OP_CHECKSIG.to_u8()],
+            ]
+            .concat()
+        }
+        0x01 => {
+            let mut hash = [0u8; 20];
+            reader.read_exact(&mut hash)?;
+            [&[OP_HASH160.to_u8(), 20][..], &hash, &[OP_EQUAL.to_u8()]].concat()
+        }
+        0x02 | 0x03 => {
+            let mut x = [0u8; 32];
+            reader.read_exact(&mut x)?;
+            [&[33, u8::try_from(size)?][..], &x, &[OP_CHECKSIG.to_u8()]].concat()
+        }
+        0x04 | 0x05 => {
+            let mut key = [0u8; 33];
+            key[0] = u8::try_from(size - 2)?;
+            reader.read_exact(&mut key[1..])?;
+            let pubkey = bitcoin::secp256k1::PublicKey::from_slice(&key)?;
+            [
+                &[65][..],
+                &pubkey.serialize_uncompressed(),
+                &[OP_CHECKSIG.to_u8()],
+            ]
+            .concat()
+        }
+        _ => {
+            let len = size - SPECIAL_SCRIPTS;
+            if len > MAX_SCRIPT_SIZE as u64 {
+                // oversized scripts are unspendable, Bitcoin Core skips them and stores OP_RETURN
+                let mut oversized = std::io::Read::take(&mut *reader, len);
+                let skipped = std::io::copy(&mut oversized, &mut std::io::sink())?;
+                if skipped < len {
+                    return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+                }
+                vec![OP_RETURN.to_u8()]
+            } else {
+                let mut script = vec![0u8; usize::try_from(len)?];
+                reader.read_exact(&mut script)?;
+                script
+            }
+        }
+    };
+    Ok(bitcoin::ScriptBuf::from(script))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompress_amount() {
+        assert_eq!(decompress_amount(0x0), 0);
+        assert_eq!(decompress_amount(0x1), 1);
+        assert_eq!(decompress_amount(0x7), 1_000_000);
+        assert_eq!(decompress_amount(0x9), 100_000_000);
+        assert_eq!(decompress_amount(0x32), 5_000_000_000);
+        assert_eq!(decompress_amount(0x0140_6f40), 2_100_000_000_000_000);
+    }
+
+    #[test]
+    fn test_read_block_undo() {
+        // one transaction spending a P2PKH output of 50 BTC created by a coinbase at height 9
+        let mut data = vec![0x01, 0x01, 0x13, 0x00, 0x32, 0x00];
+        data.extend_from_slice(&[0xab; 20]);
+        let undo = BlockUndo::read_from(&mut std::io::Cursor::new(data)).unwrap();
+
+        let prevouts = undo.prevouts(1).unwrap();
+        assert_eq!(prevouts.len(), 1);
+        assert_eq!(prevouts[0].height, 9);
+        assert!(prevouts[0].is_coinbase);
+        assert_eq!(prevouts[0].txout.value, 5_000_000_000);
+        assert!(prevouts[0].txout.script_pubkey.is_p2pkh());
+        assert!(undo.prevouts(0).is_none());
+        assert_eq!(undo.total_spent(), 5_000_000_000);
+    }
+
+    #[test]
+    fn test_read_corrupt_block_undo() {
+        // VARINT of the first spent output overflowing 64 bits
+        let mut data = vec![0x01, 0x01];
+        data.extend_from_slice(&[0xff; 10]);
+        let err = BlockUndo::read_from(&mut std::io::Cursor::new(data)).unwrap_err();
+        assert!(err.to_string().contains("varint too large"));
+
+        // transaction count of 2^64 - 1
+        let data = [0xff; 9];
+        assert!(BlockUndo::read_from(&mut std::io::Cursor::new(data)).is_err());
+    }
+
+    #[test]
+    fn test_read_oversized_script() {
+        // VARINT of MAX_SCRIPT_SIZE + 1 + SPECIAL_SCRIPTS, followed by the script and a trailing byte
+        let mut data = vec![0xcd, 0x17];
+        data.extend_from_slice(&[0x51; MAX_SCRIPT_SIZE + 1]);
+        data.push(0xff);
+        let mut reader = std::io::Cursor::new(data);
+        let script = read_compressed_script(&mut reader).unwrap();
+        assert_eq!(script.as_bytes(), &[OP_RETURN.to_u8()]);
+        let mut rest = Vec::new();
+        std::io::Read::read_to_end(&mut reader, &mut rest).unwrap();
+        assert_eq!(rest, [0xff]);
+
+        let truncated = vec![0xcd, 0x17, 0x51];
+        assert!(read_compressed_script(&mut std::io::Cursor::new(truncated)).is_err());
+    }
+}
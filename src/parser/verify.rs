@@ -0,0 +1,469 @@
+use std::collections::HashSet;
+
+use bitcoin::blockdata::constants::{
+    MAX_BLOCK_SIGOPS_COST, MAX_BLOCK_WEIGHT, MAX_MONEY, WITNESS_SCALE_FACTOR,
+};
+use bitcoin::blockdata::opcodes::all::{
+    OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY, OP_CHECKSIG, OP_CHECKSIGVERIFY,
+};
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::hashes::Hash;
+
+use crate::parser::types::CoinType;
+
+/// Prefix of the coinbase output carrying the witness commitment (BIP141)
+const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// Category of an inconsistency found while verifying a block
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IssueKind {
+    /// Block hash doesn't match the block index (or the genesis hash)
+    Hash,
+    Merkle,
+    /// prev_blockhash doesn't point to the previous block in the index
+    Linkage,
+    /// Magic bytes or size preceding the block are wrong, or the block can't be decoded
+    Framing,
+    /// Number of transactions doesn't match the block index
+    TxCount,
+    /// Any other consensus rule checked by `--verify`
+    Consensus,
+    /// Input script failed to validate against its prevout
+    Script,
+}
+
+impl IssueKind {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IssueKind::Hash => "hash",
+            IssueKind::Merkle => "merkle",
+            IssueKind::Linkage => "linkage",
+            IssueKind::Framing => "framing",
+            IssueKind::TxCount => "tx_count",
+            IssueKind::Consensus => "consensus",
+            IssueKind::Script => "script",
+        }
+    }
+}
+
+impl std::fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationIssue {
+    pub height: u64,
+    pub block_hash: Option<bitcoin::BlockHash>,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+impl VerificationIssue {
+    #[must_use]
+    pub fn new(
+        height: u64,
+        block_hash: Option<bitcoin::BlockHash>,
+        kind: IssueKind,
+        message: String,
+    ) -> Self {
+        Self {
+            height,
+            block_hash,
+            kind,
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for VerificationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] height {}: {}",
+            self.kind, self.height, self.message
+        )
+    }
+}
+
+/// Context free block checks, roughly following `CheckBlock` in Bitcoin Core.
+/// The merkle root is checked separately.
+pub fn check_block(block: &bitcoin::Block) -> anyhow::Result<()> {
+    if block.txdata.is_empty() {
+        anyhow::bail!("block {} has no transactions", &block.block_hash());
+    }
+    let max_size = MAX_BLOCK_WEIGHT as usize / WITNESS_SCALE_FACTOR;
+    if block.txdata.len() > max_size || block.strippedsize() > max_size {
+        anyhow::bail!("block {} exceeds the size limit", &block.block_hash());
+    }
+    if !block.txdata[0].is_coin_base() {
+        anyhow::bail!(
+            "first transaction of block {} is not a coinbase",
+            &block.block_hash()
+        );
+    }
+    if let Some(tx) = block.txdata.iter().skip(1).find(|tx| tx.is_coin_base()) {
+        anyhow::bail!(
+            "block {} contains more than one coinbase: {}",
+            &block.block_hash(),
+            tx.txid()
+        );
+    }
+    for tx in &block.txdata {
+        check_transaction(tx)?;
+    }
+    let sigops: usize = block.txdata.iter().map(legacy_sigop_count).sum();
+    if sigops * WITNESS_SCALE_FACTOR > MAX_BLOCK_SIGOPS_COST as usize {
+        anyhow::bail!(
+            "block {} exceeds the sigop limit: {}",
+            &block.block_hash(),
+            sigops
+        );
+    }
+    Ok(())
+}
+
+/// Signature operations in the scripts of `tx` counted like `GetLegacySigOpCount` in
+/// Bitcoin Core, where every multisig counts as 20. Counting stops at an invalid push.
+fn legacy_sigop_count(tx: &bitcoin::Transaction) -> usize {
+    let scripts = tx
+        .input
+        .iter()
+        .map(|i| i.script_sig.as_script())
+        .chain(tx.output.iter().map(|o| o.script_pubkey.as_script()));
+    scripts
+        .map(|script| {
+            script
+                .instructions()
+                .map_while(Result::ok)
+                .map(|instruction| match instruction {
+                    Instruction::Op(OP_CHECKSIG | OP_CHECKSIGVERIFY) => 1,
+                    Instruction::Op(OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY) => 20,
+                    _ => 0,
+                })
+                .sum::<usize>()
+        })
+        .sum()
+}
+
+/// Structural transaction checks, roughly following `CheckTransaction` in Bitcoin Core.
+pub fn check_transaction(tx: &bitcoin::Transaction) -> anyhow::Result<()> {
+    if tx.input.is_empty() {
+        anyhow::bail!("transaction {} has no inputs", tx.txid());
+    }
+    if tx.output.is_empty() {
+        anyhow::bail!("transaction {} has no outputs", tx.txid());
+    }
+    if tx.strippedsize() * WITNESS_SCALE_FACTOR > MAX_BLOCK_WEIGHT as usize {
+        anyhow::bail!("transaction {} exceeds the size limit", tx.txid());
+    }
+
+    let mut total: u64 = 0;
+    for output in &tx.output {
+        total = total.saturating_add(output.value);
+        if output.value > MAX_MONEY || total > MAX_MONEY {
+            anyhow::bail!("transaction {} has an output value out of range", tx.txid());
+        }
+    }
+
+    let mut prevouts = HashSet::with_capacity(tx.input.len());
+    if !tx.input.iter().all(|i| prevouts.insert(i.previous_output)) {
+        anyhow::bail!("transaction {} spends an input twice", tx.txid());
+    }
+
+    if tx.is_coin_base() {
+        let len = tx.input[0].script_sig.len();
+        if !(2..=100).contains(&len) {
+            anyhow::bail!(
+                "coinbase {} has a scriptSig of invalid length {}",
+                tx.txid(),
+                len
+            );
+        }
+    } else if tx.input.iter().any(|i| i.previous_output.is_null()) {
+        anyhow::bail!("transaction {} spends a null prevout", tx.txid());
+    }
+    Ok(())
+}
+
+/// Block checks depending on the height and the deployments active at this height,
+/// roughly following `ContextualCheckBlock` in Bitcoin Core.
+pub fn check_contextual(
+    block: &bitcoin::Block,
+    height: u64,
+    coin: &CoinType,
+) -> anyhow::Result<()> {
+    if height >= coin.bip34_height {
+        match block.bip34_block_height() {
+            Ok(encoded) if encoded == height => {}
+            Ok(encoded) => anyhow::bail!(
+                "coinbase of block {} commits to height {}, expected {}",
+                &block.block_hash(),
+                encoded,
+                height
+            ),
+            Err(e) => anyhow::bail!(
+                "coinbase of block {} has no valid BIP34 height: {:?}",
+                &block.block_hash(),
+                e
+            ),
+        }
+    }
+
+    if height >= coin.segwit_height {
+        check_witness_commitment(block)?;
+    } else if has_witness(block) {
+        anyhow::bail!(
+            "block {} contains witness data before segwit activation",
+            &block.block_hash()
+        );
+    }
+
+    if block.weight().to_wu() > u64::from(MAX_BLOCK_WEIGHT) {
+        anyhow::bail!(
+            "block {} exceeds the weight limit: {}",
+            &block.block_hash(),
+            block.weight()
+        );
+    }
+    Ok(())
+}
+
+/// Validates the witness commitment in the coinbase. If the coinbase doesn't commit,
+/// no transaction in the block may carry witness data.
+fn check_witness_commitment(block: &bitcoin::Block) -> anyhow::Result<()> {
+    let coinbase = &block.txdata[0];
+    let Some(commitment) = witness_commitment(coinbase) else {
+        if has_witness(block) {
+            anyhow::bail!(
+                "block {} contains witness data but no witness commitment",
+                &block.block_hash()
+            );
+        }
+        return Ok(());
+    };
+
+    let witness = &coinbase.input[0].witness;
+    let reserved_value = match witness.nth(0) {
+        Some(value) if witness.len() == 1 && value.len() == 32 => value,
+        _ => anyhow::bail!(
+            "coinbase of block {} has an invalid witness reserved value",
+            &block.block_hash()
+        ),
+    };
+    let witness_root = block
+        .witness_root()
+        .ok_or_else(|| anyhow::anyhow!("unable to compute witness root"))?;
+    let expected = bitcoin::Block::compute_witness_commitment(&witness_root, reserved_value);
+    if expected.as_byte_array()[..] != commitment[6..38] {
+        anyhow::bail!(
+            "witness commitment of block {} doesn't match!",
+            &block.block_hash()
+        );
+    }
+    Ok(())
+}
+
+/// Returns the script of the last coinbase output carrying a witness commitment.
+pub(crate) fn witness_commitment(coinbase: &bitcoin::Transaction) -> Option<&[u8]> {
+    coinbase
+        .output
+        .iter()
+        .rev()
+        .map(|o| o.script_pubkey.as_bytes())
+        .find(|s| s.len() >= 38 && s[0..6] == WITNESS_COMMITMENT_HEADER)
+}
+
+fn has_witness(block: &bitcoin::Block) -> bool {
+    block
+        .txdata
+        .iter()
+        .any(|tx| tx.input.iter().any(|i| !i.witness.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::Bitcoin;
+
+    fn genesis() -> bitcoin::Block {
+        bitcoin::blockdata::constants::genesis_block(bitcoin::network::constants::Network::Bitcoin)
+    }
+
+    #[test]
+    fn test_genesis_passes() {
+        let block = genesis();
+        check_block(&block).unwrap();
+        check_contextual(&block, 0, &CoinType::from(Bitcoin)).unwrap();
+    }
+
+    #[test]
+    fn test_unexpected_witness() {
+        let mut block = genesis();
+        block.txdata[0].input[0].witness.push([0u8; 32]);
+        assert!(check_contextual(&block, 0, &CoinType::from(Bitcoin)).is_err());
+    }
+
+    #[test]
+    fn test_bip34_height() {
+        let block = genesis();
+        let mut coin = CoinType::from(Bitcoin);
+        coin.bip34_height = 0;
+        assert!(check_contextual(&block, 0, &coin).is_err());
+    }
+
+    const SEGWIT_HEIGHT: u64 = 481_824;
+
+    /// Block at the segwit activation height with a coinbase and a transaction spending a
+    /// segwit output, committing to its witness
+    fn segwit_block() -> bitcoin::Block {
+        let coinbase = bitcoin::Transaction {
+            version: 1,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint::null(),
+                script_sig: bitcoin::blockdata::script::Builder::new()
+                    .push_int(i64::try_from(SEGWIT_HEIGHT).unwrap())
+                    .into_script(),
+                sequence: bitcoin::Sequence::MAX,
+                witness: bitcoin::Witness::from_slice(&[[0u8; 32]]),
+            }],
+            output: vec![bitcoin::TxOut {
+                value: 1_250_000_000,
+                script_pubkey: bitcoin::ScriptBuf::new(),
+            }],
+        };
+        let spend = bitcoin::Transaction {
+            version: 2,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint::new(genesis().txdata[0].txid(), 0),
+                script_sig: bitcoin::ScriptBuf::new(),
+                sequence: bitcoin::Sequence::MAX,
+                witness: bitcoin::Witness::from_slice(&[vec![1u8; 72], vec![2u8; 33]]),
+            }],
+            output: vec![bitcoin::TxOut {
+                value: 1_000,
+                script_pubkey: bitcoin::ScriptBuf::new(),
+            }],
+        };
+        let mut header = genesis().header;
+        // BIP34 heights are only encoded in blocks of version 2 and later
+        header.version = bitcoin::block::Version::from_consensus(0x2000_0000);
+        let mut block = bitcoin::Block {
+            header,
+            txdata: vec![coinbase, spend],
+        };
+        commit_witness(&mut block);
+        block
+    }
+
+    /// Replaces the witness commitment of the coinbase with the one of the block's witnesses
+    fn commit_witness(block: &mut bitcoin::Block) {
+        block.txdata[0]
+            .output
+            .retain(|output| witness_commitment_of(output).is_none());
+        let witness_root = block.witness_root().unwrap();
+        let reserved_value = block.txdata[0].input[0].witness.nth(0).unwrap().to_vec();
+        let commitment = bitcoin::Block::compute_witness_commitment(&witness_root, &reserved_value);
+        let mut script = WITNESS_COMMITMENT_HEADER.to_vec();
+        script.extend_from_slice(commitment.as_byte_array());
+        block.txdata[0].output.push(bitcoin::TxOut {
+            value: 0,
+            script_pubkey: bitcoin::ScriptBuf::from(script),
+        });
+    }
+
+    fn witness_commitment_of(output: &bitcoin::TxOut) -> Option<&[u8]> {
+        let script = output.script_pubkey.as_bytes();
+        (script.len() >= 38 && script[0..6] == WITNESS_COMMITMENT_HEADER).then_some(script)
+    }
+
+    #[test]
+    fn test_witness_commitment() {
+        let coin = CoinType::from(Bitcoin);
+        let block = segwit_block();
+        check_block(&block).unwrap();
+        check_contextual(&block, SEGWIT_HEIGHT, &coin).unwrap();
+
+        let mut corrupted = block.clone();
+        corrupted.txdata[1].input[0].witness = bitcoin::Witness::from_slice(&[[3u8; 72]]);
+        let err = check_contextual(&corrupted, SEGWIT_HEIGHT, &coin).unwrap_err();
+        assert!(err.to_string().contains("doesn't match"));
+
+        let mut uncommitted = block.clone();
+        uncommitted.txdata[0].output.pop();
+        let err = check_contextual(&uncommitted, SEGWIT_HEIGHT, &coin).unwrap_err();
+        assert!(err.to_string().contains("no witness commitment"));
+
+        let mut invalid_reserved_value = block.clone();
+        invalid_reserved_value.txdata[0].input[0].witness =
+            bitcoin::Witness::from_slice(&[[0u8; 31]]);
+        commit_witness(&mut invalid_reserved_value);
+        let err = check_contextual(&invalid_reserved_value, SEGWIT_HEIGHT, &coin).unwrap_err();
+        assert!(err.to_string().contains("witness reserved value"));
+
+        // witness data is checked according to the segwit activation height
+        assert!(check_contextual(&block, SEGWIT_HEIGHT - 1, &coin).is_err());
+    }
+
+    #[test]
+    fn test_weight_limit() {
+        let coin = CoinType::from(Bitcoin);
+        let with_witness = |len: u64| {
+            let mut block = segwit_block();
+            block.txdata[1].input[0].witness =
+                bitcoin::Witness::from_slice(&[vec![0u8; len as usize]]);
+            commit_witness(&mut block);
+            block
+        };
+        // witness bytes count once towards the weight
+        let len = 100_000;
+        let len = len + u64::from(MAX_BLOCK_WEIGHT) - with_witness(len).weight().to_wu();
+        let block = with_witness(len);
+        assert_eq!(block.weight().to_wu(), u64::from(MAX_BLOCK_WEIGHT));
+        check_contextual(&block, SEGWIT_HEIGHT, &coin).unwrap();
+
+        let block = with_witness(len + 1);
+        let err = check_contextual(&block, SEGWIT_HEIGHT, &coin).unwrap_err();
+        assert!(err.to_string().contains("weight limit"));
+    }
+
+    #[test]
+    fn test_sigop_limit() {
+        let limit = MAX_BLOCK_SIGOPS_COST as usize / WITNESS_SCALE_FACTOR;
+        let checksigs = |count: usize| bitcoin::ScriptBuf::from(vec![OP_CHECKSIG.to_u8(); count]);
+        let mut block = segwit_block();
+        // the segwit block itself has no sigops
+        block.txdata[1].output[0].script_pubkey = checksigs(limit - 20);
+        block.txdata[1].output.push(bitcoin::TxOut {
+            value: 0,
+            script_pubkey: bitcoin::ScriptBuf::from(vec![OP_CHECKMULTISIG.to_u8()]),
+        });
+        check_block(&block).unwrap();
+
+        block.txdata[1].output[0].script_pubkey = checksigs(limit - 19);
+        let err = check_block(&block).unwrap_err();
+        assert!(err.to_string().contains("sigop limit"));
+
+        // the bytes of a push aren't counted
+        let mut push = vec![0x4d, 0x10, 0x27];
+        push.extend_from_slice(&vec![OP_CHECKSIG.to_u8(); 10_000]);
+        block.txdata[1].output[0].script_pubkey = bitcoin::ScriptBuf::from(push);
+        check_block(&block).unwrap();
+    }
+
+    #[test]
+    fn test_transaction_checks() {
+        let mut tx = genesis().txdata[0].clone();
+        check_transaction(&tx).unwrap();
+
+        tx.output[0].value = MAX_MONEY + 1;
+        assert!(check_transaction(&tx).is_err());
+
+        tx.output.clear();
+        assert!(check_transaction(&tx).is_err());
+    }
+}
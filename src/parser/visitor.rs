@@ -0,0 +1,68 @@
+use crate::parser::undo::BlockUndo;
+use crate::parser::verify::VerificationIssue;
+
+/// A block together with everything the parser computed for it
+pub struct BlockData<'a> {
+    pub height: u64,
+    pub block: &'a bitcoin::Block,
+    /// Spent outputs from the `rev*.dat` files, `None` if they couldn't be read
+    pub undo: Option<&'a BlockUndo>,
+    /// Cumulative work of the chain up to and including this block
+    pub chainwork: bitcoin::Work,
+    /// Estimated hashrate for every window given with `--hashrate-window`
+    pub hashrates: &'a [(u64, f64)],
+    /// Median time of the last 11 blocks up to and including this block
+    pub median_time: u32,
+    /// Height of the best block known to the node, also if it's beyond `--end`
+    pub tip_height: u64,
+    pub next_hash: Option<bitcoin::BlockHash>,
+}
+
+/// Callbacks invoked by the [`BlockchainParser`](super::BlockchainParser) for every
+/// block in height order. Returning an error stops the parser.
+pub trait BlockVisitor {
+    /// Called before `on_start` if the parser continues after the checkpoint of an earlier run,
+    /// `checkpoint` is its height. Returns the first height the visitor needs, which may be
+    /// lower to write output again that the earlier run left incomplete.
+    fn on_resume(&mut self, checkpoint: u64) -> anyhow::Result<u64> {
+        Ok(checkpoint + 1)
+    }
+
+    /// Called once before the first block, `height` is the first height to be parsed.
+    fn on_start(&mut self, _height: u64) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn on_header(
+        &mut self,
+        _header: &bitcoin::blockdata::block::Header,
+        _height: u64,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn on_block(&mut self, data: &BlockData) -> anyhow::Result<()>;
+
+    /// Called for every inconsistency found with `--verify=report`, before the block
+    /// it was found in is visited.
+    fn on_issue(&mut self, _issue: &VerificationIssue) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called after the last block for every block of the index which isn't part of the active
+    /// chain, unless the parser was interrupted. `block` is `None` if its data isn't on disk.
+    fn on_stale_block(
+        &mut self,
+        _header: &bitcoin::blockdata::block::Header,
+        _height: u64,
+        _block: Option<&bitcoin::Block>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called once after the last block, also if the parser was interrupted.
+    /// `height` is the last height that was processed.
+    fn on_complete(&mut self, _height: u64) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use std::io::Write;
+
+use crate::db::Db;
+use crate::BlockHeightRange;
+
+/// Canned query of the `query` subcommand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    /// Totals and averages over the blocks of the range
+    Summary,
+    /// One row per block with its hash, time, size and pool
+    Blocks,
+    /// Number of blocks per pool
+    Pools,
+    /// Inconsistencies recorded by `parse --verify=report`
+    Issues,
+    /// Blocks of the index which aren't part of the active chain
+    Stale,
+}
+
+impl Query {
+    pub const ALL: [&'static str; 5] = ["summary", "blocks", "pools", "issues", "stale"];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Summary => "summary",
+            Self::Blocks => "blocks",
+            Self::Pools => "pools",
+            Self::Issues => "issues",
+            Self::Stale => "stale",
+        }
+    }
+}
+
+impl std::str::FromStr for Query {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "summary" => Ok(Self::Summary),
+            "blocks" => Ok(Self::Blocks),
+            "pools" => Ok(Self::Pools),
+            "issues" => Ok(Self::Issues),
+            "stale" => Ok(Self::Stale),
+            _ => anyhow::bail!("unknown query '{s}'"),
+        }
+    }
+}
+
+pub struct QueryOptions {
+    pub db_url: String,
+    pub query: Query,
+    /// Heights the query is restricted to, all blocks of the database by default
+    pub range: BlockHeightRange,
+}
+
+/// Runs the query against the existing database at `options.db_url` and writes the result as
+/// tab-separated rows with a header to `out`.
+pub fn run(options: &QueryOptions, out: &mut impl Write) -> anyhow::Result<()> {
+    let path = options
+        .db_url
+        .strip_prefix(crate::db::DUCKDB_PREFIX)
+        .unwrap_or(&options.db_url);
+    // opening a missing SQLite or DuckDB file would create an empty database
+    if !path.contains("://") && !std::path::Path::new(path).exists() {
+        anyhow::bail!("database {path} doesn't exist");
+    }
+    let db = Db::open_existing(&options.db_url)?;
+    let heights = i32::try_from(options.range.start)?
+        ..=options.range.end.map_or(Ok(i32::MAX), i32::try_from)?;
+
+    match options.query {
+        Query::Summary => {
+            let aggregates = db.block_aggregates(heights)?;
+            writeln!(
+                out,
+                "blocks\ttransactions\tturnover\tminer_reward\tavg_size\tavg_weight"
+            )?;
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{:.1}\t{:.1}",
+                aggregates.block_count,
+                aggregates.tx_count,
+                aggregates.turnover,
+                aggregates.miner_reward,
+                aggregates.avg_size,
+                aggregates.avg_weight
+            )?;
+        }
+        Query::Blocks => {
+            writeln!(out, "height\thash\ttime\ttx_count\tsize\tweight\tpool")?;
+            for block in db.blocks_range(heights)? {
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    block.height,
+                    block.hash,
+                    block.time,
+                    block.tx_count,
+                    block.size,
+                    block.weight,
+                    block.pool.unwrap_or_default()
+                )?;
+            }
+        }
+        Query::Pools => {
+            writeln!(out, "pool\tblocks")?;
+            for pool in db.pool_counts(heights)? {
+                let name = pool.pool.as_deref().unwrap_or("unknown");
+                writeln!(out, "{name}\t{}", pool.block_count)?;
+            }
+        }
+        Query::Issues => {
+            writeln!(out, "height\tblock_hash\tkind\tmessage")?;
+            for issue in db.verification_issues()? {
+                if heights.contains(&issue.height) {
+                    writeln!(
+                        out,
+                        "{}\t{}\t{}\t{}",
+                        issue.height,
+                        issue.block_hash.unwrap_or_default(),
+                        issue.kind,
+                        issue.message
+                    )?;
+                }
+            }
+        }
+        Query::Stale => {
+            writeln!(
+                out,
+                "height\thash\tprev_hash\ttime\ttx_count\tpool\tminer_tag"
+            )?;
+            for block in db.stale_blocks()? {
+                if heights.contains(&block.height) {
+                    writeln!(
+                        out,
+                        "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                        block.height,
+                        block.block_hash,
+                        block.prev_hash,
+                        block.time,
+                        block.tx_count.map(|n| n.to_string()).unwrap_or_default(),
+                        block.pool.unwrap_or_default(),
+                        // tags are separated by newlines in the table
+                        block.miner_tag.unwrap_or_default().replace('\n', " "),
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
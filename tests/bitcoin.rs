@@ -10,7 +10,7 @@ fn parser() -> bitcoin_blockparser::parser::BlockchainParser {
 
 #[test]
 fn test_bitcoin_genesis() {
-    let genesis = storage().get_block(0).unwrap();
+    let genesis = storage().get_block(0).unwrap().unwrap();
     assert_eq!(
         genesis,
         bitcoin::blockdata::constants::genesis_block(bitcoin::network::constants::Network::Bitcoin)
@@ -92,14 +92,14 @@ fn test_genesis_header() {
 fn test_blockdata_parsing() {
     let mut storage = storage();
     for height in 0..=169 {
-        let block = storage.get_block(height).unwrap();
+        let block = storage.get_block(height).unwrap().unwrap();
         assert_eq!(block.txdata.len(), 1);
     }
-    let first_tx_block = storage.get_block(170).unwrap();
+    let first_tx_block = storage.get_block(170).unwrap().unwrap();
     assert_eq!(first_tx_block.txdata.len(), 2);
 
     let tx = first_tx_block.txdata.get(1).unwrap();
-    let to_hal_finney = tx.output.get(0).unwrap();
+    let to_hal_finney = tx.output.first().unwrap();
     assert_eq!(to_hal_finney.value, 10 * bitcoin::Amount::ONE_BTC.to_sat());
     assert!(to_hal_finney.script_pubkey.is_p2pk());
     assert_eq!(
@@ -108,6 +108,32 @@ fn test_blockdata_parsing() {
     );
 }
 
+#[test]
+fn test_random_access() {
+    let mut storage = storage();
+    for height in [120, 5, 121, 170, 0, 5] {
+        let header = storage.get_header(height).unwrap();
+        let block = storage.get_block(height).unwrap().unwrap();
+        assert_eq!(header, block.header);
+        if height > 0 {
+            let prev = storage.get_header(height - 1).unwrap();
+            assert_eq!(block.header.prev_blockhash, prev.block_hash());
+        }
+    }
+}
+
+#[test]
+fn test_empty_index() {
+    let options = common::options("bitcoin", 170);
+    let index = options.blockchain_dir.join("index");
+    std::fs::remove_dir_all(&index).unwrap();
+    std::fs::create_dir(&index).unwrap();
+    let err = bitcoin_blockparser::parser::chain::ChainStorage::new(&options)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("has no active chain"));
+}
+
 #[test]
 fn test_headers() {
     let mut storage = storage();
@@ -135,3 +161,1280 @@ fn test_blocks_db() {
     );
     assert!(parser.db().block(75).unwrap().pool.is_none());
 }
+
+/// Records the heights of all callbacks
+#[derive(Default)]
+struct RecordingVisitor {
+    calls: std::rc::Rc<std::cell::RefCell<Vec<(&'static str, u64)>>>,
+}
+
+impl bitcoin_blockparser::parser::visitor::BlockVisitor for RecordingVisitor {
+    fn on_start(&mut self, height: u64) -> anyhow::Result<()> {
+        self.calls.borrow_mut().push(("start", height));
+        Ok(())
+    }
+
+    fn on_header(
+        &mut self,
+        _header: &bitcoin::blockdata::block::Header,
+        height: u64,
+    ) -> anyhow::Result<()> {
+        self.calls.borrow_mut().push(("header", height));
+        Ok(())
+    }
+
+    fn on_block(
+        &mut self,
+        data: &bitcoin_blockparser::parser::visitor::BlockData,
+    ) -> anyhow::Result<()> {
+        assert!(data.undo.is_some());
+        assert_eq!(data.hashrates.len(), 1);
+        self.calls.borrow_mut().push(("block", data.height));
+        Ok(())
+    }
+
+    fn on_complete(&mut self, height: u64) -> anyhow::Result<()> {
+        self.calls.borrow_mut().push(("complete", height));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_visitor() {
+    let visitor = RecordingVisitor::default();
+    let calls = std::rc::Rc::clone(&visitor.calls);
+    let mut parser = common::parser("bitcoin", 2);
+    parser.add_visitor(visitor);
+    parser.start().unwrap();
+    assert_eq!(
+        *calls.borrow(),
+        vec![
+            ("start", 0),
+            ("header", 0),
+            ("block", 0),
+            ("header", 1),
+            ("block", 1),
+            ("header", 2),
+            ("block", 2),
+            ("complete", 2)
+        ]
+    );
+    // the database writer is still run
+    assert_eq!(parser.db().blocks_count().unwrap(), 3);
+}
+
+#[test]
+fn test_block_header_db() {
+    let mut parser = parser();
+    parser.start().unwrap();
+
+    let block = parser.db().block(170).unwrap();
+    assert_eq!(block.time, 1_231_731_025);
+    assert_eq!(block.encoded_target, 0x1d00_ffff);
+    assert_eq!(
+        block.hash,
+        "00000000d1145790a8694403d4063f323d499e655c83426834d4ce2f8dd4a2ee"
+    );
+    assert_eq!(
+        block.prev_hash,
+        "000000002a22cfee1f2c846adbd12b3e183d4f97683f85dad08a79780a84bd55"
+    );
+    assert_eq!(parser.db().block(169).unwrap().hash, block.prev_hash);
+}
+
+#[test]
+fn test_query_api() {
+    let mut parser = parser();
+    parser.start().unwrap();
+    let db = parser.db();
+
+    let blocks = db.blocks_range(100..=109).unwrap();
+    let heights: Vec<_> = blocks.iter().map(|b| b.height).collect();
+    assert_eq!(heights, (100..=109).collect::<Vec<_>>());
+
+    let block = db
+        .block_by_hash("00000000d1145790a8694403d4063f323d499e655c83426834d4ce2f8dd4a2ee")
+        .unwrap()
+        .unwrap();
+    assert_eq!(block.height, 170);
+    assert!(db.block_by_hash("00").unwrap().is_none());
+    let by_time = db.blocks_by_time(block.time..=block.time).unwrap();
+    assert!(by_time.iter().any(|b| b.height == 170));
+
+    let all = db.blocks_range(0..=170).unwrap();
+    let aggregates = db.block_aggregates(0..=170).unwrap();
+    assert_eq!(aggregates.block_count, 171);
+    assert_eq!(aggregates.tx_count, 172);
+    assert_eq!(
+        aggregates.turnover,
+        all.iter().map(|b| b.turnover).sum::<i64>()
+    );
+    assert_eq!(aggregates.miner_reward, 171 * 50 * 100_000_000);
+    assert!(aggregates.avg_size > 0.0);
+    let empty = db.block_aggregates(1000..=2000).unwrap();
+    assert_eq!(empty.block_count, 0);
+    assert_eq!(empty.turnover, 0);
+
+    let pools = db.pool_counts(0..=170).unwrap();
+    assert_eq!(pools.iter().map(|p| p.block_count).sum::<i64>(), 171);
+    assert!(pools
+        .windows(2)
+        .all(|w| w[0].block_count >= w[1].block_count));
+
+    let txs = db
+        .iter_transactions(0..=170)
+        .collect::<anyhow::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(txs.len(), 172);
+    assert_eq!(
+        txs.last().unwrap().txid,
+        "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+    );
+    let outputs = db
+        .iter_outputs(0..=170)
+        .collect::<anyhow::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(
+        i64::try_from(outputs.len()).unwrap(),
+        txs.iter().map(|tx| i64::from(tx.output_count)).sum::<i64>()
+    );
+    assert_eq!(outputs.last().unwrap().txid, txs.last().unwrap().txid);
+    assert_eq!(db.iter_outputs(171..=300).count(), 0);
+}
+
+#[test]
+fn test_query_subcommand() {
+    let db_dir = tempfile::tempdir().unwrap();
+    let db_url = db_dir
+        .path()
+        .join("blocks.db")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let mut options = common::options("bitcoin", 170);
+    options.db_url = db_url.clone();
+    common::parser_from(&options).start().unwrap();
+
+    let query = |query, start, end| {
+        let mut out = Vec::new();
+        bitcoin_blockparser::query::run(
+            &bitcoin_blockparser::query::QueryOptions {
+                db_url: db_url.clone(),
+                query,
+                range: bitcoin_blockparser::BlockHeightRange::new(start, end).unwrap(),
+            },
+            &mut out,
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let summary = query(bitcoin_blockparser::query::Query::Summary, 0, None);
+    let mut lines = summary.lines();
+    assert!(lines.next().unwrap().starts_with("blocks\ttransactions\t"));
+    assert!(lines.next().unwrap().starts_with("171\t172\t"));
+
+    let blocks = query(bitcoin_blockparser::query::Query::Blocks, 169, Some(170));
+    let rows: Vec<_> = blocks.lines().skip(1).collect();
+    assert_eq!(rows.len(), 2);
+    assert!(rows[1]
+        .starts_with("170\t00000000d1145790a8694403d4063f323d499e655c83426834d4ce2f8dd4a2ee\t"));
+
+    let missing = bitcoin_blockparser::query::run(
+        &bitcoin_blockparser::query::QueryOptions {
+            db_url: db_dir
+                .path()
+                .join("missing.db")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            query: bitcoin_blockparser::query::Query::Pools,
+            range: bitcoin_blockparser::BlockHeightRange::new(0, None).unwrap(),
+        },
+        &mut Vec::new(),
+    );
+    assert!(missing.is_err());
+
+    // a database without the current schema isn't migrated
+    let outdated = db_dir.path().join("outdated.db");
+    std::fs::write(&outdated, []).unwrap();
+    let err = bitcoin_blockparser::query::run(
+        &bitcoin_blockparser::query::QueryOptions {
+            db_url: outdated.to_str().unwrap().to_string(),
+            query: bitcoin_blockparser::query::Query::Pools,
+            range: bitcoin_blockparser::BlockHeightRange::new(0, None).unwrap(),
+        },
+        &mut Vec::new(),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("schema is outdated"));
+    assert!(bitcoin_blockparser::db::Db::open_existing(outdated.to_str().unwrap()).is_err());
+}
+
+#[test]
+fn test_datadir_info() {
+    let info =
+        bitcoin_blockparser::parser::info::DatadirInfo::new(&common::options("bitcoin", 170))
+            .unwrap();
+    assert_eq!(info.blk_files, 1);
+    assert!(info.blk_size > 0);
+    assert!(info.tip_height >= 170);
+    assert!(info.index_records > 170);
+    assert!(!info.flags.pruned);
+    assert!(!info.xor_obfuscated);
+    assert!(info
+        .to_string()
+        .contains(&format!("tip hash:       {}", info.tip_hash)));
+}
+
+#[test]
+fn test_chain_tips() {
+    let options = common::options("bitcoin", 170);
+    let info = bitcoin_blockparser::parser::info::DatadirInfo::new(&options).unwrap();
+    let tips = bitcoin_blockparser::parser::info::chain_tips(&options).unwrap();
+    let active: Vec<_> = tips
+        .iter()
+        .filter(|tip| tip.status == bitcoin_blockparser::parser::TipStatus::Active)
+        .collect();
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].height, info.tip_height);
+    assert_eq!(active[0].hash, info.tip_hash);
+    assert_eq!(active[0].branch_len, 0);
+
+    let mut out = Vec::new();
+    bitcoin_blockparser::parser::info::write_chain_tips(&tips, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("height\thash\tbranchlen\tstatus\n"));
+    assert!(out.contains(&format!(
+        "{}\t{}\t0\tactive\n",
+        info.tip_height, info.tip_hash
+    )));
+
+    // all stale blocks of the index are written, also those beyond --end
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    let stale_blocks = parser.db().stale_blocks().unwrap();
+    assert_eq!(
+        stale_blocks.len(),
+        common::storage("bitcoin", 170).stale_block_count()
+    );
+    assert!(stale_blocks
+        .iter()
+        .all(|block| block.block_hash != info.tip_hash.to_string()));
+}
+
+#[test]
+fn test_stale_blocks_replaced() {
+    let db_dir = tempfile::tempdir().unwrap();
+    let mut options = common::options("bitcoin", 50);
+    options.db_url = db_dir
+        .path()
+        .join("blocks.db")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    // a block of an earlier run which the index no longer knows about
+    let header = common::storage("bitcoin", 50).get_header(10).unwrap();
+    parser
+        .db()
+        .replace_stale_blocks(vec![bitcoin_blockparser::db::StaleBlock {
+            block_hash: "0".repeat(64),
+            height: 10,
+            prev_hash: header.prev_blockhash.to_string(),
+            version: header.version.to_consensus(),
+            merkle_root: header.merkle_root.to_string(),
+            time: header.time.into(),
+            encoded_target: header.bits.to_consensus().into(),
+            nonce: header.nonce.into(),
+            tx_count: None,
+            pool: None,
+            miner_tag: None,
+        }])
+        .unwrap();
+    assert_eq!(parser.db().stale_blocks().unwrap().len(), 1);
+    assert_eq!(common::storage("bitcoin", 50).stale_block_count(), 0);
+
+    options.range = bitcoin_blockparser::BlockHeightRange::new(0, Some(60)).unwrap();
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    assert!(parser.db().stale_blocks().unwrap().is_empty());
+}
+
+#[test]
+fn test_chainwork_db() {
+    let mut parser = parser();
+    parser.start().unwrap();
+
+    let block = parser.db().block(170).unwrap();
+    assert!((block.difficulty - 1.0).abs() < f64::EPSILON);
+    assert_eq!(
+        block.work,
+        "0000000000000000000000000000000000000000000000000000000100010001"
+    );
+    assert_eq!(
+        block.chainwork,
+        "000000000000000000000000000000000000000000000000000000ab00ab00ab"
+    );
+    let hashrates = parser.db().block_hashrates(170).unwrap();
+    assert_eq!(hashrates.len(), 1);
+    assert_eq!(hashrates[0].window_size, 144);
+    assert!(hashrates[0].hashrate > 0.0);
+
+    // the chainwork preceding the range is taken from the block index
+    let mut options = common::options("bitcoin", 170);
+    options.range = bitcoin_blockparser::BlockHeightRange::new(100, Some(170)).unwrap();
+    let mut ranged = common::parser_from(&options);
+    ranged.start().unwrap();
+    let ranged_block = ranged.db().block(170).unwrap();
+    assert_eq!(ranged_block.chainwork, block.chainwork);
+    assert!(
+        (ranged.db().block_hashrates(170).unwrap()[0].hashrate - hashrates[0].hashrate).abs()
+            < 1e-6
+    );
+}
+
+#[test]
+fn test_coinbases_db() {
+    let mut parser = parser();
+    parser.start().unwrap();
+
+    let genesis = parser.db().coinbase(0).unwrap();
+    assert_eq!(
+        genesis.txid,
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+    );
+    assert_eq!(
+        genesis.tags,
+        "The Times 03/Jan/2009 Chancellor on brink of second bailout for banks"
+    );
+    assert_eq!(genesis.bip34_height, Some(0x1d00_ffff));
+    assert!(!genesis.bip34_valid);
+    assert_eq!(genesis.extranonce_length, Some(1));
+    assert!(!genesis.witness_commitment);
+    assert!(genesis.merge_mining.is_none());
+
+    // the P2PK output has no address but is stored with its script
+    let payouts = parser.db().coinbase_payouts(0).unwrap();
+    assert_eq!(payouts.len(), 1);
+    assert_eq!(payouts[0].vout, 0);
+    assert_eq!(
+        u64::try_from(payouts[0].value).unwrap(),
+        50 * bitcoin::Amount::ONE_BTC.to_sat()
+    );
+    assert!(bitcoin::Script::from_bytes(&payouts[0].script).is_p2pk());
+    assert!(payouts[0].address.is_none());
+    assert_eq!(parser.db().coinbase(170).unwrap().script_sig.len(), 7);
+}
+
+#[test]
+fn test_transactions_db() {
+    let mut parser = parser();
+    parser.start().unwrap();
+    assert_eq!(parser.db().transactions_count().unwrap(), 172);
+
+    let txs = parser.db().transactions(170).unwrap();
+    assert_eq!(txs.len(), 2);
+    assert!(txs[0].is_coinbase);
+    assert_eq!(
+        txs[0].txid,
+        "b1fea52486ce0c62bb442b530a3f0132b826c74e473d1f2c220bfa78111c5082"
+    );
+
+    let tx = &txs[1];
+    assert_eq!(
+        tx.txid,
+        "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+    );
+    assert_eq!(tx.txid, tx.wtxid);
+    assert_eq!(tx.position, 1);
+    assert_eq!(tx.version, 1);
+    assert_eq!(tx.locktime, 0);
+    assert_eq!(tx.size, 275);
+    assert_eq!(tx.vsize, 275);
+    assert_eq!(tx.weight, 1100);
+    assert_eq!(tx.input_count, 1);
+    assert_eq!(tx.output_count, 2);
+    assert_eq!(
+        u64::try_from(tx.output_value).unwrap(),
+        50 * bitcoin::Amount::ONE_BTC.to_sat()
+    );
+    assert!(!tx.is_segwit);
+    assert!(!tx.is_coinbase);
+}
+
+#[test]
+fn test_block_fees_db() {
+    let mut parser = parser();
+    parser.start().unwrap();
+
+    let genesis = parser.db().block_fees(0).unwrap().unwrap();
+    assert_eq!(genesis.total_fee, 0);
+    assert_eq!(
+        u64::try_from(genesis.subsidy).unwrap(),
+        50 * bitcoin::Amount::ONE_BTC.to_sat()
+    );
+    assert_eq!(genesis.max_feerate, 0);
+
+    // the first transaction spends the 50 BTC coinbase of block 9 without a fee
+    let fees = parser.db().block_fees(170).unwrap().unwrap();
+    assert_eq!(fees.total_fee, 0);
+    assert_eq!(fees.max_fee, 0);
+    assert_eq!(
+        u64::try_from(fees.subsidy).unwrap(),
+        50 * bitcoin::Amount::ONE_BTC.to_sat()
+    );
+}
+
+#[test]
+fn test_block_script_stats_db() {
+    let mut parser = parser();
+    parser.start().unwrap();
+
+    let stats = parser.db().block_script_stats(170).unwrap();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].script_type, "p2pk");
+    assert_eq!(stats[0].output_count, 3);
+    assert_eq!(
+        u64::try_from(stats[0].output_value).unwrap(),
+        100 * bitcoin::Amount::ONE_BTC.to_sat()
+    );
+    // spent outputs are only known from the undo data
+    assert_eq!(stats[0].input_count, Some(1));
+    assert_eq!(
+        stats[0]
+            .input_value
+            .map(|value| u64::try_from(value).unwrap()),
+        Some(50 * bitcoin::Amount::ONE_BTC.to_sat())
+    );
+}
+
+#[test]
+fn test_inputs_outputs_db() {
+    let mut parser = parser();
+    parser.start().unwrap();
+    let db = parser.db();
+
+    let spending_txid = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+    let inputs = db.inputs(spending_txid).unwrap();
+    assert_eq!(inputs.len(), 1);
+    assert_eq!(
+        inputs[0].prev_txid,
+        "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9"
+    );
+    assert_eq!(inputs[0].prev_vout, 0);
+    assert_eq!(inputs[0].sequence, 0xffff_ffff);
+    assert!(inputs[0].witness.is_none());
+
+    let spent = db.output(&inputs[0].prev_txid, 0).unwrap();
+    assert_eq!(spent.height, 9);
+    assert_eq!(spent.script_type, "p2pk");
+    assert!(spent.address.is_none());
+    assert_eq!(spent.spent_by_txid.as_deref(), Some(spending_txid));
+    assert_eq!(spent.spent_height, Some(170));
+
+    let to_hal_finney = db.output(spending_txid, 0).unwrap();
+    assert_eq!(
+        u64::try_from(to_hal_finney.value).unwrap(),
+        10 * bitcoin::Amount::ONE_BTC.to_sat()
+    );
+    assert!(to_hal_finney.spent_by_txid.is_none());
+    assert!(to_hal_finney.spent_height.is_none());
+}
+
+#[test]
+fn test_csv_export() {
+    let export_dir = tempfile::tempdir().unwrap();
+    let mut options = common::options("bitcoin", 170);
+    options.write_db = false;
+    options.export = Some(bitcoin_blockparser::export::ExportOptions {
+        rotate: Some(100),
+        ..common::export_options(
+            bitcoin_blockparser::export::ExportFormat::Csv,
+            export_dir.path(),
+        )
+    });
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    assert_eq!(parser.db().blocks_count().unwrap(), 0);
+
+    let read = |name: &str| {
+        csv::Reader::from_path(export_dir.path().join(name))
+            .unwrap()
+            .into_records()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(read("blocks_000000000-000000099.csv").len(), 100);
+    let blocks = read("blocks_000000100-000000199.csv");
+    assert_eq!(blocks.len(), 71);
+    assert_eq!(&blocks[70][0], "170");
+    assert_eq!(
+        &blocks[70][1],
+        "00000000d1145790a8694403d4063f323d499e655c83426834d4ce2f8dd4a2ee"
+    );
+    assert_eq!(&blocks[70][6], "1d00ffff");
+
+    let transactions = read("transactions_000000100-000000199.csv");
+    let spend = transactions
+        .iter()
+        .find(|tx| &tx[0] == "170" && &tx[1] == "1")
+        .unwrap();
+    assert_eq!(
+        &spend[2],
+        "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+    );
+    assert_eq!(&spend[11], "5000000000");
+    assert_eq!(&spend[13], "0");
+
+    let inputs = read("inputs_000000100-000000199.csv");
+    let input = inputs
+        .iter()
+        .find(|input| {
+            &input[1] == "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+        })
+        .unwrap();
+    assert_eq!(
+        &input[3],
+        "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9"
+    );
+    assert_eq!(&input[8], "5000000000");
+
+    let outputs = read("outputs_000000100-000000199.csv");
+    assert_eq!(
+        outputs.iter().filter(|output| &output[0] == "170").count(),
+        3
+    );
+    assert!(outputs.iter().all(|output| &output[5] == "p2pk"));
+}
+
+#[test]
+fn test_csv_export_gzip() {
+    let export_dir = tempfile::tempdir().unwrap();
+    let mut options = common::options("bitcoin", 10);
+    options.export = Some(bitcoin_blockparser::export::ExportOptions {
+        gzip: true,
+        ..common::export_options(
+            bitcoin_blockparser::export::ExportFormat::Csv,
+            export_dir.path(),
+        )
+    });
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    assert_eq!(parser.db().blocks_count().unwrap(), 11);
+
+    let file = std::fs::File::open(export_dir.path().join("blocks.csv.gz")).unwrap();
+    let mut reader = csv::Reader::from_reader(flate2::read::MultiGzDecoder::new(file));
+    assert_eq!(&reader.headers().unwrap()[0], "height");
+    assert_eq!(reader.records().count(), 11);
+}
+
+#[test]
+fn test_csv_export_rerun() {
+    let export_dir = tempfile::tempdir().unwrap();
+    let mut options = common::options("bitcoin", 10);
+    options.write_db = false;
+    options.export = Some(common::export_options(
+        bitcoin_blockparser::export::ExportFormat::Csv,
+        export_dir.path(),
+    ));
+    // without a persisted checkpoint every run starts over
+    for _ in 0..2 {
+        common::parser_from(&options).start().unwrap();
+    }
+    let reader = csv::Reader::from_path(export_dir.path().join("blocks.csv")).unwrap();
+    assert_eq!(reader.into_records().count(), 11);
+}
+
+#[test]
+fn test_csv_export_resume() {
+    let export_dir = tempfile::tempdir().unwrap();
+    let db_dir = tempfile::tempdir().unwrap();
+    let db_url = db_dir
+        .path()
+        .join("blocks.db")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let export = common::export_options(
+        bitcoin_blockparser::export::ExportFormat::Csv,
+        export_dir.path(),
+    );
+
+    let mut options = common::options("bitcoin", 50);
+    options.db_url = db_url.clone();
+    options.export = Some(export.clone());
+    common::parser_from(&options).start().unwrap();
+
+    let mut options = common::options("bitcoin", 170);
+    options.db_url = db_url;
+    options.export = Some(export);
+    common::parser_from(&options).start().unwrap();
+
+    let heights: Vec<u64> = csv::Reader::from_path(export_dir.path().join("blocks.csv"))
+        .unwrap()
+        .into_records()
+        .map(|record| record.unwrap()[0].parse().unwrap())
+        .collect();
+    assert_eq!(heights, (0..=170).collect::<Vec<_>>());
+}
+
+/// Fails the parser when it reaches `height`
+struct FailingVisitor {
+    height: u64,
+}
+
+impl bitcoin_blockparser::parser::visitor::BlockVisitor for FailingVisitor {
+    fn on_block(
+        &mut self,
+        data: &bitcoin_blockparser::parser::visitor::BlockData,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            data.height != self.height,
+            "failing at height {}",
+            self.height
+        );
+        Ok(())
+    }
+}
+
+/// Heights of the rows of all `blocks` files in `dir`, in file name order
+fn exported_heights(dir: &std::path::Path) -> Vec<u64> {
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .starts_with("blocks")
+        })
+        .collect();
+    paths.sort();
+    let mut heights = Vec::new();
+    for path in paths {
+        let file = std::fs::File::open(&path).unwrap();
+        if path.extension().unwrap() == "parquet" {
+            for batch in ParquetRecordBatchReaderBuilder::try_new(file)
+                .unwrap()
+                .build()
+                .unwrap()
+            {
+                let batch = batch.unwrap();
+                let column = batch
+                    .column_by_name("height")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<arrow_array::UInt64Array>()
+                    .unwrap();
+                heights.extend(column.values().iter().copied());
+            }
+        } else {
+            let reader = csv::Reader::from_reader(flate2::read::MultiGzDecoder::new(file));
+            heights.extend(
+                reader
+                    .into_records()
+                    .map(|record| record.unwrap()[0].parse::<u64>().unwrap()),
+            );
+        }
+    }
+    heights
+}
+
+#[test]
+fn test_export_resume_after_failure() {
+    use bitcoin_blockparser::export::ExportFormat;
+
+    for (format, gzip) in [(ExportFormat::Csv, true), (ExportFormat::Parquet, false)] {
+        let export_dir = tempfile::tempdir().unwrap();
+        let db_dir = tempfile::tempdir().unwrap();
+        let mut options = common::options("bitcoin", 170);
+        options.db_url = db_dir
+            .path()
+            .join("blocks.db")
+            .to_str()
+            .unwrap()
+            .to_string();
+        options.batch_size = 64;
+        options.export = Some(bitcoin_blockparser::export::ExportOptions {
+            rotate: Some(50),
+            gzip,
+            row_group_size: 16,
+            ..common::export_options(format, export_dir.path())
+        });
+
+        // fails in the middle of the second batch, after blocks 64 to 99 were exported
+        let mut parser = common::parser_from(&options);
+        parser.add_visitor(FailingVisitor { height: 100 });
+        assert!(parser.start().is_err());
+        assert_eq!(parser.db().checkpoint().unwrap().unwrap().height, 63);
+
+        let mut parser = common::parser_from(&options);
+        parser.start().unwrap();
+        assert_eq!(parser.db().blocks_count().unwrap(), 171);
+        assert_eq!(
+            exported_heights(export_dir.path()),
+            (0..=170).collect::<Vec<_>>(),
+            "{format:?}"
+        );
+    }
+}
+
+#[test]
+fn test_parquet_export() {
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let export_dir = tempfile::tempdir().unwrap();
+    let mut options = common::options("bitcoin", 170);
+    options.export = Some(bitcoin_blockparser::export::ExportOptions {
+        rotate: Some(100),
+        row_group_size: 64,
+        ..common::export_options(
+            bitcoin_blockparser::export::ExportFormat::Parquet,
+            export_dir.path(),
+        )
+    });
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+
+    let open = |name: &str| {
+        ParquetRecordBatchReaderBuilder::try_new(
+            std::fs::File::open(export_dir.path().join(name)).unwrap(),
+        )
+        .unwrap()
+    };
+    let blocks = open("blocks_000000000-000000099.parquet");
+    assert_eq!(blocks.metadata().file_metadata().num_rows(), 100);
+    assert_eq!(blocks.metadata().num_row_groups(), 2);
+    let schema = blocks.schema();
+    assert_eq!(
+        schema.field_with_name("hash").unwrap().data_type(),
+        &arrow_schema::DataType::FixedSizeBinary(32)
+    );
+    assert!(!schema.field_with_name("height").unwrap().is_nullable());
+
+    let outputs = open("outputs_000000100-000000199.parquet");
+    assert_eq!(
+        outputs
+            .schema()
+            .field_with_name("value")
+            .unwrap()
+            .data_type(),
+        &arrow_schema::DataType::UInt64
+    );
+    let batches: Vec<_> = outputs.build().unwrap().map(Result::unwrap).collect();
+    assert_eq!(
+        batches
+            .iter()
+            .map(arrow_array::RecordBatch::num_rows)
+            .sum::<usize>(),
+        73
+    );
+
+    let transactions = open("transactions_000000100-000000199.parquet");
+    let batch = transactions.build().unwrap().next().unwrap().unwrap();
+    let txids = batch
+        .column_by_name("txid")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<arrow_array::FixedSizeBinaryArray>()
+        .unwrap();
+    assert_eq!(
+        hex::encode(txids.value(arrow_array::Array::len(txids) - 1)),
+        "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+    );
+}
+
+#[test]
+fn test_ndjson_export() {
+    let export_dir = tempfile::tempdir().unwrap();
+    let mut options = common::options("bitcoin", 170);
+    options.write_db = false;
+    options.export = Some(common::export_options(
+        bitcoin_blockparser::export::ExportFormat::Ndjson,
+        export_dir.path(),
+    ));
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+
+    let content = std::fs::read_to_string(export_dir.path().join("blocks.ndjson")).unwrap();
+    let blocks: Vec<serde_json::Value> = content
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(blocks.len(), 171);
+
+    let genesis = &blocks[0];
+    let keys: Vec<_> = genesis.as_object().unwrap().keys().collect();
+    assert_eq!(
+        keys,
+        [
+            "hash",
+            "confirmations",
+            "height",
+            "version",
+            "versionHex",
+            "merkleroot",
+            "time",
+            "mediantime",
+            "nonce",
+            "bits",
+            "difficulty",
+            "chainwork",
+            "nTx",
+            "nextblockhash",
+            "strippedsize",
+            "size",
+            "weight",
+            "tx"
+        ]
+    );
+    assert_eq!(genesis["mediantime"], 1_231_006_505);
+    assert_eq!(genesis["difficulty"].to_string(), "1");
+    assert_eq!(genesis["bits"], "1d00ffff");
+    assert_eq!(genesis["nextblockhash"], blocks[1]["hash"]);
+    assert_eq!(blocks[1]["previousblockhash"], genesis["hash"]);
+    assert!(genesis["tx"][0]["vin"][0]["coinbase"]
+        .as_str()
+        .unwrap()
+        .starts_with("04ffff001d0104455468"));
+    assert_eq!(
+        genesis["tx"][0]["vout"][0]["value"].to_string(),
+        "50.00000000"
+    );
+
+    let block = &blocks[170];
+    assert_eq!(block["height"], 170);
+    assert_eq!(
+        block["hash"],
+        "00000000d1145790a8694403d4063f323d499e655c83426834d4ce2f8dd4a2ee"
+    );
+    assert_eq!(block["nTx"], 2);
+    assert!(block["tx"][0].get("fee").is_none());
+    let tx = &block["tx"][1];
+    assert_eq!(
+        tx["txid"],
+        "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+    );
+    assert_eq!(tx["fee"].to_string(), "0.00000000");
+    let input = &tx["vin"][0];
+    assert_eq!(
+        input["txid"],
+        "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9"
+    );
+    let asm = input["scriptSig"]["asm"].as_str().unwrap();
+    assert!(asm.starts_with("304402204e45e169"));
+    assert!(asm.ends_with("[ALL]"));
+    assert_eq!(input["prevout"]["generated"], true);
+    assert_eq!(input["prevout"]["height"], 9);
+    assert_eq!(input["prevout"]["value"].to_string(), "50.00000000");
+    let output = &tx["vout"][0];
+    assert_eq!(output["value"].to_string(), "10.00000000");
+    assert_eq!(output["scriptPubKey"]["type"], "pubkey");
+    assert!(output["scriptPubKey"].get("address").is_none());
+    assert!(output["scriptPubKey"]["desc"]
+        .as_str()
+        .unwrap()
+        .starts_with("pk(04ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84c)#"));
+}
+
+#[test]
+fn test_crypto_bitcoin_export() {
+    let export_dir = tempfile::tempdir().unwrap();
+    let mut options = common::options("bitcoin", 170);
+    options.write_db = false;
+    options.export = Some(common::export_options(
+        bitcoin_blockparser::export::ExportFormat::CryptoBitcoin,
+        export_dir.path(),
+    ));
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+
+    let read = |name: &str| {
+        std::fs::read_to_string(export_dir.path().join(name))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let blocks = read("blocks.json");
+    assert_eq!(blocks.len(), 171);
+    assert_eq!(blocks[0]["number"], 0);
+    assert_eq!(blocks[0]["timestamp"], "2009-01-03 18:15:05 UTC");
+    assert_eq!(blocks[0]["timestamp_month"], "2009-01-01");
+    assert_eq!(blocks[0]["nonce"], "7c2bac1d");
+    assert_eq!(blocks[0]["bits"], "1d00ffff");
+    assert!(blocks[0]["coinbase_param"]
+        .as_str()
+        .unwrap()
+        .starts_with("04ffff001d0104455468"));
+    assert_eq!(blocks[170]["transaction_count"], 2);
+
+    let transactions = read("transactions.json");
+    assert_eq!(transactions.len(), 172);
+    let coinbase = &transactions[0];
+    assert_eq!(coinbase["is_coinbase"], true);
+    assert_eq!(coinbase["input_count"], 0);
+    assert_eq!(coinbase["outputs"][0]["value"], 5_000_000_000_u64);
+    assert_eq!(
+        coinbase["outputs"][0]["addresses"],
+        serde_json::json!(["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"])
+    );
+    assert_eq!(coinbase["outputs"][0]["type"], "pubkey");
+
+    let spend = transactions.last().unwrap();
+    assert_eq!(
+        spend["hash"],
+        "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+    );
+    assert_eq!(spend["block_number"], 170);
+    assert_eq!(spend["block_hash"], blocks[170]["hash"]);
+    assert_eq!(spend["input_value"], 5_000_000_000_u64);
+    assert_eq!(spend["fee"], 0);
+    let input = &spend["inputs"][0];
+    assert_eq!(
+        input["spent_transaction_hash"],
+        "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9"
+    );
+    assert_eq!(input["spent_output_index"], 0);
+    assert_eq!(input["type"], "pubkey");
+    assert_eq!(input["required_signatures"], 1);
+    assert_eq!(input["value"], 5_000_000_000_u64);
+    assert_eq!(spend["outputs"][1]["value"], 4_000_000_000_u64);
+}
+
+#[test]
+fn test_verify_report() {
+    let mut options = common::options("bitcoin", 10);
+    options.verify = bitcoin_blockparser::Verify::Report;
+
+    // corrupt the merkle root of the genesis block
+    let blk_file = options.blockchain_dir.join("blk00000.dat");
+    let mut data = std::fs::read(&blk_file).unwrap();
+    data[50] ^= 0xff;
+    std::fs::write(&blk_file, data).unwrap();
+
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    assert!(parser.verification_failed());
+    assert_eq!(parser.db().blocks_count().unwrap(), 11);
+
+    let issues: Vec<_> = parser
+        .db()
+        .verification_issues()
+        .unwrap()
+        .into_iter()
+        .map(|issue| (issue.height, issue.kind))
+        .collect();
+    assert!(issues.contains(&(0, "hash".to_string())));
+    assert!(issues.contains(&(0, "merkle".to_string())));
+    assert!(issues.iter().all(|(height, _)| *height == 0));
+}
+
+#[test]
+fn test_verify_fails() {
+    let options = common::options("bitcoin", 10);
+    let blk_file = options.blockchain_dir.join("blk00000.dat");
+    let mut data = std::fs::read(&blk_file).unwrap();
+    data[50] ^= 0xff;
+    std::fs::write(&blk_file, data).unwrap();
+
+    let mut storage = bitcoin_blockparser::parser::chain::ChainStorage::new(&options).unwrap();
+    let err = storage.get_block(0).unwrap_err();
+    assert!(err.to_string().contains("Verification failed"));
+    assert!(common::parser_from(&options).start().is_err());
+}
+
+#[test]
+fn test_unreadable_block() {
+    let options = common::options("bitcoin", 10);
+    // truncate the blk file within the genesis block
+    let blk_file = options.blockchain_dir.join("blk00000.dat");
+    let data = std::fs::read(&blk_file).unwrap();
+    std::fs::write(&blk_file, &data[..100]).unwrap();
+
+    let mut storage = bitcoin_blockparser::parser::chain::ChainStorage::new(&options).unwrap();
+    let err = storage.get_block(0).unwrap_err();
+    assert!(err.to_string().contains("unable to read block"));
+    assert!(common::parser_from(&options).start().is_err());
+}
+
+#[cfg(feature = "bitcoinconsensus")]
+#[test]
+fn test_verify_scripts() {
+    use bitcoin_blockparser::parser::script;
+
+    let mut options = common::options("bitcoin", 170);
+    options.verify_scripts = true;
+    let mut storage = bitcoin_blockparser::parser::chain::ChainStorage::new(&options).unwrap();
+    let block = storage.get_block(170).unwrap().unwrap();
+    let mut undo = storage.get_block_undo(170).unwrap();
+    let flags = script::script_flags(&block.block_hash(), 170, &options.coin);
+    assert!(script::verify_scripts(&block, &undo, flags)
+        .unwrap()
+        .is_empty());
+
+    // the first transaction spending a P2PK output, signed for another public key
+    undo.txdata[0].prevouts[0].txout.script_pubkey =
+        block.txdata[0].output[0].script_pubkey.clone();
+    let failures = script::verify_scripts(&block, &undo, flags).unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].txid, block.txdata[1].txid());
+    assert_eq!(failures[0].input_index, 0);
+
+    assert!(common::parser_from(&options).start().is_ok());
+}
+
+#[test]
+fn test_resume_from_checkpoint() {
+    let db_dir = tempfile::tempdir().unwrap();
+    let db_url = db_dir
+        .path()
+        .join("blocks.db")
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let mut options = common::options("bitcoin", 50);
+    options.db_url = db_url.clone();
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    assert_eq!(parser.db().blocks_count().unwrap(), 51);
+    assert_eq!(parser.db().checkpoint().unwrap().unwrap().height, 50);
+
+    let mut options = common::options("bitcoin", 170);
+    options.db_url = db_url;
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    assert_eq!(parser.db().blocks_count().unwrap(), 171);
+    assert_eq!(parser.db().checkpoint().unwrap().unwrap().height, 170);
+}
+
+#[test]
+fn test_migrate_v1_database() {
+    use diesel::migration::MigrationSource;
+    use diesel_migrations::MigrationHarness;
+
+    const MIGRATIONS: diesel_migrations::EmbeddedMigrations =
+        diesel_migrations::embed_migrations!("migrations/sqlite");
+
+    let db_dir = tempfile::tempdir().unwrap();
+    let db_url = db_dir
+        .path()
+        .join("blocks.db")
+        .to_str()
+        .unwrap()
+        .to_string();
+    {
+        let mut conn =
+            <diesel::SqliteConnection as diesel::Connection>::establish(&db_url).unwrap();
+        // creates the table of applied migrations
+        assert!(conn.has_pending_migration(MIGRATIONS).unwrap());
+        let mut migrations =
+            MigrationSource::<diesel::sqlite::Sqlite>::migrations(&MIGRATIONS).unwrap();
+        migrations.sort_by_key(|migration| migration.name().version().as_owned());
+        for migration in migrations
+            .iter()
+            .take_while(|migration| !migration.name().to_string().ends_with("blocks_schema_v2"))
+        {
+            conn.run_migration(migration.as_ref()).unwrap();
+        }
+        diesel::RunQueryDsl::execute(
+            diesel::sql_query(
+                "INSERT INTO blocks (height, version, time, encoded_target, nonce, tx_count, size, \
+                 weight, turnover, miner_reward, pool, difficulty, work, chainwork) \
+                 VALUES (0, 1, 3231006505, 486604799, 2083236893, 1, 285, 1140, 0, 5000000000, \
+                 NULL, 1.0, '100010001', '100010001')",
+            ),
+            &mut conn,
+        )
+        .unwrap();
+    }
+
+    let db = bitcoin_blockparser::db::Db::open(&db_url).unwrap();
+    let block = db.block(0).unwrap();
+    assert_eq!(block.time, 3_231_006_505);
+    assert_eq!(block.hash, "");
+    assert_eq!(block.prev_hash, "");
+    assert!(db.block_by_hash("").unwrap().is_none());
+    assert!(bitcoin_blockparser::db::Db::open_existing(&db_url).is_ok());
+}
+
+#[test]
+fn test_bulk_load() {
+    let db_dir = tempfile::tempdir().unwrap();
+    let mut options = common::options("bitcoin", 170);
+    options.db_url = db_dir
+        .path()
+        .join("blocks.db")
+        .to_str()
+        .unwrap()
+        .to_string();
+    options.batch_size = 64;
+    options.defer_indexes = true;
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    let db = parser.db();
+    assert_eq!(db.blocks_count().unwrap(), 171);
+    assert_eq!(db.transactions_count().unwrap(), 172);
+    assert_eq!(db.checkpoint().unwrap().unwrap().height, 170);
+    let spent = db
+        .output(
+            "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9",
+            0,
+        )
+        .unwrap();
+    assert_eq!(spent.spent_height, Some(170));
+    // the WAL journal is kept next to the database
+    assert!(db_dir.path().join("blocks.db-wal").exists());
+    assert_eq!(index_names(&options.db_url), INDEX_NAMES);
+}
+
+#[test]
+fn test_bulk_load_failed() {
+    let db_dir = tempfile::tempdir().unwrap();
+    let mut options = common::options("bitcoin", 170);
+    options.db_url = db_dir
+        .path()
+        .join("blocks.db")
+        .to_str()
+        .unwrap()
+        .to_string();
+    options.defer_indexes = true;
+    // corrupt a block in the middle, which fails verification
+    let blk_file = options.blockchain_dir.join("blk00000.dat");
+    let mut data = std::fs::read(&blk_file).unwrap();
+    let middle = data.len() / 2;
+    data[middle] ^= 0xff;
+    std::fs::write(&blk_file, data).unwrap();
+
+    let mut parser = common::parser_from(&options);
+    assert!(parser.start().is_err());
+    drop(parser);
+    assert_eq!(index_names(&options.db_url), INDEX_NAMES);
+}
+
+const INDEX_NAMES: [&str; 5] = [
+    "blocks_block_hash",
+    "inputs_prevout",
+    "op_returns_protocol",
+    "outputs_address",
+    "transactions_txid",
+];
+
+/// Names of the secondary indexes of the SQLite database at `db_url`
+fn index_names(db_url: &str) -> Vec<String> {
+    #[derive(diesel::QueryableByName)]
+    struct Index {
+        #[diesel(sql_type = diesel::sql_types::Text)]
+        name: String,
+    }
+    let mut conn = <diesel::SqliteConnection as diesel::Connection>::establish(db_url).unwrap();
+    let indexes: Vec<Index> = diesel::RunQueryDsl::load(
+        diesel::sql_query(
+            "SELECT name FROM sqlite_master WHERE type = 'index' AND sql IS NOT NULL ORDER BY name",
+        ),
+        &mut conn,
+    )
+    .unwrap();
+    indexes.into_iter().map(|index| index.name).collect()
+}
+
+#[cfg(feature = "duckdb")]
+#[test]
+fn test_duckdb() {
+    let db_dir = tempfile::tempdir().unwrap();
+    let db_url = format!(
+        "duckdb://{}",
+        db_dir.path().join("blocks.duckdb").to_str().unwrap()
+    );
+
+    let mut options = common::options("bitcoin", 50);
+    options.db_url = db_url.clone();
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    assert_eq!(parser.db().blocks_count().unwrap(), 51);
+    drop(parser);
+
+    let mut options = common::options("bitcoin", 170);
+    options.db_url = db_url;
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    let db = parser.db();
+    assert_eq!(db.blocks_count().unwrap(), 171);
+    assert_eq!(db.transactions_count().unwrap(), 172);
+    assert_eq!(db.checkpoint().unwrap().unwrap().height, 170);
+    assert_eq!(db.block(170).unwrap().tx_count, 2);
+    assert_eq!(
+        db.block(170).unwrap().hash,
+        "00000000d1145790a8694403d4063f323d499e655c83426834d4ce2f8dd4a2ee"
+    );
+    assert_eq!(db.block_hashrates(170).unwrap().len(), 1);
+
+    let spent = db
+        .output(
+            "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9",
+            0,
+        )
+        .unwrap();
+    assert_eq!(
+        spent.spent_by_txid.as_deref(),
+        Some("f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16")
+    );
+    assert_eq!(spent.spent_height, Some(170));
+    let inputs = db
+        .inputs("f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16")
+        .unwrap();
+    assert_eq!(inputs.len(), 1);
+    assert_eq!(inputs[0].prev_txid, spent.txid);
+}
+
+#[test]
+#[ignore = "requires an empty PostgreSQL database at $DATABASE_URL"]
+fn test_postgres() {
+    let db_url = std::env::var("DATABASE_URL").unwrap();
+
+    let mut options = common::options("bitcoin", 50);
+    options.db_url = db_url.clone();
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    assert_eq!(parser.db().blocks_count().unwrap(), 51);
+
+    let mut options = common::options("bitcoin", 170);
+    options.db_url = db_url;
+    let mut parser = common::parser_from(&options);
+    parser.start().unwrap();
+    let db = parser.db();
+    assert_eq!(db.blocks_count().unwrap(), 171);
+    assert_eq!(db.transactions_count().unwrap(), 172);
+    assert_eq!(db.checkpoint().unwrap().unwrap().height, 170);
+    assert_eq!(db.block(170).unwrap().tx_count, 2);
+    assert_eq!(db.coinbase(170).unwrap().height, 170);
+
+    let spent = db
+        .output(
+            "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9",
+            0,
+        )
+        .unwrap();
+    assert_eq!(
+        spent.spent_by_txid.as_deref(),
+        Some("f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16")
+    );
+    assert_eq!(spent.spent_height, Some(170));
+}
+
+#[test]
+fn test_interrupt() {
+    let mut parser = parser();
+    parser
+        .interrupt_handle()
+        .store(true, std::sync::atomic::Ordering::SeqCst);
+    parser.start().unwrap();
+    assert!(parser.interrupted());
+    assert_eq!(parser.db().blocks_count().unwrap(), 0);
+    assert!(parser.db().checkpoint().unwrap().is_none());
+}
@@ -15,31 +15,53 @@ fn copy_dir_all(
     Ok(())
 }
 
-pub fn storage(datadir: &str, max_height: u64) -> bitcoin_blockparser::parser::chain::ChainStorage {
+pub fn options(datadir: &str, max_height: u64) -> bitcoin_blockparser::ParserOptions {
     let tempdir = tempfile::tempdir().unwrap();
     copy_dir_all(format!("tests/testdata/{datadir}"), &tempdir).unwrap();
-    let options = bitcoin_blockparser::ParserOptions {
+    bitcoin_blockparser::ParserOptions {
         db_url: ":memory:".parse().unwrap(),
         coin: datadir.parse().unwrap(),
-        verify: true,
+        verify: bitcoin_blockparser::Verify::Strict,
+        verify_scripts: false,
+        hashrate_windows: vec![144],
+        write_db: true,
+        batch_size: 2,
+        defer_indexes: false,
+        export: None,
         blockchain_dir: tempdir.into_path(),
         range: bitcoin_blockparser::BlockHeightRange::new(0, Some(max_height)).unwrap(),
-    };
-    bitcoin_blockparser::parser::chain::ChainStorage::new(&options).unwrap()
+    }
+}
+
+/// Export options writing `format` to `dir` in one file per table, without gzip
+#[allow(dead_code)] // only the bitcoin tests export
+pub fn export_options(
+    format: bitcoin_blockparser::export::ExportFormat,
+    dir: &std::path::Path,
+) -> bitcoin_blockparser::export::ExportOptions {
+    bitcoin_blockparser::export::ExportOptions {
+        format,
+        dir: dir.to_path_buf(),
+        rotate: None,
+        gzip: false,
+        row_group_size: 1024,
+    }
+}
+
+pub fn storage(datadir: &str, max_height: u64) -> bitcoin_blockparser::parser::chain::ChainStorage {
+    bitcoin_blockparser::parser::chain::ChainStorage::new(&options(datadir, max_height)).unwrap()
 }
 
 pub fn parser(datadir: &str, max_height: u64) -> bitcoin_blockparser::parser::BlockchainParser {
-    let tempdir = tempfile::tempdir().unwrap();
-    copy_dir_all(format!("tests/testdata/{datadir}"), &tempdir).unwrap();
-    let options = bitcoin_blockparser::ParserOptions {
-        db_url: ":memory:".parse().unwrap(),
-        coin: datadir.parse().unwrap(),
-        verify: true,
-        blockchain_dir: tempdir.into_path(),
-        range: bitcoin_blockparser::BlockHeightRange::new(0, Some(max_height)).unwrap(),
-    };
+    parser_from(&options(datadir, max_height))
+}
+
+pub fn parser_from(
+    options: &bitcoin_blockparser::ParserOptions,
+) -> bitcoin_blockparser::parser::BlockchainParser {
     bitcoin_blockparser::parser::BlockchainParser::new(
-        &options,
-        bitcoin_blockparser::parser::chain::ChainStorage::new(&options).unwrap(),
+        options,
+        bitcoin_blockparser::parser::chain::ChainStorage::new(options).unwrap(),
     )
+    .unwrap()
 }
@@ -0,0 +1,152 @@
+"""Rebuilds rev00000.dat from blk00000.dat in Bitcoin Core's undo format.
+
+Core only writes undo data for blocks it connects, so this fills in the rev file for a
+`blocks` dir copied without it. The offsets match the `nUndoPos` of the index records.
+"""
+import hashlib
+import struct
+
+BLOCKS_PATH = "./bitcoin"
+MAX_BLOCK_HEIGHT = 200
+MAGIC = b"\xf9\xbe\xb4\xd9"
+
+
+def sha256d(data: bytes) -> bytes:
+    return hashlib.sha256(hashlib.sha256(data).digest()).digest()
+
+
+class Reader:
+    def __init__(self, data: bytes):
+        self.data = data
+        self.pos = 0
+
+    def take(self, n: int) -> bytes:
+        value = self.data[self.pos:self.pos + n]
+        self.pos += n
+        return value
+
+    def u32(self) -> int:
+        return struct.unpack("<I", self.take(4))[0]
+
+    def u64(self) -> int:
+        return struct.unpack("<Q", self.take(8))[0]
+
+    def compact_size(self) -> int:
+        n = self.take(1)[0]
+        if n < 0xfd:
+            return n
+        if n == 0xfd:
+            return struct.unpack("<H", self.take(2))[0]
+        return self.u32() if n == 0xfe else self.u64()
+
+
+def parse_transactions(block: bytes) -> list:
+    """Returns (txid, inputs, outputs) of every (non-segwit) transaction in the block"""
+    reader = Reader(block)
+    reader.take(80)
+    transactions = []
+    for _ in range(reader.compact_size()):
+        start = reader.pos
+        reader.take(4)
+        inputs = []
+        for _ in range(reader.compact_size()):
+            prev_txid = reader.take(32)
+            vout = reader.u32()
+            reader.take(reader.compact_size())
+            reader.take(4)
+            inputs.append((prev_txid, vout))
+        outputs = []
+        for _ in range(reader.compact_size()):
+            value = reader.u64()
+            outputs.append((value, reader.take(reader.compact_size())))
+        reader.take(4)
+        transactions.append((sha256d(block[start:reader.pos]), inputs, outputs))
+    return transactions
+
+
+def compact_size(n: int) -> bytes:
+    if n < 0xfd:
+        return bytes([n])
+    if n <= 0xffff:
+        return b"\xfd" + struct.pack("<H", n)
+    return b"\xfe" + struct.pack("<I", n)
+
+
+def varint(n: int) -> bytes:
+    """Core's VARINT, see serialize.h"""
+    out = []
+    while True:
+        out.append((n & 0x7f) | (0x80 if out else 0))
+        if n <= 0x7f:
+            break
+        n = (n >> 7) - 1
+    return bytes(reversed(out))
+
+
+def compress_amount(n: int) -> int:
+    if n == 0:
+        return 0
+    e = 0
+    while n % 10 == 0 and e < 9:
+        n //= 10
+        e += 1
+    if e < 9:
+        d = n % 10
+        n //= 10
+        return 1 + (n * 9 + d - 1) * 10 + e
+    return 1 + (n - 1) * 10 + 9
+
+
+def compress_script(script: bytes) -> bytes:
+    if len(script) == 25 and script[:3] == b"\x76\xa9\x14" and script[23:] == b"\x88\xac":
+        return b"\x00" + script[3:23]
+    if len(script) == 23 and script[:2] == b"\xa9\x14" and script[22] == 0x87:
+        return b"\x01" + script[2:22]
+    if len(script) == 35 and script[0] == 33 and script[34] == 0xac and script[1] in (2, 3):
+        return script[1:34]
+    if len(script) == 67 and script[0] == 65 and script[66] == 0xac and script[1] == 4:
+        return bytes([4 | (script[65] & 1)]) + script[2:34]
+    return varint(len(script) + 6) + script
+
+
+def read_blocks(path: str) -> dict:
+    """Maps the previous block hash to (hash, block) for every block in the blk file"""
+    data = open(path, "rb").read()
+    reader = Reader(data)
+    blocks = {}
+    while reader.pos + 8 <= len(data) and reader.take(4) == MAGIC:
+        block = reader.take(reader.u32())
+        blocks[block[4:36]] = (sha256d(block[:80]), block)
+    return blocks
+
+
+def make_rev_file(blocks: dict) -> bytes:
+    utxos = {}
+    out = bytearray()
+    block_hash, block = blocks[bytes(32)]
+    for height in range(MAX_BLOCK_HEIGHT + 1):
+        transactions = parse_transactions(block)
+        undo = bytearray(compact_size(len(transactions) - 1))
+        for index, (txid, inputs, outputs) in enumerate(transactions):
+            if index > 0:
+                undo += compact_size(len(inputs))
+                for prevout in inputs:
+                    coin_height, coinbase, value, script = utxos.pop(prevout)
+                    undo += varint(coin_height * 2 + coinbase)
+                    if coin_height > 0:
+                        undo += b"\x00"
+                    undo += varint(compress_amount(value)) + compress_script(script)
+            for vout, (value, script) in enumerate(outputs):
+                utxos[(txid, vout)] = (height, int(index == 0), value, script)
+        # the genesis block is never connected and has no undo data
+        if height > 0:
+            out += MAGIC + struct.pack("<I", len(undo)) + undo
+            out += sha256d(block[4:36] + bytes(undo))
+        if height < MAX_BLOCK_HEIGHT:
+            block_hash, block = blocks[block_hash]
+    return bytes(out)
+
+
+if __name__ == "__main__":
+    rev = make_rev_file(read_blocks(f"{BLOCKS_PATH}/blk00000.dat"))
+    open(f"{BLOCKS_PATH}/rev00000.dat", "wb").write(rev)
@@ -11,7 +11,7 @@ fn parser() -> bitcoin_blockparser::parser::BlockchainParser {
 #[test]
 fn test_blockdata_parsing() {
     let mut storage = storage();
-    let genesis = storage.get_block(0).unwrap();
+    let genesis = storage.get_block(0).unwrap().unwrap();
     assert_eq!(
         genesis,
         bitcoin::blockdata::constants::genesis_block(bitcoin::network::constants::Network::Testnet)
@@ -21,7 +21,7 @@ fn test_blockdata_parsing() {
         "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943"
     );
     for height in 0..=120 {
-        let block = storage.get_block(height).unwrap();
+        let block = storage.get_block(height).unwrap().unwrap();
         assert_eq!(block.txdata.len(), 1);
     }
 }